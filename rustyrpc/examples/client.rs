@@ -2,7 +2,11 @@ mod common;
 
 use std::{sync::Arc, time::Duration};
 
-use common::{auth_service::AuthServiceClient, hello_service::HelloServiceClient};
+use common::{
+    auth_service::AuthServiceClient,
+    hello_service::{HelloService, HelloServiceCallback, HelloServiceClient, HelloServiceWrapper},
+};
+use futures::future;
 use log::{error, info};
 use quinn::ClientConfig;
 use rustyrpc::{
@@ -10,8 +14,13 @@ use rustyrpc::{
         Decode, DecodeZeroCopy, DecodeZeroCopyFallible, Encode, EncodingFormat,
         ZeroCopyEncodingFormat,
     },
-    protocol::{PrivateServiceDeallocateRequestResult, RequestKind, ServiceCallRequestResult},
-    transport, Client,
+    protocol::{
+        Credentials, PrivateServiceDeallocateRequestResult, RequestKind, ServiceCallRequestResult,
+        ServiceIdRequestResult,
+    },
+    server::ServiceRef,
+    service::IntoService,
+    transport, Client, RetryPolicy,
 };
 
 fn parse_args() -> (String, String) {
@@ -29,28 +38,91 @@ async fn main() {
 
     let (username, password) = parse_args();
 
-    let connection = transport::quic::ClientConnection::connect(
+    let connector = transport::quic::Connector::new(
         client_config(),
         "0.0.0.0:0".parse().unwrap(),
         "127.0.0.1:8888".parse().unwrap(),
         "localhost",
         1,
     )
-    .await
     .unwrap();
 
-    let client = Arc::new(Client::from(connection));
+    // Connection is authenticated by handshake, so server rejects it before any service is reachable.
+    let credentials = Credentials::Password {
+        username: &username,
+        password: &password,
+    };
+    let client =
+        match Client::connect_authenticated(connector, RetryPolicy::default(), credentials).await {
+            Ok(client) => Arc::new(client),
+            Err(err) => {
+                error!("Failed to connect: {err}");
+                return;
+            }
+        };
     let auth_service_client: AuthServiceClient<_, _> =
         client.clone().get_service_client().await.unwrap();
 
-    if let Some(hello_service_client) = auth_service_client.auth(&username, &password).await {
-        info!("Successful authentication");
-
-        start_healthcheck(hello_service_client).await;
+    // Hello is sent together with authentication request, so both of them take a single round trip.
+    let (hello_service_client, authenticated) = auth_service_client
+        .auth_pipelined(&username, &password)
+        .await
+        .unwrap();
+    let (authenticated, greeting) = tokio::join!(authenticated, hello_service_client.hello());
+
+    if authenticated.unwrap() {
+        info!("Successful authentication. Greeting: {}", greeting.unwrap());
+
+        // Server calls hello service hosted by client over the same connection. Client has single endpoint.
+        let greeter = client
+            .host_service(0, Box::new(ClientHelloServiceImpl.into_service()))
+            .await
+            .unwrap();
+        match hello_service_client.hello_back(&greeter).await {
+            Ok(reply) => info!("Server greeted back: {reply}"),
+            Err(err) => error!("Failed to ask server to greet back: {err}"),
+        }
+        client.unhost_service(0, greeter).await;
+
+        // Events published by server are received while healthcheck is running.
+        let mut uptime = client.subscribe::<u64>("uptime").await.unwrap();
+        let log_uptime = async {
+            while let Some(event) = uptime.next().await {
+                match event {
+                    Ok(seconds) => info!("Server uptime: {seconds}s"),
+                    Err(err) => {
+                        error!("Failed to receive server uptime: {err}");
+                        break;
+                    }
+                }
+            }
+            future::pending().await
+        };
+
+        // Notices are broadcast by server to all connected clients.
+        let mut notices = client.broadcasts::<String>().await;
+        let log_notices = async {
+            while let Some(notice) = notices.next().await {
+                match notice {
+                    Ok(text) => info!("Server notice: {text}"),
+                    Err(err) => error!("Failed to decode server notice: {err}"),
+                }
+            }
+            future::pending().await
+        };
+
+        // Clones share the same private service, which is deallocated once the last of them is dropped.
+        tokio::select! {
+            () = start_healthcheck(hello_service_client.clone()) => {}
+            () = hello_service_client.revoked() => error!("Hello service is revoked by server"),
+            () = log_uptime => {}
+            () = log_notices => {}
+        }
     } else {
         error!("Failed to authenticate: invalid username or password");
     }
-    tokio::time::sleep(Duration::from_secs(2)).await; // Waiting to allow HelloService deallocation request to be sent.
+    drop(hello_service_client);
+    client.close().await.unwrap();
 }
 
 async fn start_healthcheck<
@@ -66,6 +138,7 @@ async fn start_healthcheck<
         <ServiceCallRequestResult<'a> as DecodeZeroCopyFallible<Format>>::Error,
     >,
     PrivateServiceDeallocateRequestResult: Decode<Format>,
+    ServiceIdRequestResult: Decode<Format>,
     (): Encode<Format>,
     String: Decode<Format>,
 {
@@ -80,6 +153,30 @@ async fn start_healthcheck<
     }
 }
 
+struct ClientHelloServiceImpl;
+
+impl<Format: EncodingFormat> HelloService<Format> for ClientHelloServiceImpl
+where
+    String: Encode<Format>,
+    ServiceRef: Decode<Format>,
+{
+    async fn hello(&self) -> String {
+        "Hello from client".to_string()
+    }
+
+    async fn hello_back(&self, _greeter: HelloServiceCallback<Format>) -> String {
+        "Client doesn't greet back".to_string()
+    }
+}
+
+impl<Format: EncodingFormat> IntoService<Format> for ClientHelloServiceImpl
+where
+    String: Encode<Format>,
+    ServiceRef: Decode<Format>,
+{
+    type Wrapper = HelloServiceWrapper<Self, Format>;
+}
+
 struct SkipCertVerification;
 
 impl rustls::client::ServerCertVerifier for SkipCertVerification {
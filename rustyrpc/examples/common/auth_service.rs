@@ -1,4 +1,4 @@
-use std::{borrow::Cow, future::Future, marker::PhantomData, sync::Arc};
+use std::{borrow::Cow, future::Future, io, marker::PhantomData, sync::Arc};
 
 use async_trait::async_trait;
 use derive_where::derive_where;
@@ -10,19 +10,21 @@ use rustyrpc::{
     },
     multipart::{MultipartReceived, MultipartSendable},
     protocol::{
-        PrivateServiceDeallocateRequestResult, RequestKind, ServiceCallRequestError,
-        ServiceCallRequestResult, ServiceKind,
+        PrivateServiceDeallocateRequestResult, PrivateServiceLease, RequestKind,
+        ServiceCallRequestError, ServiceCallRequestResult, ServiceIdRequestResult,
     },
-    server::{PrivateServiceAllocator, ServiceRef},
+    server::{PrivateServiceAllocator, TypedServiceRef},
     service::{IntoService, Service, ServiceClient, ServiceMetadata, ServiceWrapper},
-    transport, Client,
+    transport, Client, SharedService,
 };
 
-use super::hello_service::HelloServiceClient;
+use super::hello_service::{HelloServiceClient, HelloServiceDescriptor};
 
 const SERVICE_NAME: &str = "Auth";
 const SERVICE_CHECKSUM: &[u8] = &[];
 
+pub type HelloServiceRef = TypedServiceRef<HelloServiceDescriptor>;
+
 #[derive(Serialize, Archive)]
 #[archive(check_bytes)]
 pub struct AuthRequest<'a> {
@@ -52,7 +54,7 @@ impl<T, Format> ServiceWrapper<T, Format> for AuthServiceWrapper<T, Format>
 where
     T: AuthService<Format>,
     Format: EncodingFormat,
-    Option<ServiceRef>: Encode<Format>,
+    Option<HelloServiceRef>: Encode<Format>,
 {
     fn wrap(to_wrap: T) -> Self {
         Self(to_wrap, PhantomData)
@@ -63,7 +65,7 @@ impl<T, Format> ServiceMetadata<Format> for AuthServiceWrapper<T, Format>
 where
     T: AuthService<Format>,
     Format: EncodingFormat,
-    Option<ServiceRef>: Encode<Format>,
+    Option<HelloServiceRef>: Encode<Format>,
 {
     const NAME: &'static str = SERVICE_NAME;
     const CHECKSUM: &'static [u8] = SERVICE_CHECKSUM;
@@ -74,7 +76,7 @@ impl<T, Format> Service<Format> for AuthServiceWrapper<T, Format>
 where
     T: AuthService<Format>,
     Format: EncodingFormat,
-    Option<ServiceRef>: Encode<Format>,
+    Option<HelloServiceRef>: Encode<Format>,
 {
     fn checksum(&self) -> std::borrow::Cow<'static, [u8]> {
         Cow::Borrowed(SERVICE_CHECKSUM)
@@ -95,7 +97,13 @@ where
 
         let service_response = self.0.auth(&request.username, &request.password).await;
         let service_ref = if let Some(service) = service_response {
-            Some(service_allocator.allocate(service).await)
+            service_allocator.mark_authenticated().await;
+            Some(
+                service_allocator
+                    .allocate_typed(service)
+                    .await
+                    .ok_or(ServiceCallRequestError::ServerInternal)?,
+            )
         } else {
             None
         };
@@ -110,8 +118,7 @@ where
 
 #[derive_where(Clone)]
 pub struct AuthServiceClient<Connection: transport::ClientConnection, Format: EncodingFormat> {
-    service_kind: ServiceKind,
-    service_id: usize,
+    service: SharedService,
     rpc_client: Arc<Client<Connection, Format>>,
 }
 
@@ -125,6 +132,8 @@ where
         <ServiceCallRequestResult<'a> as DecodeZeroCopyFallible<Format>>::Error,
     >,
     PrivateServiceDeallocateRequestResult: Decode<Format>,
+    PrivateServiceLease: Decode<Format>,
+    ServiceIdRequestResult: Decode<Format>,
 {
     pub async fn auth(
         &self,
@@ -133,22 +142,49 @@ where
     ) -> Option<HelloServiceClient<Connection, Format>>
     where
         for<'a> AuthRequest<'a>: Encode<Format>,
-        Option<ServiceRef>: Decode<Format>,
+        Option<HelloServiceRef>: Decode<Format>,
     {
         let request = AuthRequest { username, password };
-        let service_ref: Option<ServiceRef> = self
+        let service_ref: Option<HelloServiceRef> = self
             .rpc_client
-            .call_service(
-                self.service_kind,
-                self.service_id.try_into().unwrap(),
-                0,
-                &request,
-            )
+            .call_service(self.service.handle(), 0, &request)
             .await
             .unwrap();
 
         service_ref.map(|service_ref| service_ref.into_client(self.rpc_client.clone()).unwrap())
     }
+
+    /// Returns hello service client usable before authentication finishes and future resolving with whether authentication succeeded.
+    pub async fn auth_pipelined(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> io::Result<(
+        HelloServiceClient<Connection, Format>,
+        impl Future<Output = io::Result<bool>>,
+    )>
+    where
+        for<'a> AuthRequest<'a>: Encode<Format>,
+        Option<HelloServiceRef>: Decode<Format>,
+    {
+        let request = AuthRequest { username, password };
+        let (service, service_ref) = self
+            .rpc_client
+            .call_service_pipelined::<_, Option<HelloServiceRef>>(
+                self.service.handle(),
+                0,
+                &request,
+            )
+            .await?;
+
+        let hello_service_client = HelloServiceClient::new(
+            self.rpc_client.share_service(service),
+            self.rpc_client.clone(),
+        );
+        Ok((hello_service_client, async move {
+            Ok(service_ref.receive().await?.is_some())
+        }))
+    }
 }
 
 impl<Connection: transport::ClientConnection, Format: EncodingFormat>
@@ -157,14 +193,9 @@ impl<Connection: transport::ClientConnection, Format: EncodingFormat>
     const SERVICE_NAME: &'static str = SERVICE_NAME;
     const SERVICE_CHECKSUM: &'static [u8] = SERVICE_CHECKSUM;
 
-    fn new(
-        service_kind: ServiceKind,
-        service_id: usize,
-        rpc_client: Arc<Client<Connection, Format>>,
-    ) -> Self {
+    fn new(service: SharedService, rpc_client: Arc<Client<Connection, Format>>) -> Self {
         Self {
-            service_kind,
-            service_id,
+            service,
             rpc_client,
         }
     }
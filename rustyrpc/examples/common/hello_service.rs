@@ -7,19 +7,66 @@ use rustyrpc::{
     },
     multipart::{MultipartReceived, MultipartSendable},
     protocol::{
-        PrivateServiceDeallocateRequestResult, RequestKind, ServiceCallRequestError,
-        ServiceCallRequestResult, ServiceKind,
+        PrivateServiceDeallocateRequestResult, RequestKind, RevokedServices,
+        ServiceCallRequestError, ServiceCallRequestResult, ServiceIdRequestResult,
     },
-    server::PrivateServiceAllocator,
-    service::{IntoService, Service, ServiceClient, ServiceMetadata, ServiceWrapper},
-    transport, Client,
+    server::{ClientServices, PrivateServiceAllocator, ServiceRef},
+    service::{
+        DescribedClient, IntoService, Service, ServiceClient, ServiceDescriptor, ServiceMetadata,
+        ServiceWrapper,
+    },
+    transport, Client, SharedService,
 };
 
 const SERVICE_NAME: &str = "Hello";
 const SERVICE_CHECKSUM: &[u8] = &[];
 
+pub struct HelloServiceDescriptor;
+
+impl ServiceDescriptor for HelloServiceDescriptor {
+    const NAME: &'static str = SERVICE_NAME;
+    const CHECKSUM: &'static [u8] = SERVICE_CHECKSUM;
+}
+
+impl<Connection: transport::ClientConnection, Format: ZeroCopyEncodingFormat>
+    DescribedClient<Connection, Format> for HelloServiceDescriptor
+where
+    for<'a> RequestKind<'a>: Encode<Format>,
+    for<'a> ServiceCallRequestResult<'a>: DecodeZeroCopy<
+        'a,
+        Format,
+        <ServiceCallRequestResult<'a> as DecodeZeroCopyFallible<Format>>::Error,
+    >,
+    PrivateServiceDeallocateRequestResult: Decode<Format>,
+    ServiceIdRequestResult: Decode<Format>,
+{
+    type Client = HelloServiceClient<Connection, Format>;
+}
+
 pub trait HelloService<Format: EncodingFormat>: IntoService<Format> + Send + Sync {
     fn hello(&self) -> impl Future<Output = String> + Send;
+
+    /// Greets back hello service hosted by client.
+    fn hello_back(
+        &self,
+        greeter: HelloServiceCallback<Format>,
+    ) -> impl Future<Output = String> + Send;
+}
+
+/// Hello service hosted by client, called by server.
+pub struct HelloServiceCallback<Format: EncodingFormat> {
+    client_services: ClientServices<Format>,
+    service: ServiceRef,
+}
+
+impl<Format: EncodingFormat> HelloServiceCallback<Format> {
+    pub async fn hello(&self) -> io::Result<String>
+    where
+        (): Encode<Format>,
+        String: Decode<Format>,
+    {
+        self.client_services.call(&self.service, 0, &()).await
+    }
 }
 
 pub struct HelloServiceWrapper<T: HelloService<Format>, Format: EncodingFormat>(
@@ -31,6 +78,8 @@ impl<T, Format> ServiceWrapper<T, Format> for HelloServiceWrapper<T, Format>
 where
     T: HelloService<Format>,
     Format: EncodingFormat,
+    ServiceRef: Decode<Format>,
+    String: Encode<Format>,
 {
     fn wrap(to_wrap: T) -> Self {
         Self(to_wrap, PhantomData)
@@ -41,6 +90,8 @@ impl<T, Format> ServiceMetadata<Format> for HelloServiceWrapper<T, Format>
 where
     T: HelloService<Format> + Send + Sync,
     Format: EncodingFormat,
+    ServiceRef: Decode<Format>,
+    String: Encode<Format>,
 {
     const NAME: &'static str = SERVICE_NAME;
     const CHECKSUM: &'static [u8] = SERVICE_CHECKSUM;
@@ -51,6 +102,8 @@ impl<T, Format> Service<Format> for HelloServiceWrapper<T, Format>
 where
     T: HelloService<Format> + Send + Sync,
     Format: EncodingFormat,
+    ServiceRef: Decode<Format>,
+    String: Encode<Format>,
 {
     fn checksum(&self) -> Cow<'static, [u8]> {
         Cow::Borrowed(SERVICE_CHECKSUM)
@@ -58,19 +111,30 @@ where
 
     async fn call(
         &self,
-        _service_allocator: Arc<PrivateServiceAllocator<Format>>,
+        service_allocator: Arc<PrivateServiceAllocator<Format>>,
         function_id: u32,
-        _args: MultipartReceived,
+        args: MultipartReceived,
     ) -> Result<MultipartSendable, ServiceCallRequestError> {
-        if function_id != 0 {
-            return Err(ServiceCallRequestError::InvalidFunctionId);
-        }
+        let message = match function_id {
+            0 => self.0.hello().await,
+            1 => {
+                let service = <ServiceRef as Decode<Format>>::decode(&args)
+                    .map_err(|_| ServiceCallRequestError::ArgsDecode)?;
+                let client_services = service_allocator
+                    .client_services()
+                    .ok_or(ServiceCallRequestError::ServerInternal)?
+                    .clone();
 
-        let message = self
-            .0
-            .hello()
-            .await
-            .encode()
+                self.0
+                    .hello_back(HelloServiceCallback {
+                        client_services,
+                        service,
+                    })
+                    .await
+            }
+            _ => return Err(ServiceCallRequestError::InvalidFunctionId),
+        };
+        let message = Encode::<Format>::encode(&message)
             .map_err(|_| ServiceCallRequestError::ServerInternal)?;
 
         Ok(MultipartSendable::from([message]))
@@ -88,12 +152,32 @@ pub struct HelloServiceClient<
         <ServiceCallRequestResult<'a> as DecodeZeroCopyFallible<Format>>::Error,
     >,
     PrivateServiceDeallocateRequestResult: Decode<Format>,
+    ServiceIdRequestResult: Decode<Format>,
 {
-    service_kind: ServiceKind,
-    service_id: usize,
+    service: SharedService,
     rpc_client: Arc<Client<Connection, Format>>,
 }
 
+impl<Connection: transport::ClientConnection, Format: ZeroCopyEncodingFormat> Clone
+    for HelloServiceClient<Connection, Format>
+where
+    for<'a> RequestKind<'a>: Encode<Format>,
+    for<'a> ServiceCallRequestResult<'a>: DecodeZeroCopy<
+        'a,
+        Format,
+        <ServiceCallRequestResult<'a> as DecodeZeroCopyFallible<Format>>::Error,
+    >,
+    PrivateServiceDeallocateRequestResult: Decode<Format>,
+    ServiceIdRequestResult: Decode<Format>,
+{
+    fn clone(&self) -> Self {
+        Self {
+            service: self.service.clone(),
+            rpc_client: Arc::clone(&self.rpc_client),
+        }
+    }
+}
+
 impl<Connection: transport::ClientConnection, Format: ZeroCopyEncodingFormat>
     ServiceClient<Connection, Format> for HelloServiceClient<Connection, Format>
 where
@@ -104,18 +188,14 @@ where
         <ServiceCallRequestResult<'a> as DecodeZeroCopyFallible<Format>>::Error,
     >,
     PrivateServiceDeallocateRequestResult: Decode<Format>,
+    ServiceIdRequestResult: Decode<Format>,
 {
     const SERVICE_NAME: &'static str = SERVICE_NAME;
     const SERVICE_CHECKSUM: &'static [u8] = SERVICE_CHECKSUM;
 
-    fn new(
-        service_kind: ServiceKind,
-        service_id: usize,
-        rpc_client: Arc<Client<Connection, Format>>,
-    ) -> Self {
+    fn new(service: SharedService, rpc_client: Arc<Client<Connection, Format>>) -> Self {
         Self {
-            service_kind,
-            service_id,
+            service,
             rpc_client,
         }
     }
@@ -131,6 +211,7 @@ where
         <ServiceCallRequestResult<'a> as DecodeZeroCopyFallible<Format>>::Error,
     >,
     PrivateServiceDeallocateRequestResult: Decode<Format>,
+    ServiceIdRequestResult: Decode<Format>,
 {
     pub async fn hello(&self) -> io::Result<String>
     where
@@ -138,37 +219,27 @@ where
         String: Decode<Format>,
     {
         self.rpc_client
-            .call_service(
-                self.service_kind,
-                self.service_id.try_into().unwrap(),
-                0,
-                &(),
-            )
+            .call_service(self.service.handle(), 0, &())
             .await
     }
-}
 
-impl<Connection: transport::ClientConnection, Format: ZeroCopyEncodingFormat> Drop
-    for HelloServiceClient<Connection, Format>
-where
-    for<'a> RequestKind<'a>: Encode<Format>,
-    for<'a> ServiceCallRequestResult<'a>: DecodeZeroCopy<
-        'a,
-        Format,
-        <ServiceCallRequestResult<'a> as DecodeZeroCopyFallible<Format>>::Error,
-    >,
-    PrivateServiceDeallocateRequestResult: Decode<Format>,
-{
-    fn drop(&mut self) {
-        if let ServiceKind::Private = self.service_kind {
-            let rpc_client = Arc::clone(&self.rpc_client);
-            let service_id = self.service_id.try_into().unwrap();
-            tokio::spawn(async move {
-                rpc_client
-                    .deallocate_private_service(service_id)
-                    .await
-                    .unwrap();
-            });
-        }
+    /// Asks server to greet back hello service hosted by client.
+    pub async fn hello_back(&self, greeter: &ServiceRef) -> io::Result<String>
+    where
+        ServiceRef: Encode<Format>,
+        String: Decode<Format>,
+    {
+        self.rpc_client
+            .call_service(self.service.handle(), 1, greeter)
+            .await
+    }
+
+    /// Resolves once server revokes hello service or connection to it is lost.
+    pub async fn revoked(&self)
+    where
+        Format: 'static,
+        RevokedServices: Decode<Format>,
+    {
+        self.rpc_client.revoked(self.service.handle()).await;
     }
 }
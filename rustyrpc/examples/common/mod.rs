@@ -1,2 +1,5 @@
+// Shared by client and server examples, each of them uses only its own part.
+#![allow(dead_code)]
+
 pub mod auth_service;
 pub mod hello_service;
@@ -1,19 +1,26 @@
 mod common;
 
-use std::sync::Arc;
+use std::{io, sync::Arc, time::Duration};
 
 use common::{
-    auth_service::{AuthService, AuthServiceWrapper},
-    hello_service::{HelloService, HelloServiceWrapper},
+    auth_service::{AuthService, AuthServiceWrapper, HelloServiceRef},
+    hello_service::{HelloService, HelloServiceCallback, HelloServiceWrapper},
 };
 use quinn::ServerConfig;
 use rustyrpc::{
-    format::{rkyv::RkyvFormat, Encode, EncodingFormat},
-    server::{Server, ServerBuilder, ServiceRef},
+    format::{rkyv::RkyvFormat, Decode, Encode, EncodingFormat},
+    protocol::{AuthenticationError, Credentials},
+    server::{
+        Authenticator, ConcurrencyLimits, ConcurrencyOverflow, ConnectionHooks, ConnectionInfo,
+        ConnectionRejectedError, Identity, LoadShedding, Policy, RateLimit, RateLimitKey, Server,
+        ServerBuilder, ServiceRef, Topics,
+    },
     service::IntoService,
     transport,
 };
 
+const UPTIME_TOPIC: &str = "uptime";
+
 #[tokio::main]
 async fn main() {
     pretty_env_logger::init();
@@ -23,14 +30,68 @@ async fn main() {
         transport::quic::ConnectionListener::new(server_config, "127.0.0.1:8888".parse().unwrap())
             .unwrap();
 
+    let topics = Arc::new(Topics::default().with_topic(UPTIME_TOPIC));
+    tokio::spawn(publish_uptime(Arc::clone(&topics)));
+
     let server: Arc<Server<_, RkyvFormat>> = ServerBuilder::default()
         .with_service(AuthServiceImpl)
+        .with_private_service_lease(Duration::from_secs(30))
+        .with_max_private_services(1024)
+        .with_topics(topics)
+        .with_connection_hooks(LoggingHooks)
+        .with_authenticator(PasswordAuthenticator)
+        .with_service_policy("Auth", Policy::roles(&["admin"]))
+        .with_rate_limit(
+            RateLimitKey::Identity,
+            RateLimit::new(100, Duration::from_secs(1)),
+        )
+        .with_load_shedding(LoadShedding::default())
+        .with_concurrency_limits(
+            ConcurrencyLimits::default()
+                .with_max_calls_per_connection(64)
+                .with_max_calls(1024)
+                .with_overflow(ConcurrencyOverflow::Queue(256)),
+        )
         .build(listener)
+        .unwrap()
         .into();
+    tokio::spawn(announce_maintenance(Arc::clone(&server)));
 
     server.listen().await;
 }
 
+/// Publishes seconds passed since server start to every subscribed client.
+async fn publish_uptime(topics: Arc<Topics<RkyvFormat>>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    for seconds in 0u64.. {
+        interval.tick().await;
+        if let Err(err) = topics.publish(UPTIME_TOPIC, &seconds) {
+            log::error!("Failed to publish uptime: {err}");
+        }
+    }
+}
+
+/// Periodically notifies every connected client about upcoming maintenance.
+async fn announce_maintenance(
+    server: Arc<Server<transport::quic::ConnectionListener, RkyvFormat>>,
+) {
+    let mut interval = tokio::time::interval(Duration::from_secs(2));
+    loop {
+        interval.tick().await;
+        let notice = "Maintenance is planned for tonight".to_owned();
+        match server.broadcast(&notice).await {
+            Ok(deliveries) => {
+                for (connection_id, delivery) in deliveries {
+                    if let Err(err) = delivery {
+                        log::warn!("Failed to notify client {connection_id}: {err}");
+                    }
+                }
+            }
+            Err(err) => log::error!("Failed to broadcast maintenance notice: {err}"),
+        }
+    }
+}
+
 fn generate_server_config() -> ServerConfig {
     let cert = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
     let cert_chain = vec![rustls::Certificate(cert.serialize_der().unwrap())];
@@ -40,20 +101,84 @@ fn generate_server_config() -> ServerConfig {
     ServerConfig::with_single_cert(cert_chain, private_key).unwrap()
 }
 
+const USERNAME: &str = "admin";
+const PASSWORD: &str = "admin";
+
+/// Accepts only clients presenting username and password of admin.
+struct PasswordAuthenticator;
+
+#[async_trait::async_trait]
+impl Authenticator for PasswordAuthenticator {
+    async fn authenticate(
+        &self,
+        _connection: &ConnectionInfo,
+        credentials: Credentials<'_>,
+    ) -> Result<Identity, AuthenticationError> {
+        match credentials {
+            Credentials::Password { username, password }
+                if username == USERNAME && password == PASSWORD =>
+            {
+                Ok(Identity::new(username).with_role("admin"))
+            }
+            Credentials::Password { .. } | Credentials::Token(_) | Credentials::Transport => {
+                Err(AuthenticationError)
+            }
+        }
+    }
+}
+
+/// Logs lifecycle of client connections.
+struct LoggingHooks;
+
+#[async_trait::async_trait]
+impl ConnectionHooks for LoggingHooks {
+    async fn on_accept(&self, connection: &ConnectionInfo) -> Result<(), ConnectionRejectedError> {
+        log::info!("Client connected. Connection id: {}", connection.id());
+        if let Some(subject) = connection
+            .peer_identity()
+            .and_then(transport::PeerIdentity::subject)
+        {
+            log::info!("Client presented certificate of {subject}");
+        }
+        Ok(())
+    }
+
+    async fn on_authenticated(&self, connection: &ConnectionInfo) {
+        let name = connection.identity().map_or("unknown", Identity::name);
+        log::info!(
+            "Client authenticated as {name}. Connection id: {}",
+            connection.id()
+        );
+    }
+
+    async fn on_close(
+        &self,
+        connection: &ConnectionInfo,
+        reason: &io::Error,
+        private_services: &[u64],
+    ) {
+        log::info!(
+            "Client disconnected: {reason}. Connection id: {}, private services left: {}",
+            connection.id(),
+            private_services.len()
+        );
+    }
+}
+
 struct AuthServiceImpl;
 
 impl<Format: EncodingFormat> AuthService<Format> for AuthServiceImpl
 where
-    Option<ServiceRef>: Encode<Format>,
+    Option<HelloServiceRef>: Encode<Format>,
+    (): Encode<Format>,
+    String: Decode<Format> + Encode<Format>,
+    ServiceRef: Decode<Format>,
 {
     async fn auth(
         &self,
         username: &str,
         password: &str,
     ) -> Option<Box<dyn rustyrpc::service::Service<Format>>> {
-        const USERNAME: &str = "admin";
-        const PASSWORD: &str = "admin";
-
         if username == USERNAME && password == PASSWORD {
             Some(Box::new(HelloServiceImpl.into_service()))
         } else {
@@ -64,19 +189,39 @@ where
 
 impl<Format: EncodingFormat> IntoService<Format> for AuthServiceImpl
 where
-    Option<ServiceRef>: Encode<Format>,
+    Option<HelloServiceRef>: Encode<Format>,
+    (): Encode<Format>,
+    String: Decode<Format> + Encode<Format>,
+    ServiceRef: Decode<Format>,
 {
     type Wrapper = AuthServiceWrapper<Self, Format>;
 }
 
 struct HelloServiceImpl;
 
-impl<Format: EncodingFormat> HelloService<Format> for HelloServiceImpl {
+impl<Format: EncodingFormat> HelloService<Format> for HelloServiceImpl
+where
+    (): Encode<Format>,
+    String: Decode<Format> + Encode<Format>,
+    ServiceRef: Decode<Format>,
+{
     async fn hello(&self) -> String {
         "Hello from server".to_string()
     }
+
+    async fn hello_back(&self, greeter: HelloServiceCallback<Format>) -> String {
+        match greeter.hello().await {
+            Ok(greeting) => format!("Server received: {greeting}"),
+            Err(err) => format!("Failed to greet back: {err}"),
+        }
+    }
 }
 
-impl<Format: EncodingFormat> IntoService<Format> for HelloServiceImpl {
+impl<Format: EncodingFormat> IntoService<Format> for HelloServiceImpl
+where
+    (): Encode<Format>,
+    String: Decode<Format> + Encode<Format>,
+    ServiceRef: Decode<Format>,
+{
     type Wrapper = HelloServiceWrapper<Self, Format>;
 }
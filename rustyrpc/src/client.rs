@@ -1,27 +1,96 @@
-use alloc::sync::Arc;
-use core::marker::PhantomData;
-use core::ops::DerefMut;
+mod broadcasts;
+mod call_batch;
+mod call_options;
+mod connection;
+mod credentials;
+mod deallocation_queue;
+mod hosted_services;
+mod leased_services;
+mod load_balancer;
+mod origin;
+mod pending_returns;
+mod retry;
+mod revocations;
+mod service_handle;
+mod service_lease;
+mod shared_service;
+mod subscription;
+
+use alloc::sync::{Arc, Weak};
+use core::{marker::PhantomData, time::Duration};
+use futures::future;
+use log::{trace, warn};
 use std::io;
-use tokio::sync::Mutex;
+use thiserror::Error;
+use tokio::{sync::watch, time::Instant};
 
+use self::{
+    call_batch::BatchEntry,
+    connection::{ActiveConnection, ConnectionManager, Handshake, IncomingStreamHandler},
+    credentials::OwnedCredentials,
+    deallocation_queue::DeallocationQueue,
+    hosted_services::HostedServices,
+    leased_services::LeasedServices,
+    retry::{CallAttempt, CallAttemptError},
+};
 use crate::{
     format::{self, Decode, DecodeZeroCopy, DecodeZeroCopyFallible, Encode, EncodingFormat},
     multipart::{MultipartReceived, MultipartSendable},
     protocol::{
-        PrivateServiceDeallocateRequestResult, RequestKind, ServiceCallRequestResult,
-        ServiceIdRequestResult, ServiceKind,
+        AuthenticateRequestResult, BatchedCall, BroadcastRequestResult, Credentials,
+        InvalidPrivateServiceIdError, PrivateServiceDeallocateRequestResult,
+        PrivateServiceDuplicateRequestResult, PrivateServiceLease,
+        PrivateServiceRenewRequestResult, RequestKind, RevokedServices, ServiceCallRequestResult,
+        ServiceIdRequestResult, ServiceIdsRequestResult, ServiceKind, ServiceQuery,
+        TopicSubscribeRequestResult,
     },
-    service::ServiceClient,
+    server::{CallStream, Deallocated, ServiceRef},
+    service::{Service, ServiceClient},
     transport::{self, Stream, StreamExt},
-    utils::{ConnectionCloseOnDrop, DropOwned},
 };
+pub(crate) use origin::ConnectionOrigin;
+
+pub use broadcasts::Broadcasts;
+pub use call_batch::CallBatch;
+pub use call_options::CallOptions;
+pub use connection::ConnectionState;
+pub use load_balancer::{ConsistentHash, EndpointInfo, LeastOutstanding, LoadBalancer, RoundRobin};
+pub use pending_returns::{PendingReturns, PromisingReturns};
+pub use retry::RetryPolicy;
+pub use service_handle::ServiceHandle;
+pub use service_lease::ServiceLease;
+pub use shared_service::SharedService;
+pub use subscription::Subscription;
+
+/// Error that occurs on call of private service allocated on connection which is already lost.
+#[derive(Error, Debug)]
+#[error("Private service was lost together with connection it was allocated on")]
+pub struct PrivateServiceLostError;
+
+impl From<PrivateServiceLostError> for io::Error {
+    fn from(error: PrivateServiceLostError) -> Self {
+        io::Error::new(io::ErrorKind::NotFound, error)
+    }
+}
 
 /// RPC client for calling remote services.
+///
+/// Client may be connected to several endpoints (e.g. replicas of the same server).
+/// Calls of public services are distributed between them by [load balancer][LoadBalancer],
+/// while calls of private services are sent to endpoint that allocated them.
 pub struct Client<Connection: transport::ClientConnection, Format: format::EncodingFormat> {
-    connection: Mutex<DropOwned<ConnectionCloseOnDrop<Connection>>>,
+    endpoints: Box<[Arc<ConnectionManager<Connection>>]>,
+    load_balancer: Box<dyn LoadBalancer>,
+    retry_policy: RetryPolicy,
+    leased_services: Arc<LeasedServices>,
+    deallocation_queue: DeallocationQueue,
+    hosted_services: Box<[HostedServices<Format>]>,
     _format: PhantomData<Format>,
 }
 
+/// Leases are renewed when this part of lease is passed, so renewal arrives in time despite delays.
+const LEASE_RENEWAL_FRACTION: u32 = 3;
+
 impl<Connection: transport::ClientConnection, Format: format::ZeroCopyEncodingFormat>
     Client<Connection, Format>
 where
@@ -31,40 +100,347 @@ where
         Format,
         <ServiceCallRequestResult<'a> as DecodeZeroCopyFallible<Format>>::Error,
     >,
+    ServiceIdRequestResult: Decode<Format>,
 {
-    async fn new_stream(&self) -> io::Result<Connection::Stream> {
-        let mut transport_connection = self.connection.lock().await;
-        transport_connection.deref_mut().0.new_stream().await
+    /// Establishes connection with specified connector. Unlike client created from connection,
+    /// it connects again with backoff of reconnect policy when connection is lost.
+    ///
+    /// # Errors
+    /// Returns an error if initial connection fails.
+    pub async fn connect<Connector>(
+        connector: Connector,
+        reconnect_policy: RetryPolicy,
+    ) -> io::Result<Self>
+    where
+        Connector: transport::Connector<Connection = Connection>,
+    {
+        Self::connect_many([connector], reconnect_policy).await
+    }
+
+    /// Establishes connections to several endpoints with specified connectors, reconnecting to each of them on connection loss.
+    /// Calls are distributed between endpoints in turn unless other [load balancer][Client::with_load_balancer] is set.
+    ///
+    /// # Errors
+    /// Returns an error if no connectors specified or initial connection to any of endpoints fails.
+    pub async fn connect_many<Connectors>(
+        connectors: Connectors,
+        reconnect_policy: RetryPolicy,
+    ) -> io::Result<Self>
+    where
+        Connectors: IntoIterator,
+        Connectors::Item: transport::Connector<Connection = Connection>,
+    {
+        Self::connect_endpoints(connectors, reconnect_policy, None).await
+    }
+
+    /// Establishes connection like [`connect`][Self::connect] and authenticates it with credentials,
+    /// so server attaches identity of client to connection. Connections established after reconnection are authenticated too.
+    ///
+    /// # Errors
+    /// Returns an error if initial connection fails or server rejects credentials.
+    pub async fn connect_authenticated<Connector>(
+        connector: Connector,
+        reconnect_policy: RetryPolicy,
+        credentials: Credentials<'_>,
+    ) -> io::Result<Self>
+    where
+        Connector: transport::Connector<Connection = Connection>,
+        Format: 'static,
+        AuthenticateRequestResult: Decode<Format>,
+    {
+        Self::connect_many_authenticated([connector], reconnect_policy, credentials).await
+    }
+
+    /// Establishes connections to several endpoints like [`connect_many`][Self::connect_many] and authenticates each of them with credentials.
+    ///
+    /// # Errors
+    /// Returns an error if no connectors specified, initial connection to any of endpoints fails or server rejects credentials.
+    pub async fn connect_many_authenticated<Connectors>(
+        connectors: Connectors,
+        reconnect_policy: RetryPolicy,
+        credentials: Credentials<'_>,
+    ) -> io::Result<Self>
+    where
+        Connectors: IntoIterator,
+        Connectors::Item: transport::Connector<Connection = Connection>,
+        Format: 'static,
+        AuthenticateRequestResult: Decode<Format>,
+    {
+        let credentials = Arc::new(OwnedCredentials::from(credentials));
+        let handshake: Handshake<Connection> = Arc::new(move |mut stream| {
+            let credentials = Arc::clone(&credentials);
+            Box::pin(async move {
+                let request = RequestKind::Authenticate {
+                    credentials: credentials.as_credentials(),
+                };
+                stream.send_encodable::<_, Format>(&request).await?;
+                stream.flush().await?;
+
+                Ok(stream
+                    .receive_decodable::<AuthenticateRequestResult, Format>()
+                    .await??)
+            })
+        });
+
+        Self::connect_endpoints(connectors, reconnect_policy, Some(handshake)).await
+    }
+
+    async fn connect_endpoints<Connectors>(
+        connectors: Connectors,
+        reconnect_policy: RetryPolicy,
+        handshake: Option<Handshake<Connection>>,
+    ) -> io::Result<Self>
+    where
+        Connectors: IntoIterator,
+        Connectors::Item: transport::Connector<Connection = Connection>,
+    {
+        let endpoints = future::try_join_all(connectors.into_iter().enumerate().map(
+            |(endpoint, connector)| {
+                ConnectionManager::connect(
+                    connector,
+                    reconnect_policy.clone(),
+                    endpoint,
+                    handshake.clone(),
+                )
+            },
+        ))
+        .await?;
+
+        if endpoints.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "At least one endpoint is required",
+            ));
+        }
+
+        Ok(Self {
+            load_balancer: Box::<RoundRobin>::default(),
+            retry_policy: RetryPolicy::default(),
+            leased_services: Arc::default(),
+            deallocation_queue: DeallocationQueue::default(),
+            hosted_services: HostedServices::for_endpoints(endpoints.len()),
+            endpoints: endpoints.into_boxed_slice(),
+            _format: PhantomData,
+        })
+    }
+
+    /// Sets strategy of distributing calls of public services between endpoints.
+    #[must_use]
+    pub fn with_load_balancer<Balancer: LoadBalancer>(mut self, load_balancer: Balancer) -> Self {
+        self.load_balancer = Box::new(load_balancer);
+        self
+    }
+
+    /// Sets retry policy used for idempotent calls without their own policy.
+    #[must_use]
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
     }
 
     /// Retrieves a service specified by service client.
+    /// Service id is requested once per connection and reused until connection is lost.
     ///
     /// # Errors
     /// Returns an error if service request fails.
     pub async fn get_service_client<T>(self: Arc<Self>) -> io::Result<T>
     where
-        ServiceIdRequestResult: Decode<Format>,
         T: ServiceClient<Connection, Format>,
+        Connection: 'static,
+        Format: 'static,
+        PrivateServiceDeallocateRequestResult: Decode<Format>,
+        PrivateServiceLease: Decode<Format>,
     {
+        let connection = self.pick_endpoint(None)?.current().await?;
         let service_id = self
-            .request_service(T::SERVICE_NAME, T::SERVICE_CHECKSUM)
+            .resolve_public_service_id(&connection, T::SERVICE_NAME, T::SERVICE_CHECKSUM)
             .await?;
-        let service_id = service_id
-            .try_into()
-            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let service = ServiceHandle::public(
+            T::SERVICE_NAME,
+            T::SERVICE_CHECKSUM,
+            service_id,
+            connection.origin(),
+        );
 
-        Ok(T::new(ServiceKind::Public, service_id, self))
+        Ok(T::new(self.share_service(service), self))
     }
 
     /// Retrieves a service. It's different from [`get_service_client`][Client::get_service_client], because just returns received service id except client
     ///
     /// # Errors
     /// Returns an error if service request fails.
-    pub async fn request_service<'a>(&self, name: &'a str, checksum: &'a [u8]) -> io::Result<u32>
+    pub async fn request_service<'a>(&self, name: &'a str, checksum: &'a [u8]) -> io::Result<u32> {
+        let connection = self.pick_endpoint(None)?.current().await?;
+        Self::request_service_on(&connection, name, checksum).await
+    }
+
+    /// Retrieves several services with a single request. Results are returned in order of requested services.
+    ///
+    /// # Errors
+    /// Returns an error if request fails as a whole, errors of finding particular services are returned in results.
+    pub async fn request_services(
+        &self,
+        services: &[ServiceQuery<'_>],
+    ) -> io::Result<Vec<io::Result<u32>>>
     where
-        ServiceIdRequestResult: Decode<Format>,
+        ServiceIdsRequestResult: Decode<Format>,
     {
-        let mut request_stream = self.new_stream().await?;
+        let connection = self.pick_endpoint(None)?.current().await?;
+        Self::request_services_on(&connection, services).await
+    }
+
+    /// Finds several public services on connections to all endpoints with a single request per connection,
+    /// so following [`get_service_client`][Client::get_service_client] calls don't wait for round trip to server.
+    ///
+    /// # Errors
+    /// Returns an error if request fails or any of services isn't found.
+    pub async fn prefetch_services(&self, services: &[ServiceQuery<'static>]) -> io::Result<()>
+    where
+        ServiceIdsRequestResult: Decode<Format>,
+    {
+        future::try_join_all(self.endpoints.iter().map(|endpoint| async move {
+            let connection = endpoint.current().await?;
+
+            let mut missing = Vec::with_capacity(services.len());
+            for service in services {
+                if connection
+                    .public_service_id(service.name, service.checksum)
+                    .await
+                    .is_none()
+                {
+                    missing.push(*service);
+                }
+            }
+            if missing.is_empty() {
+                return Ok(());
+            }
+
+            let service_ids = Self::request_services_on(&connection, &missing).await?;
+            for (service, service_id) in missing.iter().zip(service_ids) {
+                connection
+                    .remember_public_service_id(service.name, service.checksum, service_id?)
+                    .await;
+            }
+
+            Ok::<_, io::Error>(())
+        }))
+        .await?;
+
+        Ok(())
+    }
+
+    fn pick_endpoint(&self, key: Option<u64>) -> io::Result<&ConnectionManager<Connection>> {
+        self.pick_endpoint_excluding(key, &[])
+    }
+
+    /// Picks endpoint with load balancer among endpoints that aren't excluded. Excluded endpoints are considered only if no others are left.
+    fn pick_endpoint_excluding(
+        &self,
+        key: Option<u64>,
+        excluded: &[usize],
+    ) -> io::Result<&ConnectionManager<Connection>> {
+        let mut indices: Vec<usize> = (0..self.endpoints.len())
+            .filter(|index| !excluded.contains(index))
+            .collect();
+        if indices.is_empty() {
+            indices = (0..self.endpoints.len()).collect();
+        }
+        let endpoints_info: Vec<EndpointInfo> = indices
+            .iter()
+            .filter_map(|index| self.endpoints.get(*index))
+            .map(|endpoint| endpoint.info())
+            .collect();
+        let picked = self.load_balancer.pick(&endpoints_info, key);
+
+        indices
+            .get(picked)
+            .and_then(|endpoint| self.endpoints.get(*endpoint))
+            .map(AsRef::as_ref)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Load balancer picked nonexistent endpoint",
+                )
+            })
+    }
+
+    /// Returns endpoint that allocated private service or endpoint picked by load balancer for public service.
+    fn endpoint_for(
+        &self,
+        service: ServiceHandle,
+        options: &CallOptions,
+    ) -> io::Result<&ConnectionManager<Connection>> {
+        self.endpoint_excluding(service, options, &[])
+    }
+
+    /// Like [`Self::endpoint_for`], but public service is called on endpoint that isn't excluded if possible.
+    fn endpoint_excluding(
+        &self,
+        service: ServiceHandle,
+        options: &CallOptions,
+        excluded: &[usize],
+    ) -> io::Result<&ConnectionManager<Connection>> {
+        if service.public_service().is_some() {
+            self.pick_endpoint_excluding(options.routing_key(), excluded)
+        } else {
+            self.endpoints
+                .get(service.origin().endpoint)
+                .map(AsRef::as_ref)
+                .ok_or_else(|| PrivateServiceLostError.into())
+        }
+    }
+
+    async fn request_public_service_id(
+        &self,
+        connection: &ActiveConnection<Connection>,
+        name: &'static str,
+        checksum: &'static [u8],
+    ) -> io::Result<u32> {
+        let service_id = Self::request_service_on(connection, name, checksum).await?;
+        connection
+            .remember_public_service_id(name, checksum, service_id)
+            .await;
+
+        Ok(service_id)
+    }
+
+    /// Returns id of public service on specified connection, requesting it only if it isn't found yet.
+    async fn resolve_public_service_id(
+        &self,
+        connection: &ActiveConnection<Connection>,
+        name: &'static str,
+        checksum: &'static [u8],
+    ) -> io::Result<u32> {
+        if let Some(service_id) = connection.public_service_id(name, checksum).await {
+            Ok(service_id)
+        } else {
+            self.request_public_service_id(connection, name, checksum)
+                .await
+        }
+    }
+
+    /// Returns id of service on specified connection, finding public services again if handle was obtained on previous connection.
+    async fn resolve_service_id(
+        &self,
+        connection: &ActiveConnection<Connection>,
+        service: ServiceHandle,
+    ) -> io::Result<u64> {
+        if service.origin() == connection.origin() {
+            return Ok(service.id());
+        }
+
+        let (name, checksum) = service.public_service().ok_or(PrivateServiceLostError)?;
+        self.resolve_public_service_id(connection, name, checksum)
+            .await
+            .map(u64::from)
+    }
+
+    async fn request_service_on(
+        connection: &ActiveConnection<Connection>,
+        name: &str,
+        checksum: &[u8],
+    ) -> io::Result<u32> {
+        let mut request_stream = connection.new_stream().await?;
 
         let request = RequestKind::ServiceId { name, checksum };
         request_stream.send_encodable(&request).await?;
@@ -76,38 +452,228 @@ where
         Ok(service_id.0)
     }
 
+    async fn request_services_on(
+        connection: &ActiveConnection<Connection>,
+        services: &[ServiceQuery<'_>],
+    ) -> io::Result<Vec<io::Result<u32>>>
+    where
+        ServiceIdsRequestResult: Decode<Format>,
+    {
+        let mut request_stream = connection.new_stream().await?;
+
+        let request = RequestKind::ServiceIds {
+            services: services.to_vec(),
+        };
+        request_stream.send_encodable(&request).await?;
+        request_stream.flush().await?;
+
+        let service_ids = request_stream
+            .receive_decodable::<ServiceIdsRequestResult, _>()
+            .await?;
+        if service_ids.len() != services.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Server sent results not for every requested service",
+            ));
+        }
+
+        Ok(service_ids
+            .into_iter()
+            .map(|service_id| Ok(service_id?.0))
+            .collect())
+    }
+
     /// Call a remote service with multipart as arguments.
     /// # Errors
     /// Returns an error if service call fails.
     pub async fn call_service_multipart(
         &self,
-        kind: ServiceKind,
-        id: u32,
+        service: ServiceHandle,
         function_id: u32,
         args: &MultipartSendable,
     ) -> io::Result<MultipartReceived> {
-        let mut request_stream = self.new_stream().await?;
+        self.call_service_multipart_with_options(
+            service,
+            function_id,
+            args,
+            &CallOptions::default(),
+        )
+        .await
+    }
 
-        let part_sizes: Vec<u32> = args
-            .iter()
+    /// Call a remote service with multipart as arguments and specified call options.
+    /// # Errors
+    /// Returns an error if service call fails or deadline is exceeded.
+    pub async fn call_service_multipart_with_options(
+        &self,
+        service: ServiceHandle,
+        function_id: u32,
+        args: &MultipartSendable,
+        options: &CallOptions,
+    ) -> io::Result<MultipartReceived> {
+        self.call_service_multipart_with_origin(service, function_id, args, options)
+            .await
+            .map(|(returns, _)| returns)
+    }
+
+    /// Same as [`call_service_multipart_with_options`][Client::call_service_multipart_with_options] but also returns connection that executed the call.
+    async fn call_service_multipart_with_origin(
+        &self,
+        service: ServiceHandle,
+        function_id: u32,
+        args: &MultipartSendable,
+        options: &CallOptions,
+    ) -> io::Result<(MultipartReceived, ConnectionOrigin)> {
+        let call = self.call_service_multipart_retrying(service, function_id, args, options);
+
+        if let Some(deadline) = options.deadline() {
+            tokio::time::timeout_at(deadline, call)
+                .await
+                .map_err(|err| io::Error::new(io::ErrorKind::TimedOut, err))?
+        } else {
+            call.await
+        }
+    }
+
+    async fn call_service_multipart_retrying(
+        &self,
+        service: ServiceHandle,
+        function_id: u32,
+        args: &MultipartSendable,
+        options: &CallOptions,
+    ) -> io::Result<(MultipartReceived, ConnectionOrigin)> {
+        let part_sizes = Self::part_sizes(args)?;
+        let retry_policy = options.retry_policy().unwrap_or(&self.retry_policy);
+
+        let mut retry = 0u32;
+        // Endpoints that rejected call as overloaded, avoided by retries.
+        let mut overloaded = Vec::new();
+        loop {
+            let attempt = CallAttempt {
+                service,
+                function_id,
+                args,
+                part_sizes: &part_sizes,
+                excluded: &overloaded,
+            };
+            let error = match self.try_call_service_multipart(&attempt, options).await {
+                Ok(returns) => return Ok(returns),
+                Err(err) => err,
+            };
+
+            let attempts_left = retry.saturating_add(1) < retry_policy.max_attempts();
+            // Overloaded server rejects call before dispatching it, so retry is safe even if call isn't idempotent.
+            let retry_safe = options.is_idempotent() || error.overloaded_endpoint().is_some();
+            if !retry_safe || !attempts_left || !error.is_retryable() {
+                return Err(error.into_inner());
+            }
+
+            let backoff = retry_policy.backoff(retry);
+            let backoff = error
+                .retry_after()
+                .map_or(backoff, |retry_after| backoff.max(retry_after));
+            if let Some(deadline) = options.deadline()
+                && Instant::now()
+                    .checked_add(backoff)
+                    .map_or(true, |retry_at| retry_at >= deadline)
+            {
+                return Err(error.into_inner());
+            }
+
+            trace!(
+                "Retrying call of service {} ({:?}), function id: {function_id} in {backoff:?}. Error: {}",
+                service.id(),
+                service.kind(),
+                error.as_inner()
+            );
+            overloaded.extend(error.overloaded_endpoint());
+            tokio::time::sleep(backoff).await;
+            retry = retry.saturating_add(1);
+        }
+    }
+
+    fn part_sizes(args: &MultipartSendable) -> io::Result<Vec<u32>> {
+        args.iter()
             .map(|part| part.len().try_into())
             .try_collect()
-            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+    }
+
+    async fn try_call_service_multipart(
+        &self,
+        attempt: &CallAttempt<'_>,
+        options: &CallOptions,
+    ) -> Result<(MultipartReceived, ConnectionOrigin), CallAttemptError> {
+        let CallAttempt {
+            service,
+            function_id,
+            args,
+            part_sizes,
+            excluded,
+        } = *attempt;
+        let endpoint = self
+            .endpoint_excluding(service, options, excluded)
+            .map_err(CallAttemptError::NotSent)?;
+        let _call = endpoint.start_call();
+        let connection = endpoint
+            .current()
+            .await
+            .map_err(CallAttemptError::NotSent)?;
+        let id = self
+            .resolve_service_id(&connection, service)
+            .await
+            .map_err(CallAttemptError::NotSent)?;
+        let mut request_stream = connection
+            .new_stream()
+            .await
+            .map_err(CallAttemptError::NotSent)?;
 
         let request = RequestKind::ServiceCall {
-            kind,
+            kind: service.kind(),
             id,
             function_id,
-            part_sizes: &part_sizes,
+            part_sizes,
         };
-        request_stream.send_encodable(&request).await?;
-        request_stream.send_multipart(args).await?;
-        request_stream.flush().await?;
+        async {
+            request_stream.send_encodable(&request).await?;
+            request_stream.send_multipart(args).await?;
+            request_stream.flush().await
+        }
+        .await
+        .map_err(CallAttemptError::NotSent)?;
+
+        let returns = Self::receive_returns(&mut request_stream)
+            .await
+            .map_err(|error| CallAttemptError::Sent {
+                error,
+                endpoint: connection.origin().endpoint,
+            })?;
 
+        Ok((returns, connection.origin()))
+    }
+
+    async fn receive_returns(
+        request_stream: &mut Connection::Stream,
+    ) -> io::Result<MultipartReceived> {
         let service_call_result = request_stream.receive().await?;
         let response_part_sizes = ServiceCallRequestResult::decode_zero_copy(&service_call_result)
             .map_err(|err| io::Error::new(io::ErrorKind::Other, err))??;
-        MultipartReceived::receive_from_stream(&mut request_stream, response_part_sizes).await
+        MultipartReceived::receive_from_stream(request_stream, response_part_sizes).await
+    }
+
+    fn decode_returns<Returns: Decode<Format>>(
+        response_multipart: &MultipartReceived,
+        origin: ConnectionOrigin,
+    ) -> io::Result<Returns> {
+        let response = response_multipart.iter().next().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                "Server sent no multipart when expected at least one",
+            )
+        })?;
+        origin
+            .decode_within(|| Returns::decode(response))
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
     }
 
     /// Calls a remote service.
@@ -116,10 +682,28 @@ where
     /// Returns an error if service call fails.
     pub async fn call_service<Args, Returns>(
         &self,
-        kind: ServiceKind,
-        id: u32,
+        service: ServiceHandle,
+        function_id: u32,
+        args: &Args,
+    ) -> io::Result<Returns>
+    where
+        Args: Encode<Format>,
+        Returns: Decode<Format>,
+    {
+        self.call_service_with_options(service, function_id, args, &CallOptions::default())
+            .await
+    }
+
+    /// Calls a remote service with specified call options.
+    ///
+    /// # Errors
+    /// Returns an error if service call fails or deadline is exceeded.
+    pub async fn call_service_with_options<Args, Returns>(
+        &self,
+        service: ServiceHandle,
         function_id: u32,
         args: &Args,
+        options: &CallOptions,
     ) -> io::Result<Returns>
     where
         Args: Encode<Format>,
@@ -130,38 +714,848 @@ where
             .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
         let request_multipart = MultipartSendable::from([args_encoded]);
 
-        let response_multipart = self
-            .call_service_multipart(kind, id, function_id, &request_multipart)
+        let (response_multipart, origin) = self
+            .call_service_multipart_with_origin(service, function_id, &request_multipart, options)
             .await?;
 
-        let response = response_multipart.iter().next().ok_or_else(|| {
-            io::Error::new(
-                io::ErrorKind::Other,
-                "Server sent no multipart when expected at least one",
-            )
-        })?;
-        Returns::decode(response).map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+        Self::decode_returns(&response_multipart, origin)
     }
 
-    /// Deallocate private service previously returned from public service.
+    /// Calls a remote service function returning private service and returns handle of that service without waiting for the call to finish.
+    /// Calls of returned service are sent immediately and executed by server after this call finishes,
+    /// so chain of calls takes a single round trip. Result of the call itself is received with returned [`PendingReturns`].
+    ///
+    /// Promised service is private service returned by the function, so it must return [`ServiceRef`], [`TypedServiceRef`][crate::server::TypedServiceRef]
+    /// or optional one. If the call fails or returns no private service, calls of promised service fail.
     ///
     /// # Errors
-    /// Returns an error if service deallocation fails.
-    pub async fn deallocate_private_service(&self, id: u32) -> io::Result<()>
+    /// Returns an error if sending of request fails.
+    pub async fn call_service_pipelined<Args, Returns>(
+        &self,
+        service: ServiceHandle,
+        function_id: u32,
+        args: &Args,
+    ) -> io::Result<(ServiceHandle, PendingReturns<Connection, Format, Returns>)>
     where
-        PrivateServiceDeallocateRequestResult: Decode<Format>,
+        Args: Encode<Format>,
+        Returns: Decode<Format> + PromisingReturns,
     {
-        let mut request_stream = self.new_stream().await?;
+        let args_encoded = args
+            .encode()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let request_multipart = MultipartSendable::from([args_encoded]);
+        let part_sizes = Self::part_sizes(&request_multipart)?;
+
+        let connection = self
+            .endpoint_for(service, &CallOptions::default())?
+            .current()
+            .await?;
+        let id = self.resolve_service_id(&connection, service).await?;
+        let promise_id = connection.new_promise_id();
+        let mut request_stream = connection.new_stream().await?;
+
+        let request = RequestKind::PromisingCall {
+            kind: service.kind(),
+            id,
+            function_id,
+            part_sizes: &part_sizes,
+            promise_id,
+            returns: Returns::PROMISED_RETURNS,
+        };
+        request_stream.send_encodable(&request).await?;
+        request_stream.send_multipart(&request_multipart).await?;
+        request_stream.flush().await?;
+
+        let origin = connection.origin();
+        Ok((
+            ServiceHandle::promised(promise_id, origin),
+            PendingReturns::new(request_stream, origin),
+        ))
+    }
+
+    /// Sends calls of batch in a single request per endpoint and receives their results in one exchange.
+    /// Calls of private services are sent to endpoints that allocated them, calls of public services are sent to endpoint of the first private service,
+    /// or to a single picked endpoint if batch calls only public services.
+    /// Results are returned in order calls were added to batch.
+    ///
+    /// Parts of batch sent to different endpoints are independent: if sending of a part or receiving of its results fails,
+    /// each call of that part gets an error in results, while results of other parts are kept.
+    ///
+    /// # Errors
+    /// Returns [`InvalidInput`][io::ErrorKind::InvalidInput] error if batch is [ordered][CallBatch::ordered] and calls private services of several endpoints,
+    /// as order can't be kept between endpoints.
+    /// Returns an error if no endpoint can be picked for some call, or if batch is sent to a single endpoint and sending of it or receiving of results fails.
+    /// Errors of particular calls are returned in results.
+    pub async fn call_batch(
+        &self,
+        batch: &CallBatch<Format>,
+    ) -> io::Result<Vec<io::Result<MultipartReceived>>> {
+        let mut parts: Vec<(&ConnectionManager<Connection>, Vec<usize>)> = Vec::new();
+        let mut public_calls = Vec::new();
+        for (index, call) in batch.calls().iter().enumerate() {
+            if call.service.public_service().is_some() {
+                public_calls.push(index);
+                continue;
+            }
+
+            let endpoint = self.endpoint_for(call.service, &CallOptions::default())?;
+            let endpoint_index = endpoint.origin().endpoint;
+            match parts
+                .iter_mut()
+                .find(|(part_endpoint, _)| part_endpoint.origin().endpoint == endpoint_index)
+            {
+                Some((_, indices)) => indices.push(index),
+                None => parts.push((endpoint, vec![index])),
+            }
+        }
+
+        if batch.is_ordered() && parts.len() > 1 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Ordered batch calls private services of several endpoints",
+            ));
+        }
+        if !public_calls.is_empty() {
+            match parts.first_mut() {
+                Some((_, indices)) => {
+                    indices.extend(public_calls);
+                    indices.sort_unstable();
+                }
+                None => parts.push((self.pick_endpoint(None)?, public_calls)),
+            }
+        }
+
+        if let [(endpoint, indices)] = parts.as_slice() {
+            return self.call_batch_part(endpoint, batch, indices).await;
+        }
 
-        let request = RequestKind::DeallocatePrivateService { id };
+        let sent_parts = future::join_all(
+            parts
+                .iter()
+                .map(|(endpoint, indices)| self.call_batch_part(endpoint, batch, indices)),
+        )
+        .await;
+
+        let mut results: Vec<(usize, io::Result<MultipartReceived>)> = Vec::new();
+        for ((_, indices), part_result) in parts.into_iter().zip(sent_parts) {
+            match part_result {
+                Ok(part_results) => results.extend(indices.into_iter().zip(part_results)),
+                Err(err) => results.extend(
+                    indices
+                        .into_iter()
+                        .map(|index| (index, Err(io::Error::new(err.kind(), err.to_string())))),
+                ),
+            }
+        }
+        results.sort_unstable_by_key(|(index, _)| *index);
+
+        Ok(results.into_iter().map(|(_, result)| result).collect())
+    }
+
+    /// Sends calls of batch with specified indices to endpoint in a single request.
+    async fn call_batch_part(
+        &self,
+        endpoint: &ConnectionManager<Connection>,
+        batch: &CallBatch<Format>,
+        indices: &[usize],
+    ) -> io::Result<Vec<io::Result<MultipartReceived>>> {
+        let _call = endpoint.start_call();
+        let connection = endpoint.current().await?;
+
+        let part_calls: Vec<&BatchEntry> = indices
+            .iter()
+            .filter_map(|index| batch.calls().get(*index))
+            .collect();
+        let part_sizes: Vec<Vec<u32>> = part_calls
+            .iter()
+            .map(|call| Self::part_sizes(&call.args))
+            .try_collect()?;
+        let mut calls = Vec::with_capacity(part_calls.len());
+        for (call, call_part_sizes) in part_calls.iter().zip(&part_sizes) {
+            calls.push(BatchedCall {
+                kind: call.service.kind(),
+                id: self.resolve_service_id(&connection, call.service).await?,
+                function_id: call.function_id,
+                part_sizes: call_part_sizes,
+            });
+        }
+
+        let mut request_stream = connection.new_stream().await?;
+        let request = RequestKind::BatchCall {
+            ordered: batch.is_ordered(),
+            calls,
+        };
         request_stream.send_encodable(&request).await?;
+        for call in &part_calls {
+            request_stream.send_multipart(&call.args).await?;
+        }
         request_stream.flush().await?;
 
-        request_stream
-            .receive_decodable::<PrivateServiceDeallocateRequestResult, _>()
+        let mut results = Vec::with_capacity(part_calls.len());
+        for _ in &part_calls {
+            let service_call_result = request_stream.receive().await?;
+            let response_part_sizes =
+                ServiceCallRequestResult::decode_zero_copy(&service_call_result)
+                    .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+
+            results.push(match response_part_sizes {
+                Ok(response_part_sizes) => Ok(MultipartReceived::receive_from_stream(
+                    &mut request_stream,
+                    response_part_sizes,
+                )
+                .await?),
+                Err(err) => Err(err.into()),
+            });
+        }
+
+        Ok(results)
+    }
+
+    /// Calls one-way function of a remote service with multipart as arguments.
+    /// Returns as soon as request is sent, so failures of the call on server side aren't reported.
+    ///
+    /// # Errors
+    /// Returns an error if sending of request fails.
+    pub async fn call_service_multipart_one_way(
+        &self,
+        service: ServiceHandle,
+        function_id: u32,
+        args: &MultipartSendable,
+    ) -> io::Result<()> {
+        let part_sizes = Self::part_sizes(args)?;
+        let connection = self
+            .endpoint_for(service, &CallOptions::default())?
+            .current()
+            .await?;
+        let id = self.resolve_service_id(&connection, service).await?;
+        let mut request_stream = connection.new_stream().await?;
+
+        let request = RequestKind::OneWayCall {
+            kind: service.kind(),
+            id,
+            function_id,
+            part_sizes: &part_sizes,
+        };
+        request_stream.send_encodable(&request).await?;
+        request_stream.send_multipart(args).await?;
+        request_stream.flush().await
+    }
+
+    /// Calls one-way function of a remote service.
+    /// Returns as soon as request is sent, so failures of the call on server side aren't reported.
+    ///
+    /// # Errors
+    /// Returns an error if sending of request fails.
+    pub async fn call_service_one_way<Args: Encode<Format>>(
+        &self,
+        service: ServiceHandle,
+        function_id: u32,
+        args: &Args,
+    ) -> io::Result<()> {
+        let args_encoded = args
+            .encode()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        self.call_service_multipart_one_way(
+            service,
+            function_id,
+            &MultipartSendable::from([args_encoded]),
+        )
+        .await
+    }
+
+    /// Deallocate private service previously returned from public service, including promised services.
+    /// Services allocated on already lost connection are considered deallocated.
+    ///
+    /// # Errors
+    /// Returns an error if service deallocation fails.
+    pub async fn deallocate_private_service(&self, service: ServiceHandle) -> io::Result<()>
+    where
+        PrivateServiceDeallocateRequestResult: Decode<Format>,
+    {
+        let Some(connection) = self.private_service_connection(service).await? else {
+            return Ok(());
+        };
+
+        let request = match service.kind() {
+            ServiceKind::Promised => RequestKind::DeallocatePromisedService {
+                id: Self::promise_id(service)?,
+            },
+            ServiceKind::Public | ServiceKind::Private => {
+                RequestKind::DeallocatePrivateService { id: service.id() }
+            }
+        };
+        Self::private_service_request::<PrivateServiceDeallocateRequestResult>(
+            &connection,
+            &request,
+        )
+        .await??;
+        connection
+            .revocations()
+            .forget(service.kind(), service.id());
+        Ok(())
+    }
+
+    /// Queues deallocation of private service, including promised services. Does nothing for public services.
+    ///
+    /// Services queued at about the same time (e.g. service clients dropped together) are deallocated by a single request
+    /// sent in background. Queue is also flushed by [`flush_deallocations`][Self::flush_deallocations] and [`close`][Self::close].
+    pub fn queue_private_service_deallocation(self: &Arc<Self>, service: ServiceHandle)
+    where
+        Connection: 'static,
+        Format: 'static,
+        PrivateServiceDeallocateRequestResult: Decode<Format>,
+    {
+        if let ServiceKind::Public = service.kind() {
+            return;
+        }
+
+        let flush_needed = self.deallocation_queue.push(service);
+        if let (true, Ok(runtime)) = (flush_needed, tokio::runtime::Handle::try_current()) {
+            let rpc_client = Arc::clone(self);
+            runtime.spawn(async move {
+                // Lets services dropped together get into the same batch.
+                tokio::task::yield_now().await;
+                if let Err(err) = rpc_client.flush_deallocations().await {
+                    warn!("Failed to deallocate private services: {err}");
+                }
+            });
+        }
+    }
+
+    /// Deallocates all [queued][Self::queue_private_service_deallocation] private services sending a single request per endpoint.
+    /// Services allocated on connections that are already lost are considered deallocated.
+    ///
+    /// # Errors
+    /// Returns the first error of deallocation requests. Deallocation on other endpoints is done anyway.
+    pub async fn flush_deallocations(&self) -> io::Result<()>
+    where
+        PrivateServiceDeallocateRequestResult: Decode<Format>,
+    {
+        let queued = self.deallocation_queue.take();
+        let mut result = Ok(());
+
+        for endpoint in &*self.endpoints {
+            let Some(connection) = endpoint.established().await else {
+                continue;
+            };
+
+            let mut service_ids = Vec::new();
+            let mut promise_ids = Vec::new();
+            for service in queued
+                .iter()
+                .filter(|service| service.origin() == connection.origin())
+            {
+                connection
+                    .revocations()
+                    .forget(service.kind(), service.id());
+                match service.kind() {
+                    ServiceKind::Promised => promise_ids.extend(Self::promise_id(*service).ok()),
+                    ServiceKind::Public | ServiceKind::Private => service_ids.push(service.id()),
+                }
+            }
+            if service_ids.is_empty() && promise_ids.is_empty() {
+                continue;
+            }
+
+            let request = RequestKind::DeallocatePrivateServices {
+                ids: &service_ids,
+                promise_ids: &promise_ids,
+            };
+            match Self::private_service_request::<PrivateServiceDeallocateRequestResult>(
+                &connection,
+                &request,
+            )
+            .await
+            {
+                Ok(Ok(())) => {}
+                // Services may be already freed by server, e.g. because their lease is expired.
+                Ok(Err(err)) => trace!("Some of private services are already deallocated: {err}"),
+                Err(err) => result = result.and(Err(err)),
+            }
+        }
+
+        result
+    }
+
+    /// Flushes [queued deallocations][Self::queue_private_service_deallocation] and closes connections to all endpoints.
+    /// Calls made after close fail.
+    ///
+    /// # Errors
+    /// Returns an error if flush of deallocations fails. Connections are closed anyway.
+    pub async fn close(&self) -> io::Result<()>
+    where
+        PrivateServiceDeallocateRequestResult: Decode<Format>,
+    {
+        let flushed = self.flush_deallocations().await;
+        for endpoint in &*self.endpoints {
+            endpoint.close().await;
+        }
+
+        flushed
+    }
+
+    /// Wraps service handle into reference counted [`SharedService`], so several service clients may hold it.
+    /// Private service is [leased][Self::lease_private_service] while any clone is alive and
+    /// [queued for deallocation][Self::queue_private_service_deallocation] once the last clone is dropped.
+    pub fn share_service(self: &Arc<Self>, service: ServiceHandle) -> SharedService
+    where
+        Connection: 'static,
+        Format: 'static,
+        PrivateServiceDeallocateRequestResult: Decode<Format>,
+        PrivateServiceLease: Decode<Format>,
+    {
+        let lease = self.lease_private_service(service);
+        let rpc_client = Arc::clone(self);
+
+        SharedService::new(
+            service,
+            lease,
+            Box::new(move |shared_service| {
+                rpc_client.queue_private_service_deallocation(shared_service);
+            }),
+        )
+    }
+
+    /// Asks server for another independent reference to private service, including promised services.
+    /// Service is freed only after all its references are deallocated.
+    ///
+    /// # Errors
+    /// Returns an error if service is already freed, it's connection is lost or duplication request fails.
+    pub async fn duplicate_private_service(
+        &self,
+        service: ServiceHandle,
+    ) -> io::Result<ServiceHandle>
+    where
+        PrivateServiceDuplicateRequestResult: Decode<Format>,
+    {
+        let connection = self
+            .private_service_connection(service)
+            .await?
+            .ok_or(PrivateServiceLostError)?;
+
+        let request = RequestKind::DuplicatePrivateService {
+            kind: service.kind(),
+            id: service.id(),
+        };
+        let id = Self::private_service_request::<PrivateServiceDuplicateRequestResult>(
+            &connection,
+            &request,
+        )
+        .await??;
+
+        Ok(ServiceHandle::private(id, connection.origin()))
+    }
+
+    /// Renews lease of private service, including promised services, so server doesn't free it as abandoned.
+    ///
+    /// # Errors
+    /// Returns an error if service is already freed, it's connection is lost or renewal request fails.
+    pub async fn renew_private_service(&self, service: ServiceHandle) -> io::Result<()>
+    where
+        PrivateServiceRenewRequestResult: Decode<Format>,
+    {
+        let connection = self
+            .private_service_connection(service)
+            .await?
+            .ok_or(PrivateServiceLostError)?;
+
+        let request = match service.kind() {
+            ServiceKind::Promised => RequestKind::RenewPromisedService {
+                id: Self::promise_id(service)?,
+            },
+            ServiceKind::Public | ServiceKind::Private => {
+                RequestKind::RenewPrivateService { id: service.id() }
+            }
+        };
+        Self::private_service_request::<PrivateServiceRenewRequestResult>(&connection, &request)
             .await??;
         Ok(())
     }
+
+    /// Keeps lease of private service renewed until returned guard is dropped. Handles of public services don't need lease.
+    /// Service clients hold [shared services][Self::share_service] that are leased automatically, so it's needed only for bare handles.
+    ///
+    /// Leases are renewed only on connections whose server leases private services, with a single request per connection
+    /// sent several times per lease. Renewal stops once no services are leased.
+    pub fn lease_private_service(self: &Arc<Self>, service: ServiceHandle) -> ServiceLease
+    where
+        Connection: 'static,
+        Format: 'static,
+        PrivateServiceRenewRequestResult: Decode<Format>,
+        PrivateServiceLease: Decode<Format>,
+    {
+        if let ServiceKind::Public = service.kind() {
+            return ServiceLease::new(None);
+        }
+
+        if self.leased_services.acquire(service) {
+            if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+                runtime.spawn(Self::renew_leases(Arc::downgrade(self)));
+            } else {
+                warn!("Leases of private services aren't renewed outside of Tokio runtime");
+                self.leased_services.abort_renewal();
+            }
+        }
+
+        ServiceLease::new(Some((Arc::downgrade(&self.leased_services), service)))
+    }
+
+    /// Renews leases of leased private services until none are left.
+    async fn renew_leases(rpc_client: Weak<Self>)
+    where
+        PrivateServiceRenewRequestResult: Decode<Format>,
+        PrivateServiceLease: Decode<Format>,
+    {
+        loop {
+            let Some(client) = rpc_client.upgrade() else {
+                return;
+            };
+            let leased = client.leased_services.leased();
+            let Some(period) = client.lease_renewal_period(&leased).await else {
+                if client.leased_services.stop_renewal() {
+                    return;
+                }
+                continue;
+            };
+            drop(client);
+
+            tokio::time::sleep(period).await;
+            let Some(renewing_client) = rpc_client.upgrade() else {
+                return;
+            };
+            renewing_client
+                .renew_leased_services(&renewing_client.leased_services.leased())
+                .await;
+        }
+    }
+
+    /// Returns period of lease renewal of services, which is part of the shortest lease of their connections,
+    /// or `None` if none of them is leased.
+    async fn lease_renewal_period(&self, services: &[ServiceHandle]) -> Option<Duration>
+    where
+        PrivateServiceLease: Decode<Format>,
+    {
+        let mut shortest_period: Option<Duration> = None;
+        for endpoint in &*self.endpoints {
+            let Some(connection) = endpoint.established().await else {
+                continue;
+            };
+            if !services
+                .iter()
+                .any(|service| service.origin() == connection.origin())
+            {
+                continue;
+            }
+
+            match Self::private_service_lease(&connection).await {
+                Ok(Some(lease)) => {
+                    let Some(period) = lease.checked_div(LEASE_RENEWAL_FRACTION) else {
+                        continue;
+                    };
+                    shortest_period =
+                        Some(shortest_period.map_or(period, |shortest| shortest.min(period)));
+                }
+                Ok(None) => {}
+                Err(err) => warn!("Failed to find out lease of private services: {err}"),
+            }
+        }
+
+        shortest_period
+    }
+
+    async fn private_service_lease(
+        connection: &ActiveConnection<Connection>,
+    ) -> io::Result<Option<Duration>>
+    where
+        PrivateServiceLease: Decode<Format>,
+    {
+        connection
+            .private_service_lease()
+            .get_or_try_init(|| async {
+                Self::private_service_request::<PrivateServiceLease>(
+                    connection,
+                    &RequestKind::PrivateServiceLease,
+                )
+                .await
+                .map(|lease| lease.0)
+            })
+            .await
+            .copied()
+    }
+
+    /// Renews leases of services sending a single request per connection whose server leases private services.
+    async fn renew_leased_services(&self, services: &[ServiceHandle])
+    where
+        PrivateServiceRenewRequestResult: Decode<Format>,
+        PrivateServiceLease: Decode<Format>,
+    {
+        for endpoint in &*self.endpoints {
+            let Some(connection) = endpoint.established().await else {
+                continue;
+            };
+            let Ok(Some(_)) = Self::private_service_lease(&connection).await else {
+                continue;
+            };
+
+            let mut service_ids = Vec::new();
+            let mut promise_ids = Vec::new();
+            for service in services
+                .iter()
+                .filter(|service| service.origin() == connection.origin())
+            {
+                match service.kind() {
+                    ServiceKind::Promised => promise_ids.extend(Self::promise_id(*service).ok()),
+                    ServiceKind::Public | ServiceKind::Private => service_ids.push(service.id()),
+                }
+            }
+            if service_ids.is_empty() && promise_ids.is_empty() {
+                continue;
+            }
+
+            let request = RequestKind::RenewPrivateServices {
+                ids: &service_ids,
+                promise_ids: &promise_ids,
+            };
+            match Self::private_service_request::<PrivateServiceRenewRequestResult>(
+                &connection,
+                &request,
+            )
+            .await
+            {
+                Ok(Ok(())) => {}
+                // Services may be already freed by server, e.g. because their lease is expired.
+                Ok(Err(err)) => trace!("Leases of some private services are lost: {err}"),
+                Err(err) => warn!("Failed to renew leases of private services: {err}"),
+            }
+        }
+    }
+
+    /// Resolves once server revokes private service, e.g. when its lease expires, or its connection is lost.
+    /// Never resolves for public services.
+    pub async fn revoked(&self, service: ServiceHandle)
+    where
+        Connection: 'static,
+        Format: 'static,
+        RevokedServices: Decode<Format>,
+    {
+        if let ServiceKind::Public = service.kind() {
+            return future::pending().await;
+        }
+        let Ok(Some(connection)) = self.private_service_connection(service).await else {
+            return;
+        };
+
+        let revocations = connection.revocations();
+        if revocations.start_watching() {
+            match Self::watch_revocations(&connection).await {
+                Ok(mut revocations_stream) => {
+                    let watched_revocations = Arc::clone(revocations);
+                    tokio::spawn(async move {
+                        loop {
+                            match revocations_stream
+                                .receive_decodable::<RevokedServices, Format>()
+                                .await
+                            {
+                                Ok(revoked) => watched_revocations.revoke(revoked),
+                                Err(err) => {
+                                    trace!("Stopped watching revoked services: {err}");
+                                    watched_revocations.close();
+                                    return;
+                                }
+                            }
+                        }
+                    });
+                }
+                Err(err) => {
+                    warn!("Failed to watch revoked services: {err}");
+                    revocations.close();
+                }
+            }
+        }
+
+        revocations.revoked(service.kind(), service.id()).await;
+    }
+
+    async fn watch_revocations(
+        connection: &ActiveConnection<Connection>,
+    ) -> io::Result<Connection::Stream> {
+        let mut revocations_stream = connection.new_dedicated_stream().await?;
+        revocations_stream
+            .send_encodable(&RequestKind::WatchRevocations)
+            .await?;
+        revocations_stream.flush().await?;
+
+        Ok(revocations_stream)
+    }
+
+    /// Subscribes to topic declared by server. Each event published to topic afterwards is received by subscription.
+    ///
+    /// # Errors
+    /// Returns an error if topic isn't declared by server or connection is lost.
+    pub async fn subscribe<Event: Decode<Format>>(
+        &self,
+        topic: &str,
+    ) -> io::Result<Subscription<Connection, Format, Event>>
+    where
+        TopicSubscribeRequestResult: Decode<Format>,
+    {
+        let connection = self.pick_endpoint(None)?.current().await?;
+        let mut subscription_stream = connection.new_dedicated_stream().await?;
+        subscription_stream
+            .send_encodable(&RequestKind::Subscribe { topic })
+            .await?;
+        subscription_stream.flush().await?;
+        subscription_stream
+            .receive_decodable::<TopicSubscribeRequestResult, _>()
+            .await??;
+
+        Ok(Subscription::new(subscription_stream))
+    }
+
+    /// Returns connection private service was allocated on or `None` if it's already lost.
+    async fn private_service_connection(
+        &self,
+        service: ServiceHandle,
+    ) -> io::Result<Option<Arc<ActiveConnection<Connection>>>> {
+        let Some(endpoint) = self.endpoints.get(service.origin().endpoint) else {
+            return Ok(None);
+        };
+        let connection = endpoint.current().await?;
+
+        Ok((service.origin() == connection.origin()).then_some(connection))
+    }
+
+    #[allow(clippy::map_err_ignore)]
+    fn promise_id(service: ServiceHandle) -> io::Result<u32> {
+        service
+            .id()
+            .try_into()
+            .map_err(|_| InvalidPrivateServiceIdError.into())
+    }
+
+    async fn private_service_request<Response: Decode<Format>>(
+        connection: &ActiveConnection<Connection>,
+        request: &RequestKind<'_>,
+    ) -> io::Result<Response> {
+        let mut request_stream = connection.new_stream().await?;
+        request_stream.send_encodable(request).await?;
+        request_stream.flush().await?;
+
+        request_stream.receive_decodable::<Response, _>().await
+    }
+}
+
+// Separate from other methods because only they handle requests made by server and need encoding of responses to them.
+#[allow(clippy::multiple_inherent_impl)]
+impl<Connection: transport::ClientConnection, Format: format::ZeroCopyEncodingFormat + 'static>
+    Client<Connection, Format>
+where
+    for<'a, 'b> RequestKind<'a>:
+        DecodeZeroCopy<'a, Format, <RequestKind<'b> as DecodeZeroCopyFallible<Format>>::Error>,
+    ServiceIdRequestResult: Encode<Format>,
+    ServiceIdsRequestResult: Encode<Format>,
+    for<'a> ServiceCallRequestResult<'a>: Encode<Format>,
+    PrivateServiceDeallocateRequestResult: Encode<Format>,
+    PrivateServiceDuplicateRequestResult: Encode<Format>,
+    RevokedServices: Encode<Format>,
+    TopicSubscribeRequestResult: Encode<Format>,
+    BroadcastRequestResult: Encode<Format>,
+    AuthenticateRequestResult: Encode<Format>,
+    PrivateServiceLease: Encode<Format>,
+{
+    /// Hosts private service on client, so server of endpoint with specified index can call it by reference passed as call argument,
+    /// e.g. to invoke callback or report progress. Servers of other endpoints can't call it,
+    /// so reference should be passed to service obtained from the same [endpoint][ServiceHandle::endpoint].
+    /// Service stays hosted until it's [unhosted][Self::unhost_service].
+    /// Returns `None` if there is no such endpoint or client ran out of service ids.
+    pub async fn host_service(
+        &self,
+        endpoint: usize,
+        service: Box<dyn Service<Format>>,
+    ) -> Option<ServiceRef> {
+        let hosted_services = self.hosted_services.get(endpoint)?;
+        self.accept_incoming_streams().await;
+        hosted_services.host(service).await
+    }
+
+    /// Starts receiving messages broadcast by server to all connected clients.
+    /// Only the latest receiver gets messages, receivers returned before are closed.
+    #[allow(clippy::indexing_slicing)] // Client always has at least one endpoint.
+    pub async fn broadcasts<Message: Decode<Format>>(&self) -> Broadcasts<Format, Message> {
+        // Broadcasts of all endpoints are received by the same receiver.
+        let messages = self.hosted_services[0].receive_broadcasts();
+        self.accept_incoming_streams().await;
+        Broadcasts::new(messages)
+    }
+
+    /// Starts handling streams created by server on every endpoint.
+    async fn accept_incoming_streams(&self) {
+        for (endpoint, hosted_services) in self.endpoints.iter().zip(&*self.hosted_services) {
+            let hosted_services = hosted_services.clone();
+            let handler: IncomingStreamHandler<Connection> = Arc::new(move |stream| {
+                let handler = hosted_services.clone();
+                Box::pin(async move {
+                    if let Err(err) = CallStream::<_, Format>::from(stream)
+                        .handle_call(&handler)
+                        .await
+                    {
+                        trace!("Stream created by server is closed: {err}");
+                    }
+                })
+            });
+            endpoint.accept_incoming_streams(handler).await;
+        }
+    }
+}
+
+// Separate from other methods because they're used by code generic over any encoding format.
+#[allow(clippy::multiple_inherent_impl)]
+impl<Connection: transport::ClientConnection, Format: EncodingFormat> Client<Connection, Format> {
+    /// Returns receiver of state changes of connection to the first endpoint.
+    #[must_use]
+    #[allow(clippy::indexing_slicing)] // Client always has at least one endpoint.
+    pub fn connection_state(&self) -> watch::Receiver<ConnectionState> {
+        self.endpoints[0].state()
+    }
+
+    /// Returns receivers of connection state changes for each endpoint in order they were specified.
+    #[must_use]
+    pub fn endpoint_states(&self) -> Vec<watch::Receiver<ConnectionState>> {
+        self.endpoints
+            .iter()
+            .map(|endpoint| endpoint.state())
+            .collect()
+    }
+
+    /// Creates handle of private service allocated on current connection to the first endpoint.
+    /// Prefer [`ServiceRef::into_client`][crate::server::ServiceRef::into_client] that remembers endpoint which returned the reference.
+    #[must_use]
+    #[allow(clippy::indexing_slicing)] // Client always has at least one endpoint.
+    pub fn private_service_handle(&self, id: u64) -> ServiceHandle {
+        ServiceHandle::private(id, self.endpoints[0].origin())
+    }
+
+    /// Stops hosting service for endpoint with specified index, so server can't call it anymore.
+    /// Returns `None` if service isn't hosted by this client for the endpoint.
+    pub async fn unhost_service(
+        &self,
+        endpoint: usize,
+        service: ServiceRef,
+    ) -> Option<Deallocated<Format>> {
+        self.hosted_services.get(endpoint)?.unhost(service).await
+    }
+
+    pub(crate) fn private_service_handle_on(
+        &self,
+        id: u64,
+        origin: Option<ConnectionOrigin>,
+    ) -> ServiceHandle {
+        origin.map_or_else(
+            || self.private_service_handle(id),
+            |endpoint_origin| ServiceHandle::private(id, endpoint_origin),
+        )
+    }
 }
 
 impl<Connection: transport::ClientConnection, Format: EncodingFormat> From<Connection>
@@ -169,7 +1563,12 @@ impl<Connection: transport::ClientConnection, Format: EncodingFormat> From<Conne
 {
     fn from(connection: Connection) -> Self {
         Self {
-            connection: Mutex::new(ConnectionCloseOnDrop(connection).into()),
+            endpoints: Box::new([ConnectionManager::from_connection(connection, 0)]),
+            load_balancer: Box::<RoundRobin>::default(),
+            retry_policy: RetryPolicy::default(),
+            leased_services: Arc::default(),
+            deallocation_queue: DeallocationQueue::default(),
+            hosted_services: HostedServices::for_endpoints(1),
             _format: PhantomData,
         }
     }
@@ -0,0 +1,29 @@
+use core::marker::PhantomData;
+use std::io;
+use tokio::sync::mpsc;
+
+use crate::format::{Decode, EncodingFormat};
+
+/// Receiver of messages broadcast by server, see [`Client::broadcasts`][super::Client::broadcasts].
+pub struct Broadcasts<Format: EncodingFormat, Message> {
+    messages: mpsc::UnboundedReceiver<Vec<u8>>,
+    _phantom: PhantomData<fn() -> (Format, Message)>,
+}
+
+impl<Format: EncodingFormat, Message> Broadcasts<Format, Message> {
+    pub(super) const fn new(messages: mpsc::UnboundedReceiver<Vec<u8>>) -> Self {
+        Self {
+            messages,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<Format: EncodingFormat, Message: Decode<Format>> Broadcasts<Format, Message> {
+    /// Receives next message broadcast by server.
+    /// Returns `None` once client starts receiving broadcasts with another receiver.
+    pub async fn next(&mut self) -> Option<io::Result<Message>> {
+        let message = self.messages.recv().await?;
+        Some(Message::decode(&message).map_err(|err| io::Error::new(io::ErrorKind::Other, err)))
+    }
+}
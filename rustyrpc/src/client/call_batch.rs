@@ -0,0 +1,105 @@
+use core::marker::PhantomData;
+use std::io;
+
+use super::ServiceHandle;
+use crate::{
+    format::{Encode, EncodingFormat},
+    multipart::MultipartSendable,
+};
+
+pub(super) struct BatchEntry {
+    pub(super) service: ServiceHandle,
+    pub(super) function_id: u32,
+    pub(super) args: MultipartSendable,
+}
+
+/// Several calls sent to server in a single request with [`Client::call_batch`][super::Client::call_batch].
+///
+/// Server executes calls concurrently unless batch is [ordered][CallBatch::ordered].
+/// Calls are split between endpoints that allocated their private services, so ordered batch may call private services of a single endpoint only.
+pub struct CallBatch<Format: EncodingFormat> {
+    calls: Vec<BatchEntry>,
+    ordered: bool,
+    _format: PhantomData<Format>,
+}
+
+impl<Format: EncodingFormat> CallBatch<Format> {
+    /// Creates empty batch.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            calls: Vec::new(),
+            ordered: false,
+            _format: PhantomData,
+        }
+    }
+
+    /// Makes server execute calls one after another in order they were added.
+    #[must_use]
+    pub const fn ordered(mut self) -> Self {
+        self.ordered = true;
+        self
+    }
+
+    /// Adds call with multipart as arguments. Returns index of call's result.
+    pub fn push_multipart(
+        &mut self,
+        service: ServiceHandle,
+        function_id: u32,
+        args: MultipartSendable,
+    ) -> usize {
+        self.calls.push(BatchEntry {
+            service,
+            function_id,
+            args,
+        });
+        self.calls.len().saturating_sub(1)
+    }
+
+    /// Adds call. Returns index of call's result.
+    ///
+    /// # Errors
+    /// Returns an error if arguments encoding fails.
+    pub fn push<Args: Encode<Format>>(
+        &mut self,
+        service: ServiceHandle,
+        function_id: u32,
+        args: &Args,
+    ) -> io::Result<usize> {
+        let args_encoded = args
+            .encode()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        Ok(self.push_multipart(
+            service,
+            function_id,
+            MultipartSendable::from([args_encoded]),
+        ))
+    }
+
+    /// Returns count of calls in batch.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.calls.len()
+    }
+
+    /// Returns `true` if batch contains no calls.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+
+    pub(super) fn calls(&self) -> &[BatchEntry] {
+        &self.calls
+    }
+
+    pub(super) const fn is_ordered(&self) -> bool {
+        self.ordered
+    }
+}
+
+impl<Format: EncodingFormat> Default for CallBatch<Format> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,73 @@
+use core::{
+    hash::{Hash, Hasher},
+    time::Duration,
+};
+use std::collections::hash_map::DefaultHasher;
+
+use tokio::time::Instant;
+
+use super::retry::RetryPolicy;
+
+/// Options of a single remote call.
+#[derive(Debug, Clone, Default)]
+pub struct CallOptions {
+    idempotent: bool,
+    retry_policy: Option<RetryPolicy>,
+    deadline: Option<Instant>,
+    routing_key: Option<u64>,
+}
+
+impl CallOptions {
+    /// Marks call as idempotent, so it can be retried on retryable failures. Calls rejected by overloaded server are retried regardless.
+    #[must_use]
+    pub const fn idempotent(mut self) -> Self {
+        self.idempotent = true;
+        self
+    }
+
+    /// Sets retry policy used instead of client's one.
+    #[must_use]
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    /// Sets deadline of call including all retries.
+    #[must_use]
+    pub const fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Sets deadline of call to specified duration from now.
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.deadline = Instant::now().checked_add(timeout);
+        self
+    }
+
+    /// Sets key used by load balancer to pick endpoint for call, see [`ConsistentHash`][super::load_balancer::ConsistentHash].
+    #[must_use]
+    pub fn with_routing_key<K: Hash + ?Sized>(mut self, key: &K) -> Self {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        self.routing_key = Some(hasher.finish());
+        self
+    }
+
+    pub(super) const fn is_idempotent(&self) -> bool {
+        self.idempotent
+    }
+
+    pub(super) const fn retry_policy(&self) -> Option<&RetryPolicy> {
+        self.retry_policy.as_ref()
+    }
+
+    pub(super) const fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    pub(super) const fn routing_key(&self) -> Option<u64> {
+        self.routing_key
+    }
+}
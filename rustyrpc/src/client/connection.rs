@@ -0,0 +1,405 @@
+use alloc::sync::{Arc, Weak};
+use core::{
+    ops::DerefMut,
+    sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
+};
+use futures::future::BoxFuture;
+use log::{info, warn};
+use std::{
+    collections::HashMap,
+    io,
+    sync::{OnceLock, PoisonError},
+};
+use tokio::{
+    runtime::Handle,
+    sync::{watch, Mutex, OnceCell, RwLock},
+};
+
+use super::{
+    load_balancer::EndpointInfo, retry::RetryPolicy, revocations::Revocations, ConnectionOrigin,
+};
+use crate::{
+    transport::{self, StreamAcceptor},
+    utils::{ConnectionCloseOnDrop, DropOwned},
+};
+
+/// State of connection between client and server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// Connection is established.
+    Connected,
+    /// Connection is lost and client tries to establish a new one.
+    Reconnecting,
+    /// Connection is lost and won't be established again.
+    Closed,
+}
+
+type ConnectFn<Connection> =
+    Box<dyn Fn() -> BoxFuture<'static, io::Result<Connection>> + Send + Sync>;
+type ClosedFuture = BoxFuture<'static, io::Error>;
+type IncomingStream<Connection> =
+    <<Connection as transport::ClientConnection>::StreamAcceptor as StreamAcceptor>::Stream;
+/// Handles stream created by server to call services hosted by client.
+pub(super) type IncomingStreamHandler<Connection> =
+    Arc<dyn Fn(IncomingStream<Connection>) -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// Performs authentication handshake on the first stream of each established connection.
+pub(super) type Handshake<Connection> = Arc<
+    dyn Fn(
+            <Connection as transport::ClientConnection>::Stream,
+        ) -> BoxFuture<'static, io::Result<()>>
+        + Send
+        + Sync,
+>;
+
+struct Reconnect<Connection> {
+    connect: ConnectFn<Connection>,
+    policy: RetryPolicy,
+}
+
+/// Single transport connection established by client.
+pub(super) struct ActiveConnection<Connection: transport::ClientConnection> {
+    connection: Mutex<DropOwned<ConnectionCloseOnDrop<Connection>>>,
+    origin: ConnectionOrigin,
+    public_service_ids: Mutex<HashMap<(&'static str, &'static [u8]), u32>>,
+    next_promise_id: AtomicU32,
+    revocations: Arc<Revocations>,
+    private_service_lease: OnceCell<Option<Duration>>,
+    stream_acceptor: std::sync::Mutex<Option<Connection::StreamAcceptor>>,
+}
+
+impl<Connection: transport::ClientConnection> ActiveConnection<Connection> {
+    fn new(connection: Connection, origin: ConnectionOrigin) -> Self {
+        Self {
+            stream_acceptor: Some(connection.stream_acceptor()).into(),
+            connection: Mutex::new(ConnectionCloseOnDrop(connection).into()),
+            origin,
+            public_service_ids: Mutex::default(),
+            next_promise_id: AtomicU32::new(0),
+            revocations: Arc::default(),
+            private_service_lease: OnceCell::new(),
+        }
+    }
+
+    pub(super) async fn new_stream(&self) -> io::Result<Connection::Stream> {
+        let mut transport_connection = self.connection.lock().await;
+        transport_connection.deref_mut().0.new_stream().await
+    }
+
+    pub(super) async fn new_dedicated_stream(&self) -> io::Result<Connection::Stream> {
+        let mut transport_connection = self.connection.lock().await;
+        transport_connection
+            .deref_mut()
+            .0
+            .new_dedicated_stream()
+            .await
+    }
+
+    /// Starts accepting streams created by server, does nothing if streams are already accepted.
+    fn accept_incoming_streams(&self, handler: &IncomingStreamHandler<Connection>) {
+        let Some(mut stream_acceptor) = self
+            .stream_acceptor
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+        else {
+            return;
+        };
+
+        let handler = Arc::clone(handler);
+        tokio::spawn(async move {
+            while let Ok(stream) = stream_acceptor.accept_stream().await {
+                tokio::spawn(handler(stream));
+            }
+        });
+    }
+
+    pub(super) const fn origin(&self) -> ConnectionOrigin {
+        self.origin
+    }
+
+    pub(super) fn revocations(&self) -> &Arc<Revocations> {
+        &self.revocations
+    }
+
+    /// Returns lease of private services on connection, requested from server once.
+    pub(super) const fn private_service_lease(&self) -> &OnceCell<Option<Duration>> {
+        &self.private_service_lease
+    }
+
+    pub(super) fn new_promise_id(&self) -> u32 {
+        self.next_promise_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    pub(super) async fn public_service_id(
+        &self,
+        name: &'static str,
+        checksum: &'static [u8],
+    ) -> Option<u32> {
+        self.public_service_ids
+            .lock()
+            .await
+            .get(&(name, checksum))
+            .copied()
+    }
+
+    pub(super) async fn remember_public_service_id(
+        &self,
+        name: &'static str,
+        checksum: &'static [u8],
+        id: u32,
+    ) {
+        self.public_service_ids
+            .lock()
+            .await
+            .insert((name, checksum), id);
+    }
+}
+
+/// Owns client's transport connection and replaces it with a new one after connection loss if reconnection is enabled.
+pub(super) struct ConnectionManager<Connection: transport::ClientConnection> {
+    active: RwLock<Option<Arc<ActiveConnection<Connection>>>>,
+    endpoint: usize,
+    generation: AtomicU64,
+    outstanding_calls: AtomicUsize,
+    state: watch::Sender<ConnectionState>,
+    reconnect: Option<Reconnect<Connection>>,
+    incoming_stream_handler: OnceLock<IncomingStreamHandler<Connection>>,
+    /// Supervision of connection wrapped outside of Tokio runtime, started once connection is used.
+    pending_supervision: std::sync::Mutex<Option<BoxFuture<'static, ()>>>,
+}
+
+impl<Connection: transport::ClientConnection> ConnectionManager<Connection> {
+    fn new(connection: Connection, endpoint: usize) -> Self {
+        let origin = ConnectionOrigin::new(endpoint, 0);
+        Self {
+            active: RwLock::new(Some(ActiveConnection::new(connection, origin).into())),
+            endpoint,
+            generation: AtomicU64::new(0),
+            outstanding_calls: AtomicUsize::new(0),
+            state: watch::Sender::new(ConnectionState::Connected),
+            reconnect: None,
+            incoming_stream_handler: OnceLock::new(),
+            pending_supervision: std::sync::Mutex::new(None),
+        }
+    }
+
+    /// Establishes connection with connector and starts a task that reconnects on connection loss.
+    /// Handshake is performed on each established connection before it's used.
+    pub(super) async fn connect<Connector>(
+        connector: Connector,
+        policy: RetryPolicy,
+        endpoint: usize,
+        handshake: Option<Handshake<Connection>>,
+    ) -> io::Result<Arc<Self>>
+    where
+        Connector: transport::Connector<Connection = Connection>,
+    {
+        let connector = Arc::new(connector);
+        let connect: ConnectFn<Connection> = Box::new(move || {
+            let connector = Arc::clone(&connector);
+            let handshake = handshake.clone();
+            Box::pin(async move {
+                let mut connection = connector.connect().await?;
+                if let Some(handshake) = handshake {
+                    handshake(connection.new_dedicated_stream().await?).await?;
+                }
+
+                Ok(connection)
+            })
+        });
+
+        let connection = connect().await?;
+        let closed: ClosedFuture = Box::pin(connection.closed());
+
+        Ok(Self {
+            reconnect: Some(Reconnect { connect, policy }),
+            ..Self::new(connection, endpoint)
+        }
+        .supervised(closed))
+    }
+
+    /// Wraps already established connection and starts a task that reports its close. Connection isn't reestablished once lost.
+    /// Outside of Tokio runtime the task is started once connection is used.
+    pub(super) fn from_connection(connection: Connection, endpoint: usize) -> Arc<Self> {
+        let closed: ClosedFuture = Box::pin(connection.closed());
+        Self::new(connection, endpoint).supervised(closed)
+    }
+
+    fn supervised(self, closed: ClosedFuture) -> Arc<Self> {
+        let manager = Arc::new(self);
+        let supervision = Self::supervise(Arc::downgrade(&manager), closed);
+        match Handle::try_current() {
+            Ok(runtime) => drop(runtime.spawn(supervision)),
+            Err(_) => {
+                *manager
+                    .pending_supervision
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner) = Some(Box::pin(supervision));
+            }
+        }
+
+        manager
+    }
+
+    /// Starts supervision postponed because connection was wrapped outside of Tokio runtime.
+    fn start_pending_supervision(&self) {
+        let supervision = self
+            .pending_supervision
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if let Some(supervision) = supervision {
+            tokio::spawn(supervision);
+        }
+    }
+
+    async fn supervise(manager: Weak<Self>, mut closed: ClosedFuture) {
+        loop {
+            let error = closed.await;
+            let Some(manager) = manager.upgrade() else {
+                return;
+            };
+
+            if let Some(next_closed) = manager.reconnect(error).await {
+                closed = next_closed;
+            } else {
+                return;
+            }
+        }
+    }
+
+    /// Replaces lost connection with a new one. Returns future of its close or `None` if reconnection failed.
+    async fn reconnect(&self, error: io::Error) -> Option<ClosedFuture> {
+        // State is published before lost connection is taken, so it's never reported as connected without connection.
+        if *self.state.borrow() == ConnectionState::Closed {
+            self.active.write().await.take();
+            return None;
+        }
+        let Some(Reconnect { connect, policy }) = self.reconnect.as_ref() else {
+            warn!("Connection lost: {error}");
+            self.state.send_replace(ConnectionState::Closed);
+            self.active.write().await.take();
+            return None;
+        };
+        warn!("Connection lost: {error}. Reconnecting");
+        self.state.send_replace(ConnectionState::Reconnecting);
+        self.active.write().await.take();
+
+        let mut attempt = 0u32;
+        loop {
+            match connect().await {
+                Ok(connection) => {
+                    let closed: ClosedFuture = Box::pin(connection.closed());
+                    let generation = self
+                        .generation
+                        .fetch_add(1, Ordering::AcqRel)
+                        .saturating_add(1);
+
+                    let origin = ConnectionOrigin::new(self.endpoint, generation);
+
+                    let connection = ActiveConnection::new(connection, origin);
+                    if let Some(handler) = self.incoming_stream_handler.get() {
+                        connection.accept_incoming_streams(handler);
+                    }
+                    *self.active.write().await = Some(connection.into());
+                    self.state.send_replace(ConnectionState::Connected);
+                    info!("Connection reestablished");
+
+                    return Some(closed);
+                }
+                Err(err) => {
+                    attempt = attempt.saturating_add(1);
+                    if attempt >= policy.max_attempts() {
+                        warn!("Failed to reconnect: {err}. Giving up");
+                        self.state.send_replace(ConnectionState::Closed);
+                        return None;
+                    }
+
+                    let backoff = policy.backoff(attempt.saturating_sub(1));
+                    warn!("Failed to reconnect: {err}. Next attempt in {backoff:?}");
+                    tokio::time::sleep(backoff).await;
+                }
+            }
+        }
+    }
+
+    /// Returns currently established connection waiting for reconnection if it's in progress.
+    pub(super) async fn current(&self) -> io::Result<Arc<ActiveConnection<Connection>>> {
+        self.start_pending_supervision();
+        let mut state = self.state.subscribe();
+        loop {
+            if let Some(active) = self.active.read().await.as_ref() {
+                return Ok(Arc::clone(active));
+            }
+
+            let current_state = *state.borrow_and_update();
+            match current_state {
+                // Connection is being replaced right now.
+                ConnectionState::Connected => tokio::task::yield_now().await,
+                ConnectionState::Reconnecting => state
+                    .changed()
+                    .await
+                    .map_err(|err| io::Error::new(io::ErrorKind::NotConnected, err))?,
+                ConnectionState::Closed => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotConnected,
+                        "Connection is closed",
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Handles streams created by server on current and future connections. Only the first handler is used.
+    pub(super) async fn accept_incoming_streams(&self, handler: IncomingStreamHandler<Connection>) {
+        self.start_pending_supervision();
+        let handler = self.incoming_stream_handler.get_or_init(|| handler);
+        if let Some(connection) = self.active.read().await.as_ref() {
+            connection.accept_incoming_streams(handler);
+        }
+    }
+
+    /// Returns currently established connection or `None` if connection is lost.
+    pub(super) async fn established(&self) -> Option<Arc<ActiveConnection<Connection>>> {
+        self.start_pending_supervision();
+        self.active.read().await.clone()
+    }
+
+    /// Closes connection without reconnection.
+    pub(super) async fn close(&self) {
+        self.state.send_replace(ConnectionState::Closed);
+        self.active.write().await.take();
+    }
+
+    /// Returns origin of current connection or of the last lost one.
+    pub(super) fn origin(&self) -> ConnectionOrigin {
+        ConnectionOrigin::new(self.endpoint, self.generation.load(Ordering::Acquire))
+    }
+
+    pub(super) fn state(&self) -> watch::Receiver<ConnectionState> {
+        self.state.subscribe()
+    }
+
+    pub(super) fn info(&self) -> EndpointInfo {
+        EndpointInfo {
+            state: *self.state.borrow(),
+            outstanding_calls: self.outstanding_calls.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Counts call as outstanding until returned guard is dropped.
+    pub(super) fn start_call(&self) -> OutstandingCall<'_> {
+        self.outstanding_calls.fetch_add(1, Ordering::Relaxed);
+        OutstandingCall(&self.outstanding_calls)
+    }
+}
+
+pub(super) struct OutstandingCall<'a>(&'a AtomicUsize);
+
+impl Drop for OutstandingCall<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,34 @@
+use crate::protocol::Credentials;
+
+/// Credentials kept by client to authenticate each connection it establishes, including reconnections.
+pub(super) enum OwnedCredentials {
+    Token(Box<str>),
+    Password {
+        username: Box<str>,
+        password: Box<str>,
+    },
+    Transport,
+}
+
+impl OwnedCredentials {
+    pub(super) const fn as_credentials(&self) -> Credentials<'_> {
+        match self {
+            Self::Token(token) => Credentials::Token(token),
+            Self::Password { username, password } => Credentials::Password { username, password },
+            Self::Transport => Credentials::Transport,
+        }
+    }
+}
+
+impl From<Credentials<'_>> for OwnedCredentials {
+    fn from(credentials: Credentials<'_>) -> Self {
+        match credentials {
+            Credentials::Token(token) => Self::Token(token.into()),
+            Credentials::Password { username, password } => Self::Password {
+                username: username.into(),
+                password: password.into(),
+            },
+            Credentials::Transport => Self::Transport,
+        }
+    }
+}
@@ -0,0 +1,21 @@
+use core::mem;
+use std::sync::{Mutex, PoisonError};
+
+use super::ServiceHandle;
+
+/// Private services waiting for deallocation, sent to server in batches.
+#[derive(Default)]
+pub(super) struct DeallocationQueue(Mutex<Vec<ServiceHandle>>);
+
+impl DeallocationQueue {
+    /// Queues service. Returns `true` if queue was empty, so flush of queue must be scheduled.
+    pub(super) fn push(&self, service: ServiceHandle) -> bool {
+        let mut queue = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        queue.push(service);
+        queue.len() == 1
+    }
+
+    pub(super) fn take(&self) -> Vec<ServiceHandle> {
+        mem::take(&mut *self.0.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
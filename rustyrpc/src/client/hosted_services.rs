@@ -0,0 +1,225 @@
+use alloc::sync::Arc;
+use core::{future::Future, time::Duration};
+use derive_where::derive_where;
+use futures::future;
+use log::{trace, warn};
+use std::sync::{Mutex, PoisonError};
+use tokio::sync::mpsc;
+
+use crate::{
+    format::EncodingFormat,
+    multipart::{MultipartReceived, MultipartSendable},
+    protocol::{
+        BroadcastRejectedError, InvalidPrivateServiceIdError, PromisedReturns,
+        RemoteServiceIdRequestError, RevokedServices, ServiceCallRequestError, ServiceKind,
+        TopicNotFoundError,
+    },
+    server::{CallHandler, Deallocated, PrivateServiceAllocator, ServiceRef, TopicSubscription},
+    service::Service,
+};
+
+/// Handles calls made by server of single endpoint to services [hosted by client][super::Client::host_service] and [broadcasts][super::Client::broadcasts].
+/// Client hosts only private services, server gets references to them in call arguments.
+/// Each endpoint has its own services, so server can't call services hosted for another one.
+/// Hosted services are freed only by client, so server can't deallocate, duplicate or renew them.
+#[derive_where(Clone)]
+pub(super) struct HostedServices<Format: EncodingFormat> {
+    services: Arc<PrivateServiceAllocator<Format>>,
+    broadcasts: Arc<Mutex<Option<mpsc::UnboundedSender<Vec<u8>>>>>,
+}
+
+impl<Format: EncodingFormat> HostedServices<Format> {
+    /// Creates hosted services of each endpoint. Broadcasts of all endpoints are received together.
+    pub(super) fn for_endpoints(count: usize) -> Box<[Self]> {
+        let broadcasts = Arc::default();
+        (0..count)
+            .map(|_| Self {
+                services: Arc::default(),
+                broadcasts: Arc::clone(&broadcasts),
+            })
+            .collect()
+    }
+
+    pub(super) async fn host(&self, service: Box<dyn Service<Format>>) -> Option<ServiceRef> {
+        self.services.allocate(service).await
+    }
+
+    pub(super) async fn unhost(&self, service: ServiceRef) -> Option<Deallocated<Format>> {
+        self.services.deallocate(service).await
+    }
+
+    /// Returns receiver of messages broadcast by server. Receiver returned before is closed.
+    pub(super) fn receive_broadcasts(&self) -> mpsc::UnboundedReceiver<Vec<u8>> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        *self
+            .broadcasts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(sender);
+        receiver
+    }
+
+    async fn dispatch_call(
+        &self,
+        kind: ServiceKind,
+        service_id: u64,
+        function_id: u32,
+        args: MultipartReceived,
+        one_way: bool,
+    ) -> Result<MultipartSendable, ServiceCallRequestError> {
+        let ServiceKind::Private = kind else {
+            return Err(ServiceCallRequestError::InvalidServiceId);
+        };
+        let service = self
+            .services
+            .get(service_id)
+            .await
+            .ok_or(ServiceCallRequestError::InvalidServiceId)?;
+        if one_way && !service.is_one_way(function_id) {
+            return Err(ServiceCallRequestError::InvalidFunctionId);
+        }
+
+        service
+            .call(Arc::clone(&self.services), function_id, args)
+            .await
+    }
+}
+
+impl<Format: EncodingFormat> CallHandler for HostedServices<Format> {
+    async fn handle_call(
+        &self,
+        kind: ServiceKind,
+        service_id: u64,
+        function_id: u32,
+        args: MultipartReceived,
+    ) -> Result<MultipartSendable, ServiceCallRequestError> {
+        trace!("Received call of hosted service. Kind: {kind:?}, service id: {service_id}, function_id: {function_id}");
+
+        self.dispatch_call(kind, service_id, function_id, args, false)
+            .await
+    }
+
+    fn handle_promising_call(
+        &self,
+        kind: ServiceKind,
+        service_id: u64,
+        function_id: u32,
+        args: MultipartReceived,
+        _: u32,
+        _: PromisedReturns,
+    ) -> impl Future<Output = Result<MultipartSendable, ServiceCallRequestError>> + Send {
+        // Promised services are never referenced by server, so promises aren't tracked.
+        self.handle_call(kind, service_id, function_id, args)
+    }
+
+    fn handle_one_way_call(
+        &self,
+        kind: ServiceKind,
+        service_id: u64,
+        function_id: u32,
+        args: MultipartReceived,
+    ) {
+        trace!("Received one-way call of hosted service. Kind: {kind:?}, service id: {service_id}, function_id: {function_id}");
+
+        let services = self.clone();
+        tokio::spawn(async move {
+            if let Err(err) = services
+                .dispatch_call(kind, service_id, function_id, args, true)
+                .await
+            {
+                warn!("One-way call of hosted service {service_id}, function id: {function_id} failed: {err}");
+            }
+        });
+    }
+
+    fn handle_service_request(
+        &self,
+        _: &str,
+        _: &[u8],
+    ) -> impl Future<Output = Result<u32, RemoteServiceIdRequestError>> + Send {
+        future::ready(Err(RemoteServiceIdRequestError::ServiceNotFound))
+    }
+
+    fn handle_private_service_deallocation(
+        &self,
+        _: u64,
+    ) -> impl Future<Output = Result<(), InvalidPrivateServiceIdError>> + Send {
+        future::ready(Err(InvalidPrivateServiceIdError))
+    }
+
+    fn handle_promised_service_deallocation(
+        &self,
+        _: u32,
+    ) -> impl Future<Output = Result<(), InvalidPrivateServiceIdError>> + Send {
+        future::ready(Err(InvalidPrivateServiceIdError))
+    }
+
+    fn handle_private_services_deallocation(
+        &self,
+        _: &[u64],
+        _: &[u32],
+    ) -> impl Future<Output = Result<(), InvalidPrivateServiceIdError>> + Send {
+        future::ready(Err(InvalidPrivateServiceIdError))
+    }
+
+    fn handle_private_service_duplication(
+        &self,
+        _: ServiceKind,
+        _: u64,
+    ) -> impl Future<Output = Result<u64, InvalidPrivateServiceIdError>> + Send {
+        future::ready(Err(InvalidPrivateServiceIdError))
+    }
+
+    fn watch_revocations(&self) -> mpsc::UnboundedReceiver<u64> {
+        // Hosted services are freed only by client, so there is nothing to watch.
+        mpsc::unbounded_channel().1
+    }
+
+    async fn revoked_services(&self, service_ids: Vec<u64>) -> RevokedServices {
+        RevokedServices {
+            ids: service_ids,
+            promise_ids: Vec::new(),
+        }
+    }
+
+    fn subscribe(&self, _: &str) -> Result<TopicSubscription, TopicNotFoundError> {
+        Err(TopicNotFoundError)
+    }
+
+    fn handle_broadcast(&self, message: &[u8]) -> Result<(), BroadcastRejectedError> {
+        trace!("Received broadcast of {} bytes", message.len());
+
+        self.broadcasts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+            .ok_or(BroadcastRejectedError)?
+            .send(message.to_vec())
+            .map_err(|_closed| BroadcastRejectedError)
+    }
+
+    fn handle_private_service_renewal(
+        &self,
+        _: u64,
+    ) -> impl Future<Output = Result<(), InvalidPrivateServiceIdError>> + Send {
+        future::ready(Err(InvalidPrivateServiceIdError))
+    }
+
+    fn handle_promised_service_renewal(
+        &self,
+        _: u32,
+    ) -> impl Future<Output = Result<(), InvalidPrivateServiceIdError>> + Send {
+        future::ready(Err(InvalidPrivateServiceIdError))
+    }
+
+    fn handle_private_services_renewal(
+        &self,
+        _: &[u64],
+        _: &[u32],
+    ) -> impl Future<Output = Result<(), InvalidPrivateServiceIdError>> + Send {
+        future::ready(Err(InvalidPrivateServiceIdError))
+    }
+
+    fn private_service_lease(&self) -> Option<Duration> {
+        None
+    }
+}
@@ -0,0 +1,133 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, PoisonError},
+};
+
+use super::{ConnectionOrigin, ServiceHandle};
+use crate::protocol::ServiceKind;
+
+/// Private services leased by client. Their leases are renewed by a single background task with a request per connection.
+#[derive(Default)]
+pub(super) struct LeasedServices(Mutex<Leases>);
+
+#[derive(Default)]
+struct Leases {
+    /// Leased services with count of their leases.
+    services: HashMap<LeaseKey, (ServiceHandle, usize)>,
+    /// Whether renewal task is running.
+    renewing: bool,
+    /// Whether services were leased since renewal task took them last time.
+    changed: bool,
+}
+
+/// Connection, whether service is promised and its id.
+type LeaseKey = (ConnectionOrigin, bool, u64);
+
+fn lease_key(service: ServiceHandle) -> LeaseKey {
+    (
+        service.origin(),
+        matches!(service.kind(), ServiceKind::Promised),
+        service.id(),
+    )
+}
+
+impl LeasedServices {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Leases> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Leases service. Returns `true` if renewal task isn't running, so it must be started.
+    pub(super) fn acquire(&self, service: ServiceHandle) -> bool {
+        let mut leases = self.lock();
+        let (_, count) = leases
+            .services
+            .entry(lease_key(service))
+            .or_insert((service, 0));
+        *count = count.saturating_add(1);
+        leases.changed = true;
+
+        !core::mem::replace(&mut leases.renewing, true)
+    }
+
+    /// Releases lease of service taken with [`acquire`][Self::acquire].
+    pub(super) fn release(&self, service: ServiceHandle) {
+        let mut leases = self.lock();
+        let key = lease_key(service);
+        if let Some((_, count)) = leases.services.get_mut(&key) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                leases.services.remove(&key);
+            }
+        }
+    }
+
+    /// Returns leased services for renewal.
+    pub(super) fn leased(&self) -> Vec<ServiceHandle> {
+        let mut leases = self.lock();
+        leases.changed = false;
+        leases
+            .services
+            .values()
+            .map(|(service, _)| *service)
+            .collect()
+    }
+
+    /// Marks renewal task as stopped unless services were leased since they were taken last time.
+    /// Returns `true` if task must stop.
+    pub(super) fn stop_renewal(&self) -> bool {
+        let mut leases = self.lock();
+        if leases.changed {
+            return false;
+        }
+
+        leases.renewing = false;
+        true
+    }
+
+    /// Marks renewal task as stopped, e.g. when it can't be started.
+    pub(super) fn abort_renewal(&self) {
+        self.lock().renewing = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LeasedServices;
+    use crate::client::{ConnectionOrigin, ServiceHandle};
+
+    fn promised(promise_id: u32) -> ServiceHandle {
+        ServiceHandle::promised(promise_id, ConnectionOrigin::new(0, 0))
+    }
+
+    #[test]
+    fn service_is_leased_until_its_last_lease_is_released() {
+        let leases = LeasedServices::default();
+        leases.acquire(promised(0));
+        leases.acquire(promised(0));
+        leases.acquire(promised(1));
+
+        leases.release(promised(0));
+        leases.release(promised(1));
+        assert_eq!(leases.leased().len(), 1);
+
+        leases.release(promised(0));
+        assert!(leases.leased().is_empty());
+    }
+
+    #[test]
+    fn renewal_is_started_once_and_stopped_only_without_new_leases() {
+        let leases = LeasedServices::default();
+
+        assert!(leases.acquire(promised(0)));
+        assert!(!leases.acquire(promised(1)));
+        // Services leased since renewal task took them last time keep it running.
+        assert!(!leases.stop_renewal());
+
+        leases.leased();
+        assert!(leases.stop_renewal());
+        assert!(leases.acquire(promised(2)));
+
+        leases.abort_renewal();
+        assert!(leases.acquire(promised(3)));
+    }
+}
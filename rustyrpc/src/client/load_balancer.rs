@@ -0,0 +1,94 @@
+use core::{
+    hash::{Hash, Hasher},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use std::collections::hash_map::DefaultHasher;
+
+use super::connection::ConnectionState;
+
+/// Information about endpoint of client used by load balancer to pick endpoint for a call.
+#[derive(Debug, Clone, Copy)]
+pub struct EndpointInfo {
+    /// State of connection to endpoint.
+    pub state: ConnectionState,
+    /// Count of calls sent to endpoint and not finished yet.
+    pub outstanding_calls: usize,
+}
+
+/// Strategy of distributing calls of public services between endpoints of [`Client`][super::Client].
+/// Calls of private services are always sent to endpoint that allocated them.
+pub trait LoadBalancer: Send + Sync + 'static {
+    /// Picks index of endpoint for a call. Key is present if call specifies [routing key][super::CallOptions::with_routing_key].
+    fn pick(&self, endpoints: &[EndpointInfo], key: Option<u64>) -> usize;
+}
+
+/// Returns indices of connected endpoints or indices of all endpoints if none of them is connected.
+fn candidates(endpoints: &[EndpointInfo]) -> Vec<usize> {
+    let connected: Vec<usize> = endpoints
+        .iter()
+        .enumerate()
+        .filter(|(_, endpoint)| endpoint.state == ConnectionState::Connected)
+        .map(|(index, _)| index)
+        .collect();
+
+    if connected.is_empty() {
+        (0..endpoints.len()).collect()
+    } else {
+        connected
+    }
+}
+
+/// Sends calls to connected endpoints in turn.
+#[derive(Debug, Default)]
+pub struct RoundRobin(AtomicUsize);
+
+impl LoadBalancer for RoundRobin {
+    fn pick(&self, endpoints: &[EndpointInfo], _key: Option<u64>) -> usize {
+        let candidates = candidates(endpoints);
+        let turn = self.0.fetch_add(1, Ordering::Relaxed);
+
+        turn.checked_rem(candidates.len())
+            .and_then(|position| candidates.get(position))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+/// Sends calls to connected endpoint with the least count of outstanding calls.
+#[derive(Debug, Default)]
+pub struct LeastOutstanding;
+
+impl LoadBalancer for LeastOutstanding {
+    fn pick(&self, endpoints: &[EndpointInfo], _key: Option<u64>) -> usize {
+        candidates(endpoints)
+            .into_iter()
+            .min_by_key(|index| {
+                endpoints
+                    .get(*index)
+                    .map_or(usize::MAX, |endpoint| endpoint.outstanding_calls)
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Sends calls with the same routing key to the same endpoint using rendezvous hashing,
+/// so only keys of lost endpoint move to other endpoints. Calls without key are distributed in turn.
+#[derive(Debug, Default)]
+pub struct ConsistentHash(RoundRobin);
+
+impl LoadBalancer for ConsistentHash {
+    fn pick(&self, endpoints: &[EndpointInfo], key: Option<u64>) -> usize {
+        let Some(key) = key else {
+            return self.0.pick(endpoints, None);
+        };
+
+        candidates(endpoints)
+            .into_iter()
+            .max_by_key(|index| {
+                let mut hasher = DefaultHasher::new();
+                (key, index).hash(&mut hasher);
+                hasher.finish()
+            })
+            .unwrap_or_default()
+    }
+}
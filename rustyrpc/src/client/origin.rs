@@ -0,0 +1,35 @@
+use core::cell::Cell;
+
+/// Connection of client which executed a call, identified by endpoint index and connection generation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct ConnectionOrigin {
+    pub(crate) endpoint: usize,
+    pub(crate) generation: u64,
+}
+
+thread_local! {
+    static DECODING_ORIGIN: Cell<Option<ConnectionOrigin>> = const { Cell::new(None) };
+}
+
+impl ConnectionOrigin {
+    pub(crate) const fn new(endpoint: usize, generation: u64) -> Self {
+        Self {
+            endpoint,
+            generation,
+        }
+    }
+
+    /// Returns connection whose response is being decoded now, so decoded service references remember where they came from.
+    pub(crate) fn of_decoded() -> Option<Self> {
+        DECODING_ORIGIN.with(Cell::get)
+    }
+
+    /// Runs decoding of response received on this connection.
+    pub(crate) fn decode_within<T, F: FnOnce() -> T>(self, decode: F) -> T {
+        let previous = DECODING_ORIGIN.with(|origin| origin.replace(Some(self)));
+        let decoded = decode();
+        DECODING_ORIGIN.with(|origin| origin.set(previous));
+
+        decoded
+    }
+}
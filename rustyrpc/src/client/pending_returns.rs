@@ -0,0 +1,76 @@
+use core::marker::PhantomData;
+use std::io;
+
+use super::{Client, ConnectionOrigin};
+use crate::{
+    format::{self, Decode, DecodeZeroCopy, DecodeZeroCopyFallible, Encode},
+    protocol::{PromisedReturns, RequestKind, ServiceCallRequestResult, ServiceIdRequestResult},
+    server::{ServiceRef, TypedServiceRef},
+    service::ServiceDescriptor,
+    transport,
+};
+
+/// Type returned by function called with [pipelined call][Client::call_service_pipelined], so server knows which private service is promised.
+pub trait PromisingReturns {
+    /// How function returns promised private service.
+    const PROMISED_RETURNS: PromisedReturns;
+}
+
+impl PromisingReturns for ServiceRef {
+    const PROMISED_RETURNS: PromisedReturns = PromisedReturns::Service;
+}
+
+impl<S: ServiceDescriptor> PromisingReturns for TypedServiceRef<S> {
+    const PROMISED_RETURNS: PromisedReturns = PromisedReturns::Service;
+}
+
+impl PromisingReturns for Option<ServiceRef> {
+    const PROMISED_RETURNS: PromisedReturns = PromisedReturns::OptionalService;
+}
+
+impl<S: ServiceDescriptor> PromisingReturns for Option<TypedServiceRef<S>> {
+    const PROMISED_RETURNS: PromisedReturns = PromisedReturns::OptionalService;
+}
+
+/// Result of [pipelined call][Client::call_service_pipelined] that may be not received yet.
+pub struct PendingReturns<Connection: transport::ClientConnection, Format, Returns> {
+    request_stream: Connection::Stream,
+    origin: ConnectionOrigin,
+    _returns: PhantomData<fn() -> (Format, Returns)>,
+}
+
+impl<Connection: transport::ClientConnection, Format, Returns>
+    PendingReturns<Connection, Format, Returns>
+{
+    pub(super) const fn new(request_stream: Connection::Stream, origin: ConnectionOrigin) -> Self {
+        Self {
+            request_stream,
+            origin,
+            _returns: PhantomData,
+        }
+    }
+}
+
+impl<Connection, Format, Returns> PendingReturns<Connection, Format, Returns>
+where
+    Connection: transport::ClientConnection,
+    Format: format::ZeroCopyEncodingFormat,
+    Returns: Decode<Format>,
+    for<'a> RequestKind<'a>: Encode<Format>,
+    for<'a> ServiceCallRequestResult<'a>: DecodeZeroCopy<
+        'a,
+        Format,
+        <ServiceCallRequestResult<'a> as DecodeZeroCopyFallible<Format>>::Error,
+    >,
+    ServiceIdRequestResult: Decode<Format>,
+{
+    /// Waits for result of the call.
+    ///
+    /// # Errors
+    /// Returns an error if the call fails.
+    pub async fn receive(mut self) -> io::Result<Returns> {
+        let response_multipart =
+            Client::<Connection, Format>::receive_returns(&mut self.request_stream).await?;
+        Client::<Connection, Format>::decode_returns(&response_multipart, self.origin)
+    }
+}
@@ -0,0 +1,290 @@
+use core::time::Duration;
+use std::io;
+
+use rand::Rng;
+
+use super::ServiceHandle;
+use crate::{multipart::MultipartSendable, protocol::ServiceCallRequestError};
+
+/// Policy of retrying idempotent calls that failed with a retryable error and any calls rejected by overloaded server.
+///
+/// Delay before each retry grows exponentially starting from initial backoff and is capped by max backoff.
+/// If jitter is enabled, actual delay is picked randomly between zero and computed backoff.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    multiplier: u32,
+    jitter: bool,
+}
+
+impl RetryPolicy {
+    /// Creates policy that never retries calls.
+    #[must_use]
+    pub const fn disabled() -> Self {
+        Self {
+            max_attempts: 1,
+            initial_backoff: Duration::ZERO,
+            max_backoff: Duration::ZERO,
+            multiplier: 1,
+            jitter: false,
+        }
+    }
+
+    /// Sets maximum number of attempts including the first one.
+    #[must_use]
+    pub const fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Sets backoff before the first retry.
+    #[must_use]
+    pub const fn with_initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    /// Sets upper bound of backoff.
+    #[must_use]
+    pub const fn with_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Sets factor backoff is multiplied by after each retry.
+    #[must_use]
+    pub const fn with_multiplier(mut self, multiplier: u32) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+
+    /// Enables or disables randomization of backoff.
+    #[must_use]
+    pub const fn with_jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub(super) const fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Returns delay before retry with specified number (starting from zero).
+    pub(super) fn backoff(&self, retry: u32) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(self.multiplier.saturating_pow(retry))
+            .min(self.max_backoff);
+
+        if self.jitter {
+            rand::thread_rng().gen_range(Duration::ZERO..=backoff)
+        } else {
+            backoff
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            multiplier: 2,
+            jitter: true,
+        }
+    }
+}
+
+/// Single attempt of call.
+#[derive(Clone, Copy)]
+pub(super) struct CallAttempt<'a> {
+    pub(super) service: ServiceHandle,
+    pub(super) function_id: u32,
+    pub(super) args: &'a MultipartSendable,
+    pub(super) part_sizes: &'a [u32],
+    /// Endpoints public service shouldn't be called on unless no others are left.
+    pub(super) excluded: &'a [usize],
+}
+
+/// Error of a single call attempt, remembering whether request reached the server.
+pub(super) enum CallAttemptError {
+    /// Failed before request was completely sent.
+    NotSent(io::Error),
+    /// Failed after request was sent to endpoint with specified index.
+    Sent { error: io::Error, endpoint: usize },
+}
+
+impl CallAttemptError {
+    /// Checks whether failure belongs to one of retryable classes: stream reset, connection lost before request was sent, overloaded server or exceeded rate limit.
+    pub(super) fn is_retryable(&self) -> bool {
+        match self {
+            Self::NotSent(err) => matches!(
+                err.kind(),
+                io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::NotConnected
+                    | io::ErrorKind::BrokenPipe
+            ),
+            Self::Sent { error, .. } => {
+                error.kind() == io::ErrorKind::ConnectionReset
+                    || error
+                        .get_ref()
+                        .and_then(|inner| inner.downcast_ref::<ServiceCallRequestError>())
+                        .is_some_and(|call_error| {
+                            matches!(
+                                call_error,
+                                ServiceCallRequestError::Overloaded
+                                    | ServiceCallRequestError::ResourceExhausted { .. }
+                            )
+                        })
+            }
+        }
+    }
+
+    /// Returns index of endpoint that rejected call as overloaded, so retry is sent to another endpoint.
+    pub(super) fn overloaded_endpoint(&self) -> Option<usize> {
+        let Self::Sent { error, endpoint } = self else {
+            return None;
+        };
+
+        error
+            .get_ref()?
+            .downcast_ref::<ServiceCallRequestError>()
+            .is_some_and(|call_error| matches!(call_error, ServiceCallRequestError::Overloaded))
+            .then_some(*endpoint)
+    }
+
+    /// Returns time server asked to wait before retry, if any.
+    pub(super) fn retry_after(&self) -> Option<Duration> {
+        if let ServiceCallRequestError::ResourceExhausted { retry_after } = self
+            .as_inner()
+            .get_ref()?
+            .downcast_ref::<ServiceCallRequestError>()?
+        {
+            Some(*retry_after)
+        } else {
+            None
+        }
+    }
+
+    pub(super) const fn as_inner(&self) -> &io::Error {
+        match self {
+            Self::NotSent(error) | Self::Sent { error, .. } => error,
+        }
+    }
+
+    pub(super) fn into_inner(self) -> io::Error {
+        match self {
+            Self::NotSent(error) | Self::Sent { error, .. } => error,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+    use std::io;
+
+    use super::{CallAttemptError, RetryPolicy};
+    use crate::protocol::ServiceCallRequestError;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy::disabled()
+            .with_max_attempts(5)
+            .with_initial_backoff(Duration::from_millis(100))
+            .with_max_backoff(Duration::from_secs(1))
+            .with_multiplier(3)
+    }
+
+    fn rejected(error: ServiceCallRequestError) -> CallAttemptError {
+        CallAttemptError::Sent {
+            error: error.into(),
+            endpoint: 1,
+        }
+    }
+
+    #[test]
+    fn backoff_grows_exponentially_up_to_max_backoff() {
+        let policy = policy();
+
+        assert_eq!(policy.backoff(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff(1), Duration::from_millis(300));
+        assert_eq!(policy.backoff(2), Duration::from_millis(900));
+        assert_eq!(policy.backoff(3), Duration::from_secs(1));
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn jittered_backoff_doesnt_exceed_computed_backoff() {
+        let policy = policy().with_jitter(true);
+
+        for retry in 0..10 {
+            let computed = policy.clone().with_jitter(false).backoff(retry);
+            for _ in 0..100u32 {
+                assert!(policy.backoff(retry) <= computed);
+            }
+        }
+    }
+
+    #[test]
+    fn disabled_policy_makes_a_single_attempt_without_backoff() {
+        let policy = RetryPolicy::disabled();
+
+        assert_eq!(policy.max_attempts(), 1);
+        assert_eq!(policy.backoff(0), Duration::ZERO);
+    }
+
+    #[test]
+    fn connection_failures_before_sending_are_retryable() {
+        for kind in [
+            io::ErrorKind::ConnectionReset,
+            io::ErrorKind::ConnectionAborted,
+            io::ErrorKind::NotConnected,
+            io::ErrorKind::BrokenPipe,
+        ] {
+            assert!(CallAttemptError::NotSent(kind.into()).is_retryable());
+        }
+        assert!(!CallAttemptError::NotSent(io::ErrorKind::InvalidData.into()).is_retryable());
+    }
+
+    #[test]
+    fn only_reset_and_rejections_by_server_are_retryable_after_sending() {
+        let reset = CallAttemptError::Sent {
+            error: io::ErrorKind::ConnectionReset.into(),
+            endpoint: 0,
+        };
+        let lost = CallAttemptError::Sent {
+            error: io::ErrorKind::NotConnected.into(),
+            endpoint: 0,
+        };
+
+        assert!(reset.is_retryable());
+        assert!(!lost.is_retryable());
+        assert!(rejected(ServiceCallRequestError::Overloaded).is_retryable());
+        assert!(rejected(ServiceCallRequestError::ResourceExhausted {
+            retry_after: Duration::ZERO
+        })
+        .is_retryable());
+        assert!(!rejected(ServiceCallRequestError::InvalidServiceId).is_retryable());
+    }
+
+    #[test]
+    fn overloaded_endpoint_and_retry_after_are_taken_from_rejection() {
+        let overloaded = rejected(ServiceCallRequestError::Overloaded);
+        let exhausted = rejected(ServiceCallRequestError::ResourceExhausted {
+            retry_after: Duration::from_secs(2),
+        });
+
+        assert_eq!(overloaded.overloaded_endpoint(), Some(1));
+        assert_eq!(overloaded.retry_after(), None);
+        assert_eq!(exhausted.overloaded_endpoint(), None);
+        assert_eq!(exhausted.retry_after(), Some(Duration::from_secs(2)));
+        assert_eq!(
+            CallAttemptError::NotSent(io::ErrorKind::Other.into()).overloaded_endpoint(),
+            None
+        );
+    }
+}
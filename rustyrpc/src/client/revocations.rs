@@ -0,0 +1,131 @@
+use core::{
+    pin::pin,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use std::{
+    collections::HashSet,
+    sync::{Mutex, PoisonError},
+};
+use tokio::sync::Notify;
+
+use crate::protocol::{RevokedServices, ServiceKind};
+
+/// Private services of connection revoked by server.
+#[derive(Default)]
+pub(super) struct Revocations {
+    revoked_ids: Mutex<HashSet<u64>>,
+    revoked_promise_ids: Mutex<HashSet<u32>>,
+    watching: AtomicBool,
+    closed: AtomicBool,
+    changed: Notify,
+}
+
+impl Revocations {
+    /// Returns `true` only for the first call, so watching of revocations is started once per connection.
+    pub(super) fn start_watching(&self) -> bool {
+        !self.watching.swap(true, Ordering::AcqRel)
+    }
+
+    pub(super) fn revoke(&self, revoked: RevokedServices) {
+        self.revoked_ids
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .extend(revoked.ids);
+        self.revoked_promise_ids
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .extend(revoked.promise_ids);
+        self.changed.notify_waiters();
+    }
+
+    /// Forgets revocation of deallocated service, so revoked ids don't pile up for the connection's lifetime.
+    /// Ids aren't reused by server, so forgotten revocation can't be mistaken for another service.
+    pub(super) fn forget(&self, kind: ServiceKind, id: u64) {
+        match kind {
+            ServiceKind::Promised => {
+                if let Ok(promise_id) = u32::try_from(id) {
+                    self.revoked_promise_ids
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .remove(&promise_id);
+                }
+            }
+            ServiceKind::Private => {
+                self.revoked_ids
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .remove(&id);
+            }
+            ServiceKind::Public => {}
+        }
+    }
+
+    /// Marks all services as revoked because connection is lost or revocations can't be watched.
+    pub(super) fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+        self.changed.notify_waiters();
+    }
+
+    /// Waits until service is revoked or connection is closed.
+    pub(super) async fn revoked(&self, kind: ServiceKind, id: u64) {
+        loop {
+            let mut changed = pin!(self.changed.notified());
+            changed.as_mut().enable();
+
+            if self.closed.load(Ordering::Acquire) || self.is_revoked(kind, id) {
+                return;
+            }
+            changed.await;
+        }
+    }
+
+    fn is_revoked(&self, kind: ServiceKind, id: u64) -> bool {
+        match kind {
+            ServiceKind::Promised => u32::try_from(id).is_ok_and(|promise_id| {
+                self.revoked_promise_ids
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .contains(&promise_id)
+            }),
+            ServiceKind::Private => self
+                .revoked_ids
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .contains(&id),
+            ServiceKind::Public => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Revocations;
+    use crate::protocol::{RevokedServices, ServiceKind};
+
+    #[test]
+    fn forgotten_revocations_are_removed() {
+        let revocations = Revocations::default();
+        revocations.revoke(RevokedServices {
+            ids: vec![1, 2],
+            promise_ids: vec![3],
+        });
+
+        revocations.forget(ServiceKind::Private, 1);
+        revocations.forget(ServiceKind::Promised, 3);
+
+        assert!(!revocations.is_revoked(ServiceKind::Private, 1));
+        assert!(revocations.is_revoked(ServiceKind::Private, 2));
+        assert!(!revocations.is_revoked(ServiceKind::Promised, 3));
+    }
+
+    #[test]
+    fn public_services_are_never_revoked() {
+        let revocations = Revocations::default();
+        revocations.revoke(RevokedServices {
+            ids: vec![1],
+            promise_ids: Vec::new(),
+        });
+
+        assert!(!revocations.is_revoked(ServiceKind::Public, 1));
+    }
+}
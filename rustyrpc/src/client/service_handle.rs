@@ -0,0 +1,88 @@
+use super::ConnectionOrigin;
+use crate::protocol::ServiceKind;
+
+#[derive(Debug, Clone, Copy)]
+enum Service {
+    Public {
+        name: &'static str,
+        checksum: &'static [u8],
+    },
+    Private,
+    Promised,
+}
+
+/// Handle of remote service used by service clients to call it via [`Client`][super::Client].
+///
+/// Remembers endpoint and connection service was obtained on, so public services are found again after reconnection
+/// and calls of private services are sent to endpoint that allocated them and fail if it's connection is lost.
+#[derive(Debug, Clone, Copy)]
+pub struct ServiceHandle {
+    service: Service,
+    id: u64,
+    origin: ConnectionOrigin,
+}
+
+impl ServiceHandle {
+    pub(super) fn public(
+        name: &'static str,
+        checksum: &'static [u8],
+        id: u32,
+        origin: ConnectionOrigin,
+    ) -> Self {
+        Self {
+            service: Service::Public { name, checksum },
+            id: u64::from(id),
+            origin,
+        }
+    }
+
+    pub(super) const fn private(id: u64, origin: ConnectionOrigin) -> Self {
+        Self {
+            service: Service::Private,
+            id,
+            origin,
+        }
+    }
+
+    pub(super) fn promised(promise_id: u32, origin: ConnectionOrigin) -> Self {
+        Self {
+            service: Service::Promised,
+            id: u64::from(promise_id),
+            origin,
+        }
+    }
+
+    /// Returns kind of service.
+    #[must_use]
+    pub const fn kind(&self) -> ServiceKind {
+        match self.service {
+            Service::Public { .. } => ServiceKind::Public,
+            Service::Private => ServiceKind::Private,
+            Service::Promised => ServiceKind::Promised,
+        }
+    }
+
+    /// Returns service id on connection it was obtained on or promise id for promised service.
+    #[must_use]
+    pub const fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Returns index of endpoint service was obtained from, in order endpoints were specified.
+    #[must_use]
+    pub const fn endpoint(&self) -> usize {
+        self.origin.endpoint
+    }
+
+    pub(crate) const fn origin(&self) -> ConnectionOrigin {
+        self.origin
+    }
+
+    /// Returns name and checksum of public service.
+    pub(super) const fn public_service(&self) -> Option<(&'static str, &'static [u8])> {
+        match self.service {
+            Service::Public { name, checksum } => Some((name, checksum)),
+            Service::Private | Service::Promised => None,
+        }
+    }
+}
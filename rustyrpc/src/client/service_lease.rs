@@ -0,0 +1,23 @@
+use alloc::sync::Weak;
+
+use super::{leased_services::LeasedServices, ServiceHandle};
+
+/// Keeps lease of private service renewed until dropped, see [`Client::lease_private_service`][super::Client::lease_private_service].
+#[must_use = "Lease isn't renewed once guard is dropped"]
+pub struct ServiceLease(Option<(Weak<LeasedServices>, ServiceHandle)>);
+
+impl ServiceLease {
+    pub(super) const fn new(lease: Option<(Weak<LeasedServices>, ServiceHandle)>) -> Self {
+        Self(lease)
+    }
+}
+
+impl Drop for ServiceLease {
+    fn drop(&mut self) {
+        if let Some((leased_services, service)) = &self.0
+            && let Some(leased_services) = leased_services.upgrade()
+        {
+            leased_services.release(*service);
+        }
+    }
+}
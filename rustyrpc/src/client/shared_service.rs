@@ -0,0 +1,42 @@
+use alloc::sync::Arc;
+
+use super::{ServiceHandle, ServiceLease};
+
+type Release = Box<dyn FnOnce(ServiceHandle) + Send + Sync>;
+
+struct SharedServiceOwner {
+    service: ServiceHandle,
+    _lease: ServiceLease,
+    release: Option<Release>,
+}
+
+impl Drop for SharedServiceOwner {
+    fn drop(&mut self) {
+        if let Some(release) = self.release.take() {
+            release(self.service);
+        }
+    }
+}
+
+/// Reference counted handle of remote service, so service clients holding it can be cloned.
+///
+/// Private service is leased while any clone is alive and queued for deallocation once the last clone is dropped.
+/// Created by [`Client::share_service`][super::Client::share_service].
+#[derive(Clone)]
+pub struct SharedService(Arc<SharedServiceOwner>);
+
+impl SharedService {
+    pub(super) fn new(service: ServiceHandle, lease: ServiceLease, release: Release) -> Self {
+        Self(Arc::new(SharedServiceOwner {
+            service,
+            _lease: lease,
+            release: Some(release),
+        }))
+    }
+
+    /// Returns handle of shared service.
+    #[must_use]
+    pub fn handle(&self) -> ServiceHandle {
+        self.0.service
+    }
+}
@@ -0,0 +1,38 @@
+use core::marker::PhantomData;
+use std::io;
+
+use crate::{
+    format::{Decode, EncodingFormat},
+    transport::{self, StreamExt},
+};
+
+/// Subscription to server topic, see [`Client::subscribe`][super::Client::subscribe].
+/// Client is unsubscribed once subscription is dropped.
+pub struct Subscription<Connection: transport::ClientConnection, Format: EncodingFormat, Event> {
+    stream: Connection::Stream,
+    _phantom: PhantomData<fn() -> (Format, Event)>,
+}
+
+impl<Connection: transport::ClientConnection, Format: EncodingFormat, Event>
+    Subscription<Connection, Format, Event>
+{
+    pub(super) const fn new(stream: Connection::Stream) -> Self {
+        Self {
+            stream,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<Connection: transport::ClientConnection, Format: EncodingFormat, Event: Decode<Format>>
+    Subscription<Connection, Format, Event>
+{
+    /// Receives next event published to topic.
+    /// Returns `None` once subscription is closed by server, e.g. when client is disconnected as slow subscriber.
+    pub async fn next(&mut self) -> Option<io::Result<Event>> {
+        match self.stream.receive_decodable::<Event, Format>().await {
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => None,
+            result => Some(result),
+        }
+    }
+}
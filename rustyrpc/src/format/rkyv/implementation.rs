@@ -7,7 +7,6 @@ use rkyv::{
 use crate::{
     format::{Decode, Encode},
     protocol,
-    server::ServiceRef,
 };
 
 use super::{RkyvDeserializationError, RkyvFormat};
@@ -20,8 +19,6 @@ impl !DefaultEncode for protocol::ServiceIdRequestResult {}
 impl<'a> !DefaultEncode for protocol::ServiceCallRequestResult<'a> {}
 #[allow(suspicious_auto_trait_impls)]
 impl !DefaultEncode for protocol::PrivateServiceDeallocateRequestResult {}
-#[allow(suspicious_auto_trait_impls)]
-impl !DefaultEncode for Option<ServiceRef> {}
 
 impl<T> Encode<RkyvFormat> for T
 where
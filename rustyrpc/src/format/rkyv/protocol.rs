@@ -1,6 +1,12 @@
+mod batched_call;
+mod credentials;
 mod error;
+mod private_service_lease;
+mod promised_returns;
 mod request_kind;
 mod result;
+mod revoked_services;
 mod service_found;
 mod service_kind;
+mod service_query;
 mod service_ref;
@@ -0,0 +1,37 @@
+use rkyv::{with::RefAsBox, Archive, Serialize};
+
+use crate::protocol;
+
+use super::service_kind::ServiceKind;
+
+#[derive(Serialize, Archive)]
+#[archive(check_bytes)]
+pub struct BatchedCall<'a> {
+    kind: ServiceKind,
+    id: u64,
+    function_id: u32,
+    #[with(RefAsBox)]
+    part_sizes: &'a [u32],
+}
+
+impl<'a> From<&protocol::BatchedCall<'a>> for BatchedCall<'a> {
+    fn from(value: &protocol::BatchedCall<'a>) -> Self {
+        Self {
+            kind: value.kind.into(),
+            id: value.id,
+            function_id: value.function_id,
+            part_sizes: value.part_sizes,
+        }
+    }
+}
+
+impl<'a> From<&'a ArchivedBatchedCall<'_>> for protocol::BatchedCall<'a> {
+    fn from(value: &'a ArchivedBatchedCall<'_>) -> Self {
+        Self {
+            kind: (&value.kind).into(),
+            id: value.id,
+            function_id: value.function_id,
+            part_sizes: &value.part_sizes,
+        }
+    }
+}
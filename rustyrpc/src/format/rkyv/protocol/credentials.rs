@@ -0,0 +1,40 @@
+use rkyv::{with::RefAsBox, Archive, Serialize};
+
+use crate::protocol;
+
+#[derive(Serialize, Archive)]
+#[archive(check_bytes)]
+pub enum Credentials<'a> {
+    Token(#[with(RefAsBox)] &'a str),
+    Password {
+        #[with(RefAsBox)]
+        username: &'a str,
+        #[with(RefAsBox)]
+        password: &'a str,
+    },
+    Transport,
+}
+
+impl<'a> From<protocol::Credentials<'a>> for Credentials<'a> {
+    fn from(value: protocol::Credentials<'a>) -> Self {
+        match value {
+            protocol::Credentials::Token(token) => Self::Token(token),
+            protocol::Credentials::Password { username, password } => {
+                Self::Password { username, password }
+            }
+            protocol::Credentials::Transport => Self::Transport,
+        }
+    }
+}
+
+impl<'a> From<&'a ArchivedCredentials<'_>> for protocol::Credentials<'a> {
+    fn from(value: &'a ArchivedCredentials<'_>) -> Self {
+        match value {
+            ArchivedCredentials::Token(token) => Self::Token(token),
+            ArchivedCredentials::Password { username, password } => {
+                Self::Password { username, password }
+            }
+            ArchivedCredentials::Transport => Self::Transport,
+        }
+    }
+}
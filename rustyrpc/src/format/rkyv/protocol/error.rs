@@ -1,3 +1,4 @@
+use core::time::Duration;
 use rkyv::{Archive, Deserialize, Serialize};
 
 use crate::protocol;
@@ -34,6 +35,9 @@ pub enum ServiceCallRequestError {
     InvalidFunctionId,
     ArgsDecode,
     ReturnsDecode,
+    Overloaded,
+    PermissionDenied,
+    ResourceExhausted { retry_after_micros: u64 },
 }
 
 impl From<&ArchivedServiceCallRequestError> for protocol::ServiceCallRequestError {
@@ -43,6 +47,13 @@ impl From<&ArchivedServiceCallRequestError> for protocol::ServiceCallRequestErro
             ArchivedServiceCallRequestError::InvalidFunctionId => Self::InvalidFunctionId,
             ArchivedServiceCallRequestError::ArgsDecode => Self::ArgsDecode,
             ArchivedServiceCallRequestError::ReturnsDecode => Self::ServerInternal,
+            ArchivedServiceCallRequestError::Overloaded => Self::Overloaded,
+            ArchivedServiceCallRequestError::PermissionDenied => Self::PermissionDenied,
+            ArchivedServiceCallRequestError::ResourceExhausted { retry_after_micros } => {
+                Self::ResourceExhausted {
+                    retry_after: Duration::from_micros(*retry_after_micros),
+                }
+            }
         }
     }
 }
@@ -54,6 +65,13 @@ impl From<&protocol::ServiceCallRequestError> for ServiceCallRequestError {
             protocol::ServiceCallRequestError::InvalidFunctionId => Self::InvalidFunctionId,
             protocol::ServiceCallRequestError::ArgsDecode => Self::ArgsDecode,
             protocol::ServiceCallRequestError::ServerInternal => Self::ReturnsDecode,
+            protocol::ServiceCallRequestError::Overloaded => Self::Overloaded,
+            protocol::ServiceCallRequestError::PermissionDenied => Self::PermissionDenied,
+            protocol::ServiceCallRequestError::ResourceExhausted { retry_after } => {
+                Self::ResourceExhausted {
+                    retry_after_micros: retry_after.as_micros().try_into().unwrap_or(u64::MAX),
+                }
+            }
         }
     }
 }
@@ -73,3 +91,51 @@ impl From<&protocol::InvalidPrivateServiceIdError> for InvalidPrivateServiceIdEr
         Self
     }
 }
+
+#[derive(Serialize, Deserialize, Archive)]
+#[archive(check_bytes)]
+pub struct TopicNotFoundError;
+
+impl From<TopicNotFoundError> for protocol::TopicNotFoundError {
+    fn from(_error: TopicNotFoundError) -> Self {
+        Self
+    }
+}
+
+impl From<&protocol::TopicNotFoundError> for TopicNotFoundError {
+    fn from(_error: &protocol::TopicNotFoundError) -> Self {
+        Self
+    }
+}
+
+#[derive(Serialize, Deserialize, Archive)]
+#[archive(check_bytes)]
+pub struct BroadcastRejectedError;
+
+impl From<BroadcastRejectedError> for protocol::BroadcastRejectedError {
+    fn from(_error: BroadcastRejectedError) -> Self {
+        Self
+    }
+}
+
+impl From<&protocol::BroadcastRejectedError> for BroadcastRejectedError {
+    fn from(_error: &protocol::BroadcastRejectedError) -> Self {
+        Self
+    }
+}
+
+#[derive(Serialize, Deserialize, Archive)]
+#[archive(check_bytes)]
+pub struct AuthenticationError;
+
+impl From<AuthenticationError> for protocol::AuthenticationError {
+    fn from(_error: AuthenticationError) -> Self {
+        Self
+    }
+}
+
+impl From<&protocol::AuthenticationError> for AuthenticationError {
+    fn from(_error: &protocol::AuthenticationError) -> Self {
+        Self
+    }
+}
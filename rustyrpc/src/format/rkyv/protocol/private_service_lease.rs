@@ -0,0 +1,26 @@
+use core::time::Duration;
+
+use crate::{
+    format::{rkyv::RkyvFormat, Decode, Encode},
+    protocol,
+};
+
+impl Encode<RkyvFormat> for protocol::PrivateServiceLease {
+    type Error = <Option<u64> as Encode<RkyvFormat>>::Error;
+
+    fn encode(&self) -> Result<Vec<u8>, Self::Error> {
+        self.0
+            .map(|lease| lease.as_micros().try_into().unwrap_or(u64::MAX))
+            .encode()
+    }
+}
+
+impl Decode<RkyvFormat> for protocol::PrivateServiceLease {
+    type Error = <Option<u64> as Decode<RkyvFormat>>::Error;
+
+    fn decode(buffer: &[u8]) -> Result<Self, Self::Error> {
+        Ok(Self(
+            Option::<u64>::decode(buffer)?.map(Duration::from_micros),
+        ))
+    }
+}
@@ -0,0 +1,28 @@
+use rkyv::{Archive, Deserialize, Serialize};
+
+use crate::protocol;
+
+#[derive(Serialize, Deserialize, Archive)]
+#[archive(check_bytes)]
+pub enum PromisedReturns {
+    Service,
+    OptionalService,
+}
+
+impl From<protocol::PromisedReturns> for PromisedReturns {
+    fn from(value: protocol::PromisedReturns) -> Self {
+        match value {
+            protocol::PromisedReturns::Service => Self::Service,
+            protocol::PromisedReturns::OptionalService => Self::OptionalService,
+        }
+    }
+}
+
+impl From<&ArchivedPromisedReturns> for protocol::PromisedReturns {
+    fn from(value: &ArchivedPromisedReturns) -> Self {
+        match value {
+            ArchivedPromisedReturns::Service => Self::Service,
+            ArchivedPromisedReturns::OptionalService => Self::OptionalService,
+        }
+    }
+}
@@ -8,7 +8,10 @@ use crate::{
     impl_decode_zero_copy, protocol,
 };
 
-use super::service_kind::ServiceKind;
+use super::{
+    batched_call::BatchedCall, credentials::Credentials, promised_returns::PromisedReturns,
+    service_kind::ServiceKind, service_query::ServiceQuery,
+};
 
 #[derive(Serialize, Archive)]
 #[archive(check_bytes)]
@@ -19,16 +22,77 @@ pub enum RequestKind<'a> {
         #[with(RefAsBox)]
         checksum: &'a [u8],
     },
+    ServiceIds {
+        services: Vec<ServiceQuery<'a>>,
+    },
     ServiceCall {
         kind: ServiceKind,
-        id: u32,
+        id: u64,
+        function_id: u32,
+        #[with(RefAsBox)]
+        part_sizes: &'a [u32],
+    },
+    PromisingCall {
+        kind: ServiceKind,
+        id: u64,
+        function_id: u32,
+        #[with(RefAsBox)]
+        part_sizes: &'a [u32],
+        promise_id: u32,
+        returns: PromisedReturns,
+    },
+    BatchCall {
+        ordered: bool,
+        calls: Vec<BatchedCall<'a>>,
+    },
+    OneWayCall {
+        kind: ServiceKind,
+        id: u64,
         function_id: u32,
         #[with(RefAsBox)]
         part_sizes: &'a [u32],
     },
     DeallocatePrivateService {
+        id: u64,
+    },
+    DeallocatePromisedService {
         id: u32,
     },
+    DeallocatePrivateServices {
+        #[with(RefAsBox)]
+        ids: &'a [u64],
+        #[with(RefAsBox)]
+        promise_ids: &'a [u32],
+    },
+    DuplicatePrivateService {
+        kind: ServiceKind,
+        id: u64,
+    },
+    WatchRevocations,
+    Subscribe {
+        #[with(RefAsBox)]
+        topic: &'a str,
+    },
+    Broadcast {
+        #[with(RefAsBox)]
+        message: &'a [u8],
+    },
+    Authenticate {
+        credentials: Credentials<'a>,
+    },
+    RenewPrivateService {
+        id: u64,
+    },
+    RenewPromisedService {
+        id: u32,
+    },
+    RenewPrivateServices {
+        #[with(RefAsBox)]
+        ids: &'a [u64],
+        #[with(RefAsBox)]
+        promise_ids: &'a [u32],
+    },
+    PrivateServiceLease,
 }
 
 impl_decode_zero_copy!(RequestKind<'_> as ArchivedRequestKind<'_>);
@@ -39,6 +103,9 @@ impl<'a> From<&protocol::RequestKind<'a>> for RequestKind<'a> {
             protocol::RequestKind::ServiceId { name, checksum } => {
                 Self::ServiceId { name, checksum }
             }
+            protocol::RequestKind::ServiceIds { services } => Self::ServiceIds {
+                services: services.iter().map(Into::into).collect(),
+            },
             protocol::RequestKind::ServiceCall {
                 kind,
                 id,
@@ -50,9 +117,67 @@ impl<'a> From<&protocol::RequestKind<'a>> for RequestKind<'a> {
                 function_id: *function_id,
                 part_sizes,
             },
+            protocol::RequestKind::PromisingCall {
+                kind,
+                id,
+                function_id,
+                part_sizes,
+                promise_id,
+                returns,
+            } => Self::PromisingCall {
+                kind: (*kind).into(),
+                id: *id,
+                function_id: *function_id,
+                part_sizes,
+                promise_id: *promise_id,
+                returns: (*returns).into(),
+            },
+            protocol::RequestKind::BatchCall { ordered, calls } => Self::BatchCall {
+                ordered: *ordered,
+                calls: calls.iter().map(Into::into).collect(),
+            },
+            protocol::RequestKind::OneWayCall {
+                kind,
+                id,
+                function_id,
+                part_sizes,
+            } => Self::OneWayCall {
+                kind: (*kind).into(),
+                id: *id,
+                function_id: *function_id,
+                part_sizes,
+            },
             protocol::RequestKind::DeallocatePrivateService { id } => {
                 Self::DeallocatePrivateService { id: *id }
             }
+            protocol::RequestKind::DeallocatePromisedService { id } => {
+                Self::DeallocatePromisedService { id: *id }
+            }
+            protocol::RequestKind::DeallocatePrivateServices { ids, promise_ids } => {
+                Self::DeallocatePrivateServices { ids, promise_ids }
+            }
+            protocol::RequestKind::DuplicatePrivateService { kind, id } => {
+                Self::DuplicatePrivateService {
+                    kind: (*kind).into(),
+                    id: *id,
+                }
+            }
+            protocol::RequestKind::WatchRevocations => Self::WatchRevocations,
+            protocol::RequestKind::Subscribe { topic } => Self::Subscribe { topic },
+            protocol::RequestKind::Broadcast { message } => Self::Broadcast { message },
+            protocol::RequestKind::Authenticate { credentials } => Self::Authenticate {
+                credentials: (*credentials).into(),
+            },
+            protocol::RequestKind::RenewPrivateService { id } => {
+                Self::RenewPrivateService { id: *id }
+            }
+            protocol::RequestKind::RenewPromisedService { id } => {
+                Self::RenewPromisedService { id: *id }
+            }
+            protocol::RequestKind::RenewPrivateServices { ids, promise_ids } => {
+                Self::RenewPrivateServices { ids, promise_ids }
+            }
+            protocol::RequestKind::PrivateServiceLease => Self::PrivateServiceLease,
         }
     }
 }
@@ -61,6 +186,9 @@ impl<'a> From<&'a ArchivedRequestKind<'a>> for protocol::RequestKind<'a> {
     fn from(value: &'a ArchivedRequestKind) -> Self {
         match value {
             ArchivedRequestKind::ServiceId { name, checksum } => Self::ServiceId { name, checksum },
+            ArchivedRequestKind::ServiceIds { services } => Self::ServiceIds {
+                services: services.iter().map(Into::into).collect(),
+            },
             ArchivedRequestKind::ServiceCall {
                 kind,
                 id,
@@ -72,9 +200,67 @@ impl<'a> From<&'a ArchivedRequestKind<'a>> for protocol::RequestKind<'a> {
                 function_id: *function_id,
                 part_sizes,
             },
+            ArchivedRequestKind::PromisingCall {
+                kind,
+                id,
+                function_id,
+                part_sizes,
+                promise_id,
+                returns,
+            } => Self::PromisingCall {
+                kind: kind.into(),
+                id: *id,
+                function_id: *function_id,
+                part_sizes,
+                promise_id: *promise_id,
+                returns: returns.into(),
+            },
+            ArchivedRequestKind::BatchCall { ordered, calls } => Self::BatchCall {
+                ordered: *ordered,
+                calls: calls.iter().map(Into::into).collect(),
+            },
+            ArchivedRequestKind::OneWayCall {
+                kind,
+                id,
+                function_id,
+                part_sizes,
+            } => Self::OneWayCall {
+                kind: kind.into(),
+                id: *id,
+                function_id: *function_id,
+                part_sizes,
+            },
             ArchivedRequestKind::DeallocatePrivateService { id } => {
                 Self::DeallocatePrivateService { id: *id }
             }
+            ArchivedRequestKind::DeallocatePromisedService { id } => {
+                Self::DeallocatePromisedService { id: *id }
+            }
+            ArchivedRequestKind::DeallocatePrivateServices { ids, promise_ids } => {
+                Self::DeallocatePrivateServices { ids, promise_ids }
+            }
+            ArchivedRequestKind::DuplicatePrivateService { kind, id } => {
+                Self::DuplicatePrivateService {
+                    kind: kind.into(),
+                    id: *id,
+                }
+            }
+            ArchivedRequestKind::WatchRevocations => Self::WatchRevocations,
+            ArchivedRequestKind::Subscribe { topic } => Self::Subscribe { topic },
+            ArchivedRequestKind::Broadcast { message } => Self::Broadcast { message },
+            ArchivedRequestKind::Authenticate { credentials } => Self::Authenticate {
+                credentials: credentials.into(),
+            },
+            ArchivedRequestKind::RenewPrivateService { id } => {
+                Self::RenewPrivateService { id: *id }
+            }
+            ArchivedRequestKind::RenewPromisedService { id } => {
+                Self::RenewPromisedService { id: *id }
+            }
+            ArchivedRequestKind::RenewPrivateServices { ids, promise_ids } => {
+                Self::RenewPrivateServices { ids, promise_ids }
+            }
+            ArchivedRequestKind::PrivateServiceLease => Self::PrivateServiceLease,
         }
     }
 }
@@ -9,7 +9,8 @@ use crate::{
 };
 
 use super::error::{
-    InvalidPrivateServiceIdError, RemoteServiceIdRequestError, ServiceCallRequestError,
+    AuthenticationError, BroadcastRejectedError, InvalidPrivateServiceIdError,
+    RemoteServiceIdRequestError, ServiceCallRequestError, TopicNotFoundError,
 };
 
 impl Encode<RkyvFormat> for protocol::ServiceIdRequestResult {
@@ -35,6 +36,37 @@ impl Decode<RkyvFormat> for protocol::ServiceIdRequestResult {
     }
 }
 
+impl Encode<RkyvFormat> for protocol::ServiceIdsRequestResult {
+    type Error = <AllocSerializer<0> as Fallible>::Error;
+
+    fn encode(&self) -> Result<Vec<u8>, Self::Error> {
+        let results: Vec<Result<u32, RemoteServiceIdRequestError>> = self
+            .iter()
+            .map(|result| {
+                result
+                    .as_ref()
+                    .map(|service_found| service_found.0)
+                    .map_err(Into::into)
+            })
+            .collect();
+        rkyv::to_bytes::<_, 0>(&results).map(|buffer| buffer.to_vec())
+    }
+}
+
+impl Decode<RkyvFormat> for protocol::ServiceIdsRequestResult {
+    type Error = RkyvDeserializationError;
+
+    fn decode(buffer: &[u8]) -> Result<Self, Self::Error> {
+        Ok(
+            rkyv::from_bytes::<Vec<Result<u32, RemoteServiceIdRequestError>>>(buffer)
+                .map_err(|err| RkyvDeserializationError(err.to_string()))?
+                .into_iter()
+                .map(|result| result.map(protocol::ServiceFound).map_err(Into::into))
+                .collect(),
+        )
+    }
+}
+
 #[derive(Serialize, Archive)]
 #[archive(check_bytes)]
 pub enum ServiceCallRequestResult<'a> {
@@ -114,3 +146,85 @@ impl Decode<RkyvFormat> for protocol::PrivateServiceDeallocateRequestResult {
         )
     }
 }
+
+impl Encode<RkyvFormat> for protocol::PrivateServiceDuplicateRequestResult {
+    type Error = <AllocSerializer<0> as Fallible>::Error;
+
+    fn encode(&self) -> Result<Vec<u8>, Self::Error> {
+        let result = self.as_ref().copied().map_err(Into::into);
+        rkyv::to_bytes::<Result<u64, InvalidPrivateServiceIdError>, 0>(&result)
+            .map(|buffer| buffer.to_vec())
+    }
+}
+
+impl Decode<RkyvFormat> for protocol::PrivateServiceDuplicateRequestResult {
+    type Error = RkyvDeserializationError;
+
+    fn decode(buffer: &[u8]) -> Result<Self, Self::Error> {
+        Ok(
+            rkyv::from_bytes::<Result<u64, InvalidPrivateServiceIdError>>(buffer)
+                .map_err(|err| RkyvDeserializationError(err.to_string()))?
+                .map_err(Into::into),
+        )
+    }
+}
+
+impl Encode<RkyvFormat> for protocol::TopicSubscribeRequestResult {
+    type Error = <AllocSerializer<0> as Fallible>::Error;
+
+    fn encode(&self) -> Result<Vec<u8>, Self::Error> {
+        let result = self.as_ref().copied().map_err(Into::into);
+        rkyv::to_bytes::<Result<(), TopicNotFoundError>, 0>(&result).map(|buffer| buffer.to_vec())
+    }
+}
+
+impl Decode<RkyvFormat> for protocol::TopicSubscribeRequestResult {
+    type Error = RkyvDeserializationError;
+
+    fn decode(buffer: &[u8]) -> Result<Self, Self::Error> {
+        Ok(rkyv::from_bytes::<Result<(), TopicNotFoundError>>(buffer)
+            .map_err(|err| RkyvDeserializationError(err.to_string()))?
+            .map_err(Into::into))
+    }
+}
+
+impl Encode<RkyvFormat> for protocol::BroadcastRequestResult {
+    type Error = <AllocSerializer<0> as Fallible>::Error;
+
+    fn encode(&self) -> Result<Vec<u8>, Self::Error> {
+        let result = self.as_ref().copied().map_err(Into::into);
+        rkyv::to_bytes::<Result<(), BroadcastRejectedError>, 0>(&result)
+            .map(|buffer| buffer.to_vec())
+    }
+}
+
+impl Decode<RkyvFormat> for protocol::BroadcastRequestResult {
+    type Error = RkyvDeserializationError;
+
+    fn decode(buffer: &[u8]) -> Result<Self, Self::Error> {
+        Ok(
+            rkyv::from_bytes::<Result<(), BroadcastRejectedError>>(buffer)
+                .map_err(|err| RkyvDeserializationError(err.to_string()))?
+                .map_err(Into::into),
+        )
+    }
+}
+
+impl Encode<RkyvFormat> for protocol::AuthenticateRequestResult {
+    type Error = <AllocSerializer<0> as Fallible>::Error;
+
+    fn encode(&self) -> Result<Vec<u8>, Self::Error> {
+        let result = self.as_ref().copied().map_err(Into::into);
+        rkyv::to_bytes::<Result<(), AuthenticationError>, 0>(&result).map(|buffer| buffer.to_vec())
+    }
+}
+
+impl Decode<RkyvFormat> for protocol::AuthenticateRequestResult {
+    type Error = RkyvDeserializationError;
+
+    fn decode(buffer: &[u8]) -> Result<Self, Self::Error> {
+        Ok(rkyv::from_bytes::<Result<(), AuthenticationError>>(buffer)
+            .map_err(|err| RkyvDeserializationError(err.to_string()))?
+            .map_err(Into::into))
+    }
+}
@@ -0,0 +1,44 @@
+use rkyv::{ser::serializers::AllocSerializer, with::RefAsBox, Archive, Fallible, Serialize};
+
+use crate::{
+    format::{
+        rkyv::{RkyvDeserializationError, RkyvFormat},
+        Decode, Encode,
+    },
+    protocol,
+};
+
+#[derive(Serialize, Archive)]
+#[archive(check_bytes)]
+pub(crate) struct RevokedServices<'a> {
+    #[with(RefAsBox)]
+    ids: &'a [u64],
+    #[with(RefAsBox)]
+    promise_ids: &'a [u32],
+}
+
+impl Encode<RkyvFormat> for protocol::RevokedServices {
+    type Error = <AllocSerializer<0> as Fallible>::Error;
+
+    fn encode(&self) -> Result<Vec<u8>, Self::Error> {
+        let revoked = RevokedServices {
+            ids: &self.ids,
+            promise_ids: &self.promise_ids,
+        };
+        rkyv::to_bytes::<_, 0>(&revoked).map(|buffer| buffer.to_vec())
+    }
+}
+
+impl Decode<RkyvFormat> for protocol::RevokedServices {
+    type Error = RkyvDeserializationError;
+
+    fn decode(buffer: &[u8]) -> Result<Self, Self::Error> {
+        let revoked = rkyv::check_archived_root::<RevokedServices>(buffer)
+            .map_err(|err| RkyvDeserializationError(err.to_string()))?;
+
+        Ok(Self {
+            ids: revoked.ids.to_vec(),
+            promise_ids: revoked.promise_ids.to_vec(),
+        })
+    }
+}
@@ -7,6 +7,7 @@ use crate::protocol;
 pub enum ServiceKind {
     Public,
     Private,
+    Promised,
 }
 
 impl From<protocol::ServiceKind> for ServiceKind {
@@ -14,6 +15,7 @@ impl From<protocol::ServiceKind> for ServiceKind {
         match value {
             protocol::ServiceKind::Public => Self::Public,
             protocol::ServiceKind::Private => Self::Private,
+            protocol::ServiceKind::Promised => Self::Promised,
         }
     }
 }
@@ -23,6 +25,7 @@ impl From<&ArchivedServiceKind> for protocol::ServiceKind {
         match value {
             ArchivedServiceKind::Public => Self::Public,
             ArchivedServiceKind::Private => Self::Private,
+            ArchivedServiceKind::Promised => Self::Promised,
         }
     }
 }
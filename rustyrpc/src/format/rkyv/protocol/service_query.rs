@@ -0,0 +1,30 @@
+use rkyv::{with::RefAsBox, Archive, Serialize};
+
+use crate::protocol;
+
+#[derive(Serialize, Archive)]
+#[archive(check_bytes)]
+pub struct ServiceQuery<'a> {
+    #[with(RefAsBox)]
+    name: &'a str,
+    #[with(RefAsBox)]
+    checksum: &'a [u8],
+}
+
+impl<'a> From<&protocol::ServiceQuery<'a>> for ServiceQuery<'a> {
+    fn from(value: &protocol::ServiceQuery<'a>) -> Self {
+        Self {
+            name: value.name,
+            checksum: value.checksum,
+        }
+    }
+}
+
+impl<'a> From<&'a ArchivedServiceQuery<'_>> for protocol::ServiceQuery<'a> {
+    fn from(value: &'a ArchivedServiceQuery<'_>) -> Self {
+        Self {
+            name: &value.name,
+            checksum: &value.checksum,
+        }
+    }
+}
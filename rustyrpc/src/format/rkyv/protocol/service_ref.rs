@@ -1,115 +1,100 @@
-use core::num::TryFromIntError;
+#![allow(clippy::min_ident_chars)] // Single char idents are generated by `CheckBytes` derive.
 
-use crate::{
-    format::{
-        rkyv::{RkyvDeserializationError, RkyvFormat},
-        Decode, DecodeZeroCopy, DecodeZeroCopyFallible, Encode,
-    },
-    impl_decode_zero_copy, server,
-};
 use alloc::borrow::Cow;
 use rkyv::{
-    option::ArchivedOption,
-    ser::serializers::{
-        AlignedSerializer, AllocScratch, CompositeSerializerError, FallbackScratch, HeapScratch,
-        SharedSerializeMap,
-    },
-    with::RefAsBox,
-    AlignedVec, Archive, Fallible, Serialize,
+    out_field,
+    ser::{ScratchSpace, Serializer},
+    vec::{ArchivedVec, VecResolver},
+    Archive, Archived, Deserialize, Fallible, Serialize,
 };
-use thiserror::Error;
 
-#[derive(Serialize, Archive)]
-#[archive(check_bytes)]
-struct ServiceRef<'a> {
-    service_id: u32,
-    #[with(RefAsBox)]
-    service_checksum: &'a [u8],
-}
+use crate::{client::ConnectionOrigin, server, service::ServiceDescriptor};
 
-impl_decode_zero_copy!(ServiceRef<'_> as ArchivedServiceRef<'_>);
+/// Archived [`ServiceRef`][server::ServiceRef], so references can be fields of any type deriving [`Archive`].
+#[derive(rkyv::bytecheck::CheckBytes)]
+#[check_bytes(crate = "rkyv::bytecheck")]
+#[repr(C)]
+pub struct ArchivedServiceRef {
+    service_id: Archived<u64>,
+    service_checksum: ArchivedVec<u8>,
+    allocator_id: Archived<u64>,
+}
 
-impl<'a> TryFrom<&'a ArchivedServiceRef<'a>> for server::ServiceRef {
-    type Error = TryFromIntError;
+/// Resolver of [`ArchivedServiceRef`].
+pub struct ServiceRefResolver {
+    service_checksum: VecResolver,
+}
 
-    fn try_from(service_ref: &'a ArchivedServiceRef<'a>) -> Result<Self, Self::Error> {
-        Ok(Self {
-            service_id: service_ref.service_id.try_into()?,
-            service_checksum: Cow::Owned(service_ref.service_checksum.to_vec()),
-        })
+impl Archive for server::ServiceRef {
+    type Archived = ArchivedServiceRef;
+    type Resolver = ServiceRefResolver;
+
+    #[allow(clippy::undocumented_unsafe_blocks)]
+    unsafe fn resolve(&self, pos: usize, resolver: Self::Resolver, out: *mut Self::Archived) {
+        let (id_pos, id_out) = out_field!(out.service_id);
+        self.service_id
+            .resolve(pos.saturating_add(id_pos), (), id_out);
+
+        let (checksum_pos, checksum_out) = out_field!(out.service_checksum);
+        ArchivedVec::resolve_from_slice(
+            &self.service_checksum,
+            pos.saturating_add(checksum_pos),
+            resolver.service_checksum,
+            checksum_out,
+        );
+
+        let (allocator_id_pos, allocator_id_out) = out_field!(out.allocator_id);
+        self.allocator_id
+            .resolve(pos.saturating_add(allocator_id_pos), (), allocator_id_out);
     }
 }
 
-impl<'a> TryFrom<&'a server::ServiceRef> for ServiceRef<'a> {
-    type Error = TryFromIntError;
-
-    fn try_from(service_ref: &'a server::ServiceRef) -> Result<Self, Self::Error> {
-        Ok(Self {
-            service_id: service_ref.service_id.try_into()?,
-            service_checksum: &service_ref.service_checksum,
+impl<S: Serializer + ScratchSpace + ?Sized> Serialize<S> for server::ServiceRef {
+    fn serialize(&self, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        Ok(ServiceRefResolver {
+            service_checksum: ArchivedVec::serialize_from_slice(
+                &self.service_checksum,
+                serializer,
+            )?,
         })
     }
 }
 
-type EncodeError = CompositeSerializerError<
-    <AlignedSerializer<AlignedVec> as Fallible>::Error,
-    <FallbackScratch<HeapScratch<0>, AllocScratch> as Fallible>::Error,
-    <SharedSerializeMap as Fallible>::Error,
->;
-
-#[derive(Error, Debug)]
-pub enum ServiceRefEncodeError {
-    #[error("Service id is invalid")]
-    InvalidServiceId(#[from] TryFromIntError),
-    #[error(transparent)]
-    Encode(#[from] EncodeError),
-}
-
-#[derive(Error, Debug)]
-pub enum ServiceRefDecodeError {
-    #[error("Service id is invalid")]
-    InvalidServiceId(#[from] TryFromIntError),
-    #[error(transparent)]
-    Decode(#[from] RkyvDeserializationError),
-}
-
-impl Encode<RkyvFormat> for server::ServiceRef {
-    type Error = ServiceRefEncodeError;
-
-    fn encode(&self) -> Result<Vec<u8>, Self::Error> {
-        let service_ref: ServiceRef = self.try_into()?;
-        Ok(service_ref.encode()?)
+impl<D: Fallible + ?Sized> Deserialize<server::ServiceRef, D> for ArchivedServiceRef {
+    fn deserialize(&self, _deserializer: &mut D) -> Result<server::ServiceRef, D::Error> {
+        Ok(server::ServiceRef {
+            service_id: self.service_id,
+            service_checksum: Cow::Owned(self.service_checksum.to_vec()),
+            allocator_id: self.allocator_id,
+            origin: ConnectionOrigin::of_decoded(),
+        })
     }
 }
 
-impl Decode<RkyvFormat> for server::ServiceRef {
-    type Error = ServiceRefDecodeError;
+impl<S: ServiceDescriptor> Archive for server::TypedServiceRef<S> {
+    type Archived = ArchivedServiceRef;
+    type Resolver = ServiceRefResolver;
 
-    fn decode(buffer: &[u8]) -> Result<Self, Self::Error> {
-        Ok(<&ArchivedServiceRef>::decode_zero_copy(buffer)?.try_into()?)
+    #[allow(clippy::undocumented_unsafe_blocks)]
+    unsafe fn resolve(&self, pos: usize, resolver: Self::Resolver, out: *mut Self::Archived) {
+        self.as_untyped().resolve(pos, resolver, out);
     }
 }
 
-impl Encode<RkyvFormat> for Option<server::ServiceRef> {
-    type Error = ServiceRefEncodeError;
-
-    fn encode(&self) -> Result<Vec<u8>, Self::Error> {
-        let service_ref: Option<ServiceRef> = self
-            .as_ref()
-            .map_or(Ok(None), |service_ref| service_ref.try_into().map(Some))?;
-        Ok(rkyv::to_bytes::<_, 0>(&service_ref)?.to_vec())
+impl<S: ServiceDescriptor, Ser: Serializer + ScratchSpace + ?Sized> Serialize<Ser>
+    for server::TypedServiceRef<S>
+{
+    fn serialize(&self, serializer: &mut Ser) -> Result<Self::Resolver, Ser::Error> {
+        self.as_untyped().serialize(serializer)
     }
 }
 
-impl Decode<RkyvFormat> for Option<server::ServiceRef> {
-    type Error = ServiceRefDecodeError;
-
-    fn decode(buffer: &[u8]) -> Result<Self, Self::Error> {
-        let service_ref = rkyv::check_archived_root::<Option<ServiceRef>>(buffer)
-            .map_err(|err| RkyvDeserializationError(err.to_string()))?;
-        match service_ref {
-            ArchivedOption::Some(service_ref) => Ok(Some(service_ref.try_into()?)),
-            ArchivedOption::None => Ok(None),
-        }
+impl<S: ServiceDescriptor, D: Fallible + ?Sized> Deserialize<server::TypedServiceRef<S>, D>
+    for ArchivedServiceRef
+{
+    fn deserialize(&self, deserializer: &mut D) -> Result<server::TypedServiceRef<S>, D::Error> {
+        // Checksum is checked on conversion into client, because deserialization can't fail with custom error.
+        Deserialize::<server::ServiceRef, D>::deserialize(self, deserializer)
+            .map(server::TypedServiceRef::from_untyped_unchecked)
     }
 }
@@ -63,5 +63,9 @@ pub mod service;
 pub mod transport;
 mod utils;
 
-pub use client::Client;
+pub use client::{
+    Broadcasts, CallBatch, CallOptions, Client, ConnectionState, ConsistentHash, EndpointInfo,
+    LeastOutstanding, LoadBalancer, PendingReturns, PrivateServiceLostError, PromisingReturns,
+    RetryPolicy, RoundRobin, ServiceHandle, ServiceLease, SharedService, Subscription,
+};
 pub use server::Server;
@@ -1,9 +1,22 @@
+//! # Authentication handshake
+//! ```markdown
+//! RequestKind::Authenticate --> Server
+//! Client <-- AuthenticateRequestResult
+//! ```
+//! Sent on the first stream of connection if server requires authentication. Server closes connection if it fails.
+//!
 //! # Remote service id request
 //! ```markdown
 //! RequestKind::ServiceIdRequest --> Server
 //! Client <-- ServiceRequestResult
 //! ```
 //!
+//! # Batched remote service ids request
+//! ```markdown
+//! RequestKind::ServiceIds --> Server
+//! Client <-- ServiceIdsRequestResult
+//! ```
+//!
 //! # Remote call
 //! ```markdown
 //! RequestKind::ServiceCallRequest --> Server
@@ -11,17 +24,119 @@
 //! Client <-- ServiceCallRequestResult
 //! Client <-- Returns
 //! ```
+//!
+//! # Pipelined remote call
+//! ```markdown
+//! RequestKind::PromisingCall --> Server
+//! Args --> Server
+//! RequestKind::ServiceCall with ServiceKind::Promised --> Server
+//! Args --> Server
+//! Client <-- ServiceCallRequestResult of promising call
+//! Client <-- Returns of promising call
+//! Client <-- ServiceCallRequestResult of pipelined call
+//! Client <-- Returns of pipelined call
+//! ```
+//! Pipelined call is sent on its own stream without waiting for result of promising call and
+//! executed by server on private service returned by promising call once it finishes.
+//!
+//! # Batched remote calls
+//! ```markdown
+//! RequestKind::BatchCall --> Server
+//! Args of each call --> Server
+//! Client <-- ServiceCallRequestResult and Returns of each call in request order
+//! ```
+//!
+//! # One-way remote call
+//! ```markdown
+//! RequestKind::OneWayCall --> Server
+//! Args --> Server
+//! ```
+//!
+//! # Batched private services deallocation
+//! ```markdown
+//! RequestKind::DeallocatePrivateServices --> Server
+//! Client <-- PrivateServiceDeallocateRequestResult
+//! ```
+//!
+//! # Private service duplication
+//! ```markdown
+//! RequestKind::DuplicatePrivateService --> Server
+//! Client <-- PrivateServiceDuplicateRequestResult
+//! ```
+//!
+//! # Watching revocations of private services
+//! ```markdown
+//! RequestKind::WatchRevocations --> Server
+//! Client <-- RevokedServices each time server frees private services by itself
+//! ```
+//! Stream is kept open until connection is closed.
+//!
+//! # Private service lease
+//! ```markdown
+//! RequestKind::PrivateServiceLease --> Server
+//! Client <-- PrivateServiceLease
+//! ```
+//! Client renews leases only if server leases private services.
+//!
+//! # Private service lease renewal
+//! ```markdown
+//! RequestKind::RenewPrivateService --> Server
+//! Client <-- PrivateServiceRenewRequestResult
+//! ```
+//!
+//! # Batched private services lease renewal
+//! ```markdown
+//! RequestKind::RenewPrivateServices --> Server
+//! Client <-- PrivateServiceRenewRequestResult
+//! ```
+//!
+//! # Topic subscription
+//! ```markdown
+//! RequestKind::Subscribe --> Server
+//! Client <-- TopicSubscribeRequestResult
+//! Client <-- Event each time event is published to topic
+//! ```
+//! Stream is kept open until client closes it or server closes slow subscription.
+//!
+//! # Call of service hosted by client
+//! ```markdown
+//! Client <-- RequestKind::ServiceCall
+//! Client <-- Args
+//! ServiceCallRequestResult --> Server
+//! Returns --> Server
+//! ```
+//! Stream is opened by server. Only calls of private services are accepted, other requests fail.
+//!
+//! # Broadcast
+//! ```markdown
+//! Client <-- RequestKind::Broadcast
+//! BroadcastRequestResult --> Server
+//! ```
+//! Stream is opened by server for each connected client.
 
+use core::time::Duration;
 use std::io;
 
 use thiserror::Error;
 
 /// Response on service id request
 pub type ServiceIdRequestResult = Result<ServiceFound, RemoteServiceIdRequestError>;
+/// Response on batched service ids request, containing result for each requested service in request order
+pub type ServiceIdsRequestResult = Vec<ServiceIdRequestResult>;
 /// Response on service call request
 pub type ServiceCallRequestResult<'a> = Result<&'a [u32], ServiceCallRequestError>;
 /// Response on private service deallocation request
 pub type PrivateServiceDeallocateRequestResult = Result<(), InvalidPrivateServiceIdError>;
+/// Response on private service duplication request, containing id of new reference
+pub type PrivateServiceDuplicateRequestResult = Result<u64, InvalidPrivateServiceIdError>;
+/// Response on private service lease renewal request
+pub type PrivateServiceRenewRequestResult = Result<(), InvalidPrivateServiceIdError>;
+/// Response on topic subscription request, followed by published events if subscription succeeded
+pub type TopicSubscribeRequestResult = Result<(), TopicNotFoundError>;
+/// Response on authentication handshake
+pub type AuthenticateRequestResult = Result<(), AuthenticationError>;
+/// Response of client on broadcast sent by server
+pub type BroadcastRequestResult = Result<(), BroadcastRejectedError>;
 
 /// Requests that can be made.
 pub enum RequestKind<'a> {
@@ -32,12 +147,51 @@ pub enum RequestKind<'a> {
         /// Checksum of service
         checksum: &'a [u8],
     },
+    /// Request to retrieve several services at once
+    ServiceIds {
+        /// Requested services
+        services: Vec<ServiceQuery<'a>>,
+    },
     /// Request to call service's function
     ServiceCall {
         /// Kind of service
         kind: ServiceKind,
         /// Service id
-        id: u32,
+        id: u64,
+        /// Service's function id
+        function_id: u32,
+        /// Length of each part of multipart sent as function arguments
+        part_sizes: &'a [u32],
+    },
+    /// Request to call service's function and remember private service it returns as promise,
+    /// so it can be called with [`ServiceKind::Promised`] before result of this call is received
+    PromisingCall {
+        /// Kind of service
+        kind: ServiceKind,
+        /// Service id
+        id: u64,
+        /// Service's function id
+        function_id: u32,
+        /// Length of each part of multipart sent as function arguments
+        part_sizes: &'a [u32],
+        /// Id of promise chosen by client
+        promise_id: u32,
+        /// How function returns promised private service
+        returns: PromisedReturns,
+    },
+    /// Request to call several functions at once
+    BatchCall {
+        /// Whether calls must be executed one after another in request order instead of concurrently
+        ordered: bool,
+        /// Calls to execute
+        calls: Vec<BatchedCall<'a>>,
+    },
+    /// Request to call service's one-way function without waiting for its result
+    OneWayCall {
+        /// Kind of service
+        kind: ServiceKind,
+        /// Service id
+        id: u64,
         /// Service's function id
         function_id: u32,
         /// Length of each part of multipart sent as function arguments
@@ -46,8 +200,119 @@ pub enum RequestKind<'a> {
     /// Request to deallocate private service
     DeallocatePrivateService {
         /// Private service id
+        id: u64,
+    },
+    /// Request to deallocate private service returned by promising call
+    DeallocatePromisedService {
+        /// Promise id
+        id: u32,
+    },
+    /// Request to deallocate several private services at once.
+    /// Server deallocates all valid services and responds with error if any of them is already deallocated
+    DeallocatePrivateServices {
+        /// Private service ids
+        ids: &'a [u64],
+        /// Promise ids of services returned by promising calls
+        promise_ids: &'a [u32],
+    },
+    /// Request to allocate another reference to private service.
+    /// Service is freed once all its references are deallocated
+    DuplicatePrivateService {
+        /// Kind of service, either private or promised
+        kind: ServiceKind,
+        /// Private service id or promise id
+        id: u64,
+    },
+    /// Request to notify client about private services freed by server itself (e.g. revoked by service or expired)
+    WatchRevocations,
+    /// Request to receive events published to topic
+    Subscribe {
+        /// Name of topic
+        topic: &'a str,
+    },
+    /// Message sent by server to every connected client
+    Broadcast {
+        /// Encoded message
+        message: &'a [u8],
+    },
+    /// Request to authenticate connection, must be the first request on connection if server requires authentication
+    Authenticate {
+        /// Credentials presented by client
+        credentials: Credentials<'a>,
+    },
+    /// Request to renew lease of private service, so server doesn't free it as abandoned
+    RenewPrivateService {
+        /// Private service id
+        id: u64,
+    },
+    /// Request to renew lease of private service returned by promising call
+    RenewPromisedService {
+        /// Promise id
         id: u32,
     },
+    /// Request to renew leases of several private services at once
+    RenewPrivateServices {
+        /// Private service ids
+        ids: &'a [u64],
+        /// Ids of promises resolved with private services
+        promise_ids: &'a [u32],
+    },
+    /// Request to find out how long server keeps private services without renewal
+    PrivateServiceLease,
+}
+
+/// Lease of private services allocated on connection, sent in response to [`private service lease request`][RequestKind::PrivateServiceLease].
+#[derive(Debug, Clone, Copy)]
+pub struct PrivateServiceLease(
+    /// Time private service is kept without renewal or `None` if server doesn't lease private services
+    pub Option<Duration>,
+);
+
+/// Notification about private services freed by server itself, sent on [`revocations watching`][RequestKind::WatchRevocations] stream.
+#[derive(Debug, Clone, Default)]
+pub struct RevokedServices {
+    /// Ids of revoked private services
+    pub ids: Vec<u64>,
+    /// Ids of promises resolved with revoked private services
+    pub promise_ids: Vec<u32>,
+}
+
+/// Credentials presented by client in [`authentication handshake`][RequestKind::Authenticate].
+#[derive(Debug, Clone, Copy)]
+pub enum Credentials<'a> {
+    /// Bearer token
+    Token(&'a str),
+    /// Username and password
+    Password {
+        /// Username
+        username: &'a str,
+        /// Password
+        password: &'a str,
+    },
+    /// Identity established by transport, e.g. by client certificate
+    Transport,
+}
+
+/// Service requested in [`batched service ids request`][RequestKind::ServiceIds].
+#[derive(Debug, Clone, Copy)]
+pub struct ServiceQuery<'a> {
+    /// Name of service
+    pub name: &'a str,
+    /// Checksum of service
+    pub checksum: &'a [u8],
+}
+
+/// Single call of [`batched calls request`][RequestKind::BatchCall].
+#[derive(Debug, Clone, Copy)]
+pub struct BatchedCall<'a> {
+    /// Kind of service
+    pub kind: ServiceKind,
+    /// Service id
+    pub id: u64,
+    /// Service's function id
+    pub function_id: u32,
+    /// Length of each part of multipart sent as function arguments
+    pub part_sizes: &'a [u32],
 }
 
 /// Kind of service.
@@ -55,8 +320,19 @@ pub enum RequestKind<'a> {
 pub enum ServiceKind {
     /// Represents service that can be accessed with [`service id request`][RequestKind::ServiceId]
     Public,
-    /// Represents service that can be accessed only by `ServiceRef`
+    /// Represents service that can be accessed only by `ServiceRef`. Its id includes generation, so stale ids of deallocated services are rejected
     Private,
+    /// Represents private service returned by [`promising call`][RequestKind::PromisingCall] that may be not finished yet
+    Promised,
+}
+
+/// How function called by [`promising call`][RequestKind::PromisingCall] returns promised private service.
+#[derive(Debug, Clone, Copy)]
+pub enum PromisedReturns {
+    /// Function returns reference to private service
+    Service,
+    /// Function returns optional reference to private service, promise is broken if it returns none
+    OptionalService,
 }
 
 /// Successful result of finding a service, containing its service id.
@@ -102,14 +378,30 @@ pub enum ServiceCallRequestError {
     /// Indicates a failure caused by internal server errors.
     #[error("Unexpected error caused by server")]
     ServerInternal,
+    /// Indicates that the server is overloaded and rejected the call without executing it.
+    #[error("Server is overloaded")]
+    Overloaded,
+    /// Indicates that the caller isn't allowed to call the function by authorization policy of server.
+    #[error("Permission denied")]
+    PermissionDenied,
+    /// Indicates that the caller exceeded rate limit of server and may retry the call after specified time.
+    #[error("Rate limit exceeded, retry after {retry_after:?}")]
+    ResourceExhausted {
+        /// Time after which call may be accepted again
+        retry_after: Duration,
+    },
 }
 
 impl From<ServiceCallRequestError> for io::Error {
     fn from(error: ServiceCallRequestError) -> Self {
-        let kind = if let ServiceCallRequestError::ServerInternal = error {
-            io::ErrorKind::Other
-        } else {
-            io::ErrorKind::InvalidInput
+        let kind = match error {
+            ServiceCallRequestError::ServerInternal
+            | ServiceCallRequestError::Overloaded
+            | ServiceCallRequestError::ResourceExhausted { .. } => io::ErrorKind::Other,
+            ServiceCallRequestError::InvalidServiceId
+            | ServiceCallRequestError::InvalidFunctionId
+            | ServiceCallRequestError::ArgsDecode => io::ErrorKind::InvalidInput,
+            ServiceCallRequestError::PermissionDenied => io::ErrorKind::PermissionDenied,
         };
 
         io::Error::new(kind, error)
@@ -126,3 +418,36 @@ impl From<InvalidPrivateServiceIdError> for io::Error {
         io::Error::new(io::ErrorKind::InvalidInput, error)
     }
 }
+
+/// Error that may occur while trying to publish or subscribe to topic that isn't declared by server.
+#[derive(Error, Debug)]
+#[error("Topic not found")]
+pub struct TopicNotFoundError;
+
+impl From<TopicNotFoundError> for io::Error {
+    fn from(error: TopicNotFoundError) -> Self {
+        io::Error::new(io::ErrorKind::NotFound, error)
+    }
+}
+
+/// Error that may occur if client doesn't [receive broadcasts][crate::Client::broadcasts].
+#[derive(Error, Debug)]
+#[error("Broadcast is rejected by client")]
+pub struct BroadcastRejectedError;
+
+impl From<BroadcastRejectedError> for io::Error {
+    fn from(error: BroadcastRejectedError) -> Self {
+        io::Error::new(io::ErrorKind::Unsupported, error)
+    }
+}
+
+/// Error that occurs if server rejects credentials presented by client.
+#[derive(Error, Debug)]
+#[error("Authentication failed")]
+pub struct AuthenticationError;
+
+impl From<AuthenticationError> for io::Error {
+    fn from(error: AuthenticationError) -> Self {
+        io::Error::new(io::ErrorKind::PermissionDenied, error)
+    }
+}
@@ -1,31 +1,63 @@
+mod authentication;
+mod authorization;
 mod builder;
 mod call_handler;
 mod call_stream;
 mod client_connection;
+mod client_services;
+mod concurrency;
+mod connection_hooks;
+mod load_shedding;
 mod private_service;
+mod promise_table;
+mod rate_limit;
 mod task_pool;
+mod topics;
 
-use self::{client_connection::ClientConnection, task_pool::TaskPool};
+pub(crate) use self::call_stream::{CallHandler, CallStream};
+use self::{
+    authorization::Authorization, client_connection::ClientConnection,
+    concurrency::ConcurrencyLimiter, load_shedding::LoadShedder, rate_limit::RateLimiter,
+    task_pool::TaskPool,
+};
 use crate::{
     format::{
-        DecodeZeroCopy, DecodeZeroCopyFallible, Encode, EncodingFormat, ZeroCopyEncodingFormat,
+        Decode, DecodeZeroCopy, DecodeZeroCopyFallible, Encode, EncodingFormat,
+        ZeroCopyEncodingFormat,
     },
     protocol::{
-        PrivateServiceDeallocateRequestResult, RequestKind, ServiceCallRequestResult,
-        ServiceIdRequestResult,
+        AuthenticateRequestResult, BroadcastRequestResult, PrivateServiceDeallocateRequestResult,
+        PrivateServiceDuplicateRequestResult, PrivateServiceLease, RequestKind, RevokedServices,
+        ServiceCallRequestResult, ServiceIdRequestResult, ServiceIdsRequestResult,
+        TopicSubscribeRequestResult,
     },
     server::call_handler::ServerCallHandler,
     service::Service,
-    transport,
+    transport::{self, Stream as _, StreamExt, StreamOpener as _},
 };
 use alloc::sync::Arc;
-use core::marker::PhantomData;
-use futures::lock::Mutex;
+use core::{
+    marker::PhantomData,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+use futures::{future, lock::Mutex};
 use log::trace;
-use std::{collections::HashMap, io};
+use std::{collections::HashMap, io, sync::PoisonError};
 
+pub use authentication::{Authenticator, Identity};
+pub use authorization::Policy;
 pub use builder::ServerBuilder;
-pub use private_service::{PrivateServiceAllocator, ServiceRef};
+pub use client_services::ClientServices;
+pub use concurrency::{ConcurrencyLimits, ConcurrencyOverflow};
+pub use connection_hooks::{ConnectionHooks, ConnectionInfo, ConnectionRejectedError};
+pub use load_shedding::LoadShedding;
+pub use private_service::{
+    Deallocated, PrivateServiceAllocator, ServiceRef, ServiceRefLock, TypedServiceRef,
+};
+pub use rate_limit::{RateLimit, RateLimitKey};
+pub(crate) use topics::TopicSubscription;
+pub use topics::{SlowSubscriberPolicy, Topics};
 
 /// Server for handling incoming connections and managing service calls.
 pub struct Server<Listener: transport::ConnectionListener, Format: EncodingFormat> {
@@ -33,9 +65,28 @@ pub struct Server<Listener: transport::ConnectionListener, Format: EncodingForma
     tasks: TaskPool,
     service_map: HashMap<Box<str>, (Box<[u8]>, u32)>,
     services: Box<[Box<dyn Service<Format>>]>,
+    private_service_lease: Option<Duration>,
+    max_private_services: usize,
+    topics: Option<Arc<Topics<Format>>>,
+    connections: std::sync::Mutex<HashMap<u64, Arc<StreamOpener<Listener>>>>,
+    next_connection_id: AtomicU64,
+    broadcast_timeout: Duration,
+    connection_hooks: Option<Arc<dyn ConnectionHooks>>,
+    authenticator: Option<Arc<dyn Authenticator>>,
+    handshake_timeout: Duration,
+    authorization: Authorization,
+    rate_limiter: RateLimiter,
+    load_shedder: Option<Arc<LoadShedder>>,
+    concurrency_limits: ConcurrencyLimits,
+    concurrency_limiter: ConcurrencyLimiter,
     _format: PhantomData<Format>,
 }
 
+const HANDSHAKE_REJECTION_GRACE_PERIOD: Duration = Duration::from_secs(1);
+
+type StreamOpener<Listener> =
+    <<Listener as transport::ConnectionListener>::Connection as transport::ServerConnection>::StreamOpener;
+
 // Server now supports only zero-copy formats. Non zero-copy formats coming soon.
 impl<Listener: transport::ConnectionListener + 'static, Format: ZeroCopyEncodingFormat>
     Server<Listener, Format>
@@ -43,12 +94,34 @@ where
     for<'a, 'b> RequestKind<'a>:
         DecodeZeroCopy<'a, Format, <RequestKind<'b> as DecodeZeroCopyFallible<Format>>::Error>,
     ServiceIdRequestResult: Encode<Format>,
+    ServiceIdsRequestResult: Encode<Format>,
     for<'a> ServiceCallRequestResult<'a>: Encode<Format>,
     PrivateServiceDeallocateRequestResult: Encode<Format>,
+    PrivateServiceDuplicateRequestResult: Encode<Format>,
+    RevokedServices: Encode<Format>,
+    TopicSubscribeRequestResult: Encode<Format>,
+    BroadcastRequestResult: Encode<Format>,
+    AuthenticateRequestResult: Encode<Format>,
+    PrivateServiceLease: Encode<Format>,
+    // Used to call services hosted by client.
+    for<'a> RequestKind<'a>: Encode<Format>,
+    for<'a> ServiceCallRequestResult<'a>: DecodeZeroCopy<
+        'a,
+        Format,
+        <ServiceCallRequestResult<'a> as DecodeZeroCopyFallible<Format>>::Error,
+    >,
+    // Used to find private services returned by promising calls.
+    ServiceRef: Decode<Format>,
+    Option<ServiceRef>: Decode<Format>,
 {
     /// Starts listening for incoming connections and handles them.
     #[allow(clippy::unwrap_used, clippy::missing_panics_doc)]
     pub async fn listen(self: Arc<Self>) -> ! {
+        if let Some(load_shedder) = &self.load_shedder {
+            self.tasks
+                .spawn_task(Arc::clone(load_shedder).measure_latency());
+        }
+
         loop {
             let connection = self.accept_connection().await.unwrap();
             self.tasks
@@ -67,22 +140,189 @@ where
             .map(Into::into)
     }
 
-    #[allow(clippy::unwrap_used)]
+    /// Sends message to every connected client. Returns result of delivery to each client by id of its connection.
+    /// Delivery fails if client doesn't [receive broadcasts][crate::Client::broadcasts] or doesn't acknowledge message in time.
+    ///
+    /// # Errors
+    /// Returns an error if message can't be encoded.
+    pub async fn broadcast<Message: Encode<Format>>(
+        &self,
+        message: &Message,
+    ) -> io::Result<HashMap<u64, io::Result<()>>>
+    where
+        BroadcastRequestResult: Decode<Format>,
+    {
+        let message = message
+            .encode()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let connections: Vec<_> = self
+            .connections
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(connection_id, opener)| (*connection_id, Arc::clone(opener)))
+            .collect();
+
+        let deliveries = connections.into_iter().map(|(connection_id, opener)| {
+            let message = &message;
+            async move {
+                let delivery =
+                    tokio::time::timeout(self.broadcast_timeout, Self::deliver(&opener, message))
+                        .await
+                        .unwrap_or_else(|_| Err(io::ErrorKind::TimedOut.into()));
+                (connection_id, delivery)
+            }
+        });
+
+        Ok(future::join_all(deliveries).await.into_iter().collect())
+    }
+
+    async fn deliver(opener: &StreamOpener<Listener>, message: &[u8]) -> io::Result<()>
+    where
+        BroadcastRequestResult: Decode<Format>,
+    {
+        let mut stream = opener.open_stream().await?;
+        stream
+            .send_encodable::<_, Format>(&RequestKind::Broadcast { message })
+            .await?;
+        stream.flush().await?;
+        stream
+            .receive_decodable::<BroadcastRequestResult, Format>()
+            .await??;
+
+        Ok(())
+    }
+
+    /// Authenticates client. Fails with [`TimedOut`][io::ErrorKind::TimedOut] if client doesn't authenticate in time.
+    async fn authenticate(
+        &self,
+        connection: &mut ClientConnection<Listener::Connection, Format>,
+        authenticator: &dyn Authenticator,
+        connection_info: &ConnectionInfo,
+    ) -> io::Result<Identity> {
+        let handshake = async {
+            connection
+                .accept_call_stream()
+                .await?
+                .handle_handshake(authenticator, connection_info)
+                .await
+        };
+
+        #[allow(clippy::map_err_ignore)]
+        tokio::time::timeout(self.handshake_timeout, handshake)
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "Handshake timed out"))?
+    }
+
+    async fn close_connection(
+        connection: ClientConnection<Listener::Connection, Format>,
+        connection_id: u64,
+    ) {
+        if let Err(err) = connection.close().await {
+            trace!("Failed to close connection {connection_id}: {err}");
+        }
+    }
+
     async fn handle_connection(
         self: Arc<Self>,
         mut connection: ClientConnection<Listener::Connection, Format>,
     ) {
-        trace!("New connection accepted");
+        let mut connection_info = ConnectionInfo::new(
+            self.next_connection_id.fetch_add(1, Ordering::Relaxed),
+            connection.peer_identity(),
+        );
+        let connection_id = connection_info.id();
+        trace!("New connection accepted. Connection id: {connection_id}");
+
+        if let Some(hooks) = &self.connection_hooks {
+            if let Err(err) = hooks.on_accept(&connection_info).await {
+                trace!("Connection {connection_id} is rejected: {err}");
+                Self::close_connection(connection, connection_id).await;
+                return;
+            }
+        }
 
-        let call_handler = ServerCallHandler::new_for_connection(Arc::clone(&self));
+        if let Some(authenticator) = &self.authenticator {
+            match self
+                .authenticate(&mut connection, authenticator.as_ref(), &connection_info)
+                .await
+            {
+                Ok(identity) => {
+                    trace!(
+                        "Connection {connection_id} is authenticated as {}",
+                        identity.name()
+                    );
+                    connection_info = connection_info.with_identity(identity);
+                    if let Some(hooks) = &self.connection_hooks {
+                        hooks.on_authenticated(&connection_info).await;
+                    }
+                }
+                Err(err) => {
+                    trace!("Connection {connection_id} failed authentication: {err}");
+                    // Client closes connection itself once rejection is received, so rejection isn't discarded by early close.
+                    // Client that didn't authenticate in time isn't waited for.
+                    if err.kind() == io::ErrorKind::TimedOut
+                        || tokio::time::timeout(
+                            HANDSHAKE_REJECTION_GRACE_PERIOD,
+                            connection.closed(),
+                        )
+                        .await
+                        .is_err()
+                    {
+                        Self::close_connection(connection, connection_id).await;
+                    }
+                    return;
+                }
+            }
+        }
 
-        loop {
-            let call_stream = connection.accept_call_stream().await.unwrap();
+        self.connections
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(connection_id, Arc::new(connection.stream_opener()));
+        let client_services = ClientServices::new(connection.stream_opener());
+        let call_handler = ServerCallHandler::new_for_connection(
+            Arc::clone(&self),
+            client_services,
+            connection_info.clone(),
+        );
+        if let Some(lease) = self.private_service_lease {
+            self.tasks
+                .spawn_task(call_handler.expire_private_services(lease));
+        }
+
+        let stream_slots = self.concurrency_limits.connection_stream_slots();
+        let close_reason = loop {
+            // Streams of any request take slot, so client can't spawn unlimited tasks with requests other than calls.
+            let stream_slot = match &stream_slots {
+                Some(slots) => Arc::clone(slots).acquire_owned().await.ok(),
+                None => None,
+            };
+            let call_stream = match connection.accept_call_stream().await {
+                Ok(call_stream) => call_stream,
+                Err(err) => break err,
+            };
             let call_handler = call_handler.clone();
 
             self.tasks.spawn_task(async move {
-                call_stream.handle_call(&call_handler).await.unwrap();
+                if let Err(err) = call_stream.handle_call(&call_handler).await {
+                    trace!("Call stream is closed: {err}");
+                }
+                drop(stream_slot);
             });
+        };
+        trace!("Connection {connection_id} is closed: {close_reason}");
+
+        self.connections
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&connection_id);
+        self.rate_limiter.forget_connection(connection_id);
+        if let Some(hooks) = &self.connection_hooks {
+            let private_services = call_handler.allocated_private_services().await;
+            hooks
+                .on_close(&connection_info, &close_reason, &private_services)
+                .await;
         }
     }
 }
@@ -0,0 +1,54 @@
+use alloc::sync::Arc;
+use async_trait::async_trait;
+use std::collections::HashSet;
+
+use super::ConnectionInfo;
+use crate::protocol::{AuthenticationError, Credentials};
+
+/// Identity of authenticated client, attached to its connection.
+#[derive(Debug, Clone)]
+pub struct Identity {
+    name: Arc<str>,
+    roles: Arc<HashSet<Box<str>>>,
+}
+
+impl Identity {
+    /// Creates identity without roles.
+    #[must_use]
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.into(),
+            roles: Arc::default(),
+        }
+    }
+
+    /// Adds role to identity.
+    #[must_use]
+    pub fn with_role(mut self, role: &str) -> Self {
+        Arc::make_mut(&mut self.roles).insert(role.into());
+        self
+    }
+
+    /// Returns name of identity, e.g. username.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns whether identity has role.
+    #[must_use]
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.contains(role)
+    }
+}
+
+/// Verifies credentials presented by clients in authentication handshake, see [`ServerBuilder::with_authenticator`][super::ServerBuilder::with_authenticator].
+#[async_trait]
+pub trait Authenticator: Send + Sync + 'static {
+    /// Returns identity of client or an error if credentials are rejected, so connection is closed.
+    async fn authenticate(
+        &self,
+        connection: &ConnectionInfo,
+        credentials: Credentials<'_>,
+    ) -> Result<Identity, AuthenticationError>;
+}
@@ -0,0 +1,82 @@
+use alloc::sync::Arc;
+use std::collections::HashMap;
+
+use super::Identity;
+
+type Predicate = dyn Fn(Option<&Identity>) -> bool + Send + Sync;
+
+/// Rule deciding whether client may call public service or its function, see [`ServerBuilder::with_service_policy`][super::ServerBuilder::with_service_policy].
+#[derive(Clone)]
+pub struct Policy(Rule);
+
+#[derive(Clone)]
+enum Rule {
+    Roles(Arc<[Box<str>]>),
+    Predicate(Arc<Predicate>),
+}
+
+impl Policy {
+    /// Allows authenticated clients having any of roles.
+    #[must_use]
+    pub fn roles(roles: &[&str]) -> Self {
+        Self(Rule::Roles(
+            roles.iter().map(|role| (*role).into()).collect(),
+        ))
+    }
+
+    /// Allows clients predicate returns `true` for. Identity is `None` for clients that aren't authenticated.
+    #[must_use]
+    pub fn predicate<F>(predicate: F) -> Self
+    where
+        F: Fn(Option<&Identity>) -> bool + Send + Sync + 'static,
+    {
+        Self(Rule::Predicate(Arc::new(predicate)))
+    }
+
+    fn allows(&self, identity: Option<&Identity>) -> bool {
+        match &self.0 {
+            Rule::Roles(roles) => {
+                identity.is_some_and(|client| roles.iter().any(|role| client.has_role(role)))
+            }
+            Rule::Predicate(predicate) => predicate(identity),
+        }
+    }
+}
+
+/// Policies of public services and their functions by service id.
+#[derive(Default)]
+pub(super) struct Authorization {
+    services: HashMap<u32, Policy>,
+    functions: HashMap<(u32, u32), Policy>,
+}
+
+impl Authorization {
+    pub(super) fn set_service_policy(&mut self, service_id: u32, policy: Policy) {
+        self.services.insert(service_id, policy);
+    }
+
+    pub(super) fn set_function_policy(
+        &mut self,
+        service_id: u32,
+        function_id: u32,
+        policy: Policy,
+    ) {
+        self.functions.insert((service_id, function_id), policy);
+    }
+
+    /// Returns whether client may call function of public service. Call must be allowed by both service and function policies.
+    pub(super) fn allows(
+        &self,
+        service_id: u32,
+        function_id: u32,
+        identity: Option<&Identity>,
+    ) -> bool {
+        let service_policy = self.services.get(&service_id);
+        let function_policy = self.functions.get(&(service_id, function_id));
+
+        service_policy
+            .into_iter()
+            .chain(function_policy)
+            .all(|policy| policy.allows(identity))
+    }
+}
@@ -1,7 +1,6 @@
-use core::marker::PhantomData;
-use std::collections::HashMap;
-
-use derive_where::derive_where;
+use alloc::sync::Arc;
+use core::{marker::PhantomData, sync::atomic::AtomicU64, time::Duration};
+use std::{collections::HashMap, io};
 
 use crate::{
     format::EncodingFormat,
@@ -9,16 +8,58 @@ use crate::{
     transport,
 };
 
-use super::{task_pool::TaskPool, Server};
+use super::{
+    authorization::Authorization, concurrency::ConcurrencyLimiter, load_shedding::LoadShedder,
+    rate_limit::RateLimiter, task_pool::TaskPool, Authenticator, ConcurrencyLimits,
+    ConnectionHooks, LoadShedding, Policy, RateLimit, RateLimitKey, Server, Topics,
+};
+
+const DEFAULT_BROADCAST_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
 
 /// Builder for [`Server`][Server]
-#[derive_where(Default)]
 pub struct ServerBuilder<Listener: transport::ConnectionListener, Format: EncodingFormat> {
     service_map: HashMap<Box<str>, (Box<[u8]>, u32)>,
     services: Vec<Box<dyn Service<Format>>>,
+    private_service_lease: Option<Duration>,
+    max_private_services: usize,
+    topics: Option<Arc<Topics<Format>>>,
+    broadcast_timeout: Duration,
+    connection_hooks: Option<Arc<dyn ConnectionHooks>>,
+    authenticator: Option<Arc<dyn Authenticator>>,
+    handshake_timeout: Duration,
+    service_policies: Vec<(Box<str>, Policy)>,
+    function_policies: Vec<(Box<str>, u32, Policy)>,
+    rate_limiter: RateLimiter,
+    load_shedding: Option<LoadShedding>,
+    concurrency_limits: ConcurrencyLimits,
     _phantom: PhantomData<(Listener, Format)>,
 }
 
+impl<Listener: transport::ConnectionListener, Format: EncodingFormat> Default
+    for ServerBuilder<Listener, Format>
+{
+    fn default() -> Self {
+        Self {
+            service_map: HashMap::default(),
+            services: Vec::default(),
+            private_service_lease: None,
+            max_private_services: usize::MAX,
+            topics: None,
+            broadcast_timeout: DEFAULT_BROADCAST_TIMEOUT,
+            connection_hooks: None,
+            authenticator: None,
+            handshake_timeout: DEFAULT_HANDSHAKE_TIMEOUT,
+            service_policies: Vec::new(),
+            function_policies: Vec::new(),
+            rate_limiter: RateLimiter::default(),
+            load_shedding: None,
+            concurrency_limits: ConcurrencyLimits::default(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
 impl<Listener: transport::ConnectionListener, Format: EncodingFormat>
     ServerBuilder<Listener, Format>
 {
@@ -66,14 +107,153 @@ impl<Listener: transport::ConnectionListener, Format: EncodingFormat>
         self
     }
 
+    /// Sets lease of private services. Services are freed if client doesn't renew lease in time.
+    /// Lease is advertised to clients, so they [renew leases][crate::Client::lease_private_service] accordingly.
+    /// By default private services are freed only on client's request or connection close.
+    #[must_use]
+    pub const fn with_private_service_lease(mut self, lease: Duration) -> Self {
+        self.private_service_lease = Some(lease);
+        self
+    }
+
+    /// Sets limit of live private services per connection. Allocations above limit fail.
+    #[must_use]
+    pub const fn with_max_private_services(mut self, max_private_services: usize) -> Self {
+        self.max_private_services = max_private_services;
+        self
+    }
+
+    /// Sets topics clients can subscribe to. Services publish events to the same topics.
+    #[must_use]
+    pub fn with_topics(mut self, topics: Arc<Topics<Format>>) -> Self {
+        self.topics = Some(topics);
+        self
+    }
+
+    /// Sets time each client has to acknowledge [broadcast][Server::broadcast] message.
+    #[must_use]
+    pub const fn with_broadcast_timeout(mut self, timeout: Duration) -> Self {
+        self.broadcast_timeout = timeout;
+        self
+    }
+
+    /// Sets callbacks invoked when connection is accepted, authenticated and closed.
+    #[must_use]
+    pub fn with_connection_hooks<Hooks: ConnectionHooks>(mut self, hooks: Hooks) -> Self {
+        self.connection_hooks = Some(Arc::new(hooks));
+        self
+    }
+
+    /// Requires clients to authenticate right after connection is accepted. Connections that fail authentication are closed.
+    /// Identity returned by authenticator is visible to every call through [`PrivateServiceAllocator::connection`][super::PrivateServiceAllocator::connection].
+    #[must_use]
+    pub fn with_authenticator<A: Authenticator>(mut self, authenticator: A) -> Self {
+        self.authenticator = Some(Arc::new(authenticator));
+        self
+    }
+
+    /// Sets time client has to open handshake stream and authenticate. Connections that don't authenticate in time are closed.
+    #[must_use]
+    pub const fn with_handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.handshake_timeout = timeout;
+        self
+    }
+
+    /// Restricts calls of public service with policy. Calls denied by policy fail with [`PermissionDenied`][crate::protocol::ServiceCallRequestError::PermissionDenied].
+    #[must_use]
+    pub fn with_service_policy(mut self, service_name: &str, policy: Policy) -> Self {
+        self.service_policies.push((service_name.into(), policy));
+        self
+    }
+
+    /// Restricts calls of public service's function with policy. Call must be allowed by [service policy][Self::with_service_policy] too.
+    #[must_use]
+    pub fn with_function_policy(
+        mut self,
+        service_name: &str,
+        function_id: u32,
+        policy: Policy,
+    ) -> Self {
+        self.function_policies
+            .push((service_name.into(), function_id, policy));
+        self
+    }
+
+    /// Limits rate of service calls sharing key. Calls above limit are rejected before dispatch with
+    /// [`ResourceExhausted`][crate::protocol::ServiceCallRequestError::ResourceExhausted] telling when to retry.
+    /// Call must fit every limit added.
+    #[must_use]
+    pub fn with_rate_limit(mut self, key: RateLimitKey, limit: RateLimit) -> Self {
+        self.rate_limiter.add_limit(key, limit);
+        self
+    }
+
+    /// Enables shedding of calls while server is overloaded. Shed calls are rejected before dispatch with
+    /// [`Overloaded`][crate::protocol::ServiceCallRequestError::Overloaded], which clients retry, possibly against another endpoint.
+    #[must_use]
+    pub const fn with_load_shedding(mut self, load_shedding: LoadShedding) -> Self {
+        self.load_shedding = Some(load_shedding);
+        self
+    }
+
+    /// Limits calls executed at once per connection and by server. Calls above limits are queued or refused
+    /// with [`Overloaded`][crate::protocol::ServiceCallRequestError::Overloaded].
+    /// Listener is configured to limit concurrent streams of each connection accordingly.
+    #[must_use]
+    pub const fn with_concurrency_limits(mut self, limits: ConcurrencyLimits) -> Self {
+        self.concurrency_limits = limits;
+        self
+    }
+
     /// Builds server from builder.
-    pub fn build(self, listener: Listener) -> Server<Listener, Format> {
-        Server {
+    ///
+    /// # Errors
+    /// Returns an error if listener fails to limit concurrent streams of connections.
+    ///
+    /// # Panics
+    /// Panics if policy is set for service that isn't added to server.
+    pub fn build(self, mut listener: Listener) -> io::Result<Server<Listener, Format>> {
+        let service_id = |name: &str| {
+            #[allow(clippy::expect_used)]
+            self.service_map
+                .get(name)
+                .map(|(_, service_id)| *service_id)
+                .expect("Policy is set for service that isn't added to server")
+        };
+        let mut authorization = Authorization::default();
+        for (name, policy) in self.service_policies {
+            authorization.set_service_policy(service_id(&name), policy);
+        }
+        for (name, function_id, policy) in self.function_policies {
+            authorization.set_function_policy(service_id(&name), function_id, policy);
+        }
+
+        if let Some(max_streams) = self.concurrency_limits.max_streams_per_connection() {
+            listener.set_max_concurrent_streams(max_streams)?;
+        }
+
+        Ok(Server {
             listener: listener.into(),
             tasks: TaskPool::default(),
             service_map: self.service_map,
             services: self.services.into_boxed_slice(),
+            private_service_lease: self.private_service_lease,
+            max_private_services: self.max_private_services,
+            topics: self.topics,
+            connections: std::sync::Mutex::default(),
+            next_connection_id: AtomicU64::new(0),
+            broadcast_timeout: self.broadcast_timeout,
+            connection_hooks: self.connection_hooks,
+            authenticator: self.authenticator,
+            handshake_timeout: self.handshake_timeout,
+            authorization,
+            rate_limiter: self.rate_limiter,
+            load_shedder: self
+                .load_shedding
+                .map(|load_shedding| Arc::new(LoadShedder::new(load_shedding))),
+            concurrency_limiter: ConcurrencyLimiter::new(&self.concurrency_limits),
+            concurrency_limits: self.concurrency_limits,
             _format: PhantomData,
-        }
+        })
     }
 }
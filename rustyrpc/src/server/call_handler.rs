@@ -1,32 +1,104 @@
-use super::{call_stream::CallHandler, PrivateServiceAllocator, Server};
+use super::{
+    call_stream::CallHandler, concurrency::CallSlot, load_shedding::InFlightCall,
+    promise_table::PromiseTable, rate_limit, ClientServices, ConnectionInfo,
+    PrivateServiceAllocator, Server, ServiceRef, TopicSubscription,
+};
 use crate::{
-    format::EncodingFormat,
+    format::{Decode, EncodingFormat},
     multipart::{MultipartReceived, MultipartSendable},
     protocol::{
-        InvalidPrivateServiceIdError, RemoteServiceIdRequestError, ServiceCallRequestError,
-        ServiceKind,
+        BroadcastRejectedError, InvalidPrivateServiceIdError, PromisedReturns,
+        RemoteServiceIdRequestError, RevokedServices, ServiceCallRequestError, ServiceKind,
+        TopicNotFoundError,
     },
     service::Service,
     transport,
 };
 use alloc::sync::Arc;
+use core::{future::Future, time::Duration};
 use derive_where::derive_where;
-use log::trace;
+use log::{trace, warn};
+use tokio::sync::{mpsc, Semaphore};
 
 #[derive_where(Clone)]
 pub(super) struct ServerCallHandler<Listener: transport::ConnectionListener, Format: EncodingFormat>
 {
     server: Arc<Server<Listener, Format>>,
     private_service_allocator: Arc<PrivateServiceAllocator<Format>>,
+    promises: Arc<PromiseTable>,
+    call_slots: Option<Arc<Semaphore>>,
 }
 
 impl<Listener: transport::ConnectionListener, Format: EncodingFormat>
     ServerCallHandler<Listener, Format>
 {
-    pub(super) fn new_for_connection(server: Arc<Server<Listener, Format>>) -> Self {
+    pub(super) fn new_for_connection(
+        server: Arc<Server<Listener, Format>>,
+        client_services: ClientServices<Format>,
+        connection: ConnectionInfo,
+    ) -> Self {
+        let private_service_allocator = PrivateServiceAllocator::default()
+            .with_lease(server.private_service_lease)
+            .with_max_services(server.max_private_services)
+            .with_client_services(client_services)
+            .with_connection(connection, server.connection_hooks.clone());
+
         Self {
+            private_service_allocator: Arc::new(private_service_allocator),
+            promises: Arc::default(),
+            call_slots: server.concurrency_limits.connection_slots().map(Arc::new),
             server,
-            private_service_allocator: Arc::default(),
+        }
+    }
+
+    async fn deallocate_private_service(
+        &self,
+        service_id: u64,
+    ) -> Result<(), InvalidPrivateServiceIdError> {
+        self.private_service_allocator
+            .release(service_id)
+            .await
+            .ok_or(InvalidPrivateServiceIdError)?;
+        self.promises.remove_resolved_to(&[service_id]).await;
+
+        Ok(())
+    }
+
+    async fn renew_private_service(
+        &self,
+        service_id: u64,
+    ) -> Result<(), InvalidPrivateServiceIdError> {
+        self.private_service_allocator
+            .renew(service_id)
+            .await
+            .then_some(())
+            .ok_or(InvalidPrivateServiceIdError)
+    }
+
+    /// Returns ids of private services that are still allocated on connection.
+    pub(super) async fn allocated_private_services(&self) -> Vec<u64> {
+        self.private_service_allocator.allocated_ids().await
+    }
+
+    /// Periodically frees private services with expired lease until connection is closed.
+    pub(super) fn expire_private_services(
+        &self,
+        lease: Duration,
+    ) -> impl Future<Output = ()> + Send + 'static {
+        let allocator = Arc::downgrade(&self.private_service_allocator);
+        let period = lease.checked_div(2).unwrap_or(lease);
+
+        async move {
+            loop {
+                tokio::time::sleep(period).await;
+                let Some(allocator) = allocator.upgrade() else {
+                    return;
+                };
+
+                for service_id in allocator.remove_expired().await {
+                    trace!("Private service lease expired. Service id: {service_id}");
+                }
+            }
         }
     }
 
@@ -45,6 +117,146 @@ impl<Listener: transport::ConnectionListener, Format: EncodingFormat>
             .await
     }
 
+    /// Checks that client of connection may call function of public service.
+    fn authorize(&self, service_id: u64, function_id: u32) -> Result<(), ServiceCallRequestError> {
+        let identity = self
+            .private_service_allocator
+            .connection()
+            .and_then(ConnectionInfo::identity);
+        let is_allowed = u32::try_from(service_id).is_ok_and(|public_service_id| {
+            self.server
+                .authorization
+                .allows(public_service_id, function_id, identity)
+        });
+
+        is_allowed
+            .then_some(())
+            .ok_or(ServiceCallRequestError::PermissionDenied)
+    }
+
+    /// Takes slot for call within concurrency limits, waiting in queue if needed. Refused calls are rejected as overloaded.
+    async fn acquire_call_slot(
+        &self,
+        service_id: u64,
+        function_id: u32,
+    ) -> Result<CallSlot<'_>, ServiceCallRequestError> {
+        self.server
+            .concurrency_limiter
+            .acquire(self.call_slots.as_deref())
+            .await
+            .ok_or_else(|| {
+                trace!("Call is refused because concurrency limit is reached. Service id: {service_id}, function id: {function_id}");
+                ServiceCallRequestError::Overloaded
+            })
+    }
+
+    /// Rejects call if server is overloaded, otherwise counts it as in flight until returned guard is dropped.
+    fn shed_load(
+        &self,
+        service_id: u64,
+        function_id: u32,
+    ) -> Result<Option<InFlightCall<'_>>, ServiceCallRequestError> {
+        let Some(load_shedder) = &self.server.load_shedder else {
+            return Ok(None);
+        };
+
+        load_shedder
+            .admit()
+            .map(Some)
+            .ok_or_else(|| {
+                trace!("Call is shed because server is overloaded. Service id: {service_id}, function id: {function_id}");
+                ServiceCallRequestError::Overloaded
+            })
+    }
+
+    /// Checks that call fits rate limits of server.
+    fn limit_rate(
+        &self,
+        kind: ServiceKind,
+        service_id: u64,
+        function_id: u32,
+    ) -> Result<(), ServiceCallRequestError> {
+        let Some(connection) = self.private_service_allocator.connection() else {
+            return Ok(());
+        };
+        let call = rate_limit::Call {
+            connection_id: connection.id(),
+            identity: connection.identity(),
+            is_private: matches!(kind, ServiceKind::Private),
+            service_id,
+            function_id,
+        };
+
+        self.server
+            .rate_limiter
+            .acquire(&call)
+            .map_err(|retry_after| ServiceCallRequestError::ResourceExhausted { retry_after })
+    }
+
+    /// Finds service and calls its function. One-way calls are accepted only by functions declared as one-way.
+    async fn dispatch_call(
+        &self,
+        kind: ServiceKind,
+        service_id: u64,
+        function_id: u32,
+        args: MultipartReceived,
+        one_way: bool,
+    ) -> Result<MultipartSendable, ServiceCallRequestError> {
+        #[allow(clippy::map_err_ignore)]
+        let (kind, service_id) = match kind {
+            ServiceKind::Promised => {
+                let promise_id = service_id
+                    .try_into()
+                    .map_err(|_| ServiceCallRequestError::InvalidServiceId)?;
+                (
+                    ServiceKind::Private,
+                    self.promises
+                        .wait(promise_id)
+                        .await
+                        .ok_or(ServiceCallRequestError::InvalidServiceId)?,
+                )
+            }
+            ServiceKind::Public | ServiceKind::Private => (kind, service_id),
+        };
+        // Calls that are rejected anyway don't take slot or wait in queue.
+        if matches!(kind, ServiceKind::Public) {
+            self.authorize(service_id, function_id)?;
+        }
+        self.limit_rate(kind, service_id, function_id)?;
+        let _in_flight = self.shed_load(service_id, function_id)?;
+        let _slot = self.acquire_call_slot(service_id, function_id).await?;
+
+        match kind {
+            ServiceKind::Public
+                if let Some(service) = usize::try_from(service_id)
+                    .ok()
+                    .and_then(|index| self.server.services.get(index)) =>
+            {
+                if one_way && !service.is_one_way(function_id) {
+                    return Err(ServiceCallRequestError::InvalidFunctionId);
+                }
+
+                self.clone()
+                    .handle_public_service_call(service.as_ref(), function_id, args)
+                    .await
+            }
+            ServiceKind::Private
+                if let Some(service) = self.private_service_allocator.get(service_id).await =>
+            {
+                if one_way && !service.is_one_way(function_id) {
+                    return Err(ServiceCallRequestError::InvalidFunctionId);
+                }
+
+                self.clone()
+                    .handle_private_service_call(service, function_id, args)
+                    .await
+            }
+            ServiceKind::Public | ServiceKind::Private | ServiceKind::Promised => {
+                Err(ServiceCallRequestError::InvalidServiceId)
+            }
+        }
+    }
+
     async fn handle_public_service_call(
         self,
         service: &dyn Service<Format>,
@@ -61,40 +273,94 @@ impl<Listener: transport::ConnectionListener, Format: EncodingFormat>
     }
 }
 
-impl<Listener: transport::ConnectionListener, Format: EncodingFormat> CallHandler
+impl<Listener: transport::ConnectionListener, Format: EncodingFormat>
+    ServerCallHandler<Listener, Format>
+where
+    ServiceRef: Decode<Format>,
+    Option<ServiceRef>: Decode<Format>,
+{
+    /// Decodes id of private service returned by promising call. Returns `None` if no service of this connection is returned.
+    fn returned_service(&self, returns: &MultipartSendable, kind: PromisedReturns) -> Option<u64> {
+        let returns = returns.first()?;
+        let service_ref = match kind {
+            PromisedReturns::Service => ServiceRef::decode(returns).ok(),
+            PromisedReturns::OptionalService => Option::<ServiceRef>::decode(returns).ok()?,
+        }?;
+
+        self.private_service_allocator
+            .has_issued(&service_ref)
+            .then_some(service_ref.service_id)
+    }
+}
+
+impl<Listener: transport::ConnectionListener + 'static, Format: EncodingFormat> CallHandler
     for ServerCallHandler<Listener, Format>
+where
+    ServiceRef: Decode<Format>,
+    Option<ServiceRef>: Decode<Format>,
 {
     async fn handle_call(
         &self,
         kind: ServiceKind,
-        service_id: u32,
+        service_id: u64,
         function_id: u32,
         args: MultipartReceived,
     ) -> Result<MultipartSendable, ServiceCallRequestError> {
         trace!("Received service call. Kind: {kind:?}, service id: {service_id}, function_id: {function_id}");
 
-        #[allow(clippy::map_err_ignore)]
-        let service_id: usize = service_id
-            .try_into()
-            .map_err(|_| ServiceCallRequestError::InvalidServiceId)?;
+        self.dispatch_call(kind, service_id, function_id, args, false)
+            .await
+    }
 
-        match kind {
-            ServiceKind::Public if let Some(service) = self.server.services.get(service_id) => {
-                self.clone()
-                    .handle_public_service_call(service.as_ref(), function_id, args)
-                    .await
-            }
-            ServiceKind::Private
-                if let Some(service) = self.private_service_allocator.get(service_id).await =>
+    fn handle_one_way_call(
+        &self,
+        kind: ServiceKind,
+        service_id: u64,
+        function_id: u32,
+        args: MultipartReceived,
+    ) {
+        trace!("Received one-way service call. Kind: {kind:?}, service id: {service_id}, function_id: {function_id}");
+
+        // Call takes slot and is rate limited the same way as any other call.
+        let handler = self.clone();
+        self.server.tasks.spawn_task(async move {
+            if let Err(err) = handler
+                .dispatch_call(kind, service_id, function_id, args, true)
+                .await
             {
-                self.clone()
-                    .handle_private_service_call(service, function_id, args)
-                    .await
-            }
-            ServiceKind::Public | ServiceKind::Private => {
-                Err(ServiceCallRequestError::InvalidServiceId)
+                warn!("One-way call of service {service_id} ({kind:?}), function id: {function_id} failed: {err}");
             }
+        });
+    }
+
+    async fn handle_promising_call(
+        &self,
+        kind: ServiceKind,
+        service_id: u64,
+        function_id: u32,
+        args: MultipartReceived,
+        promise_id: u32,
+        promised_returns: PromisedReturns,
+    ) -> Result<MultipartSendable, ServiceCallRequestError> {
+        trace!("Received promising service call. Kind: {kind:?}, service id: {service_id}, function_id: {function_id}, promise id: {promise_id}");
+
+        self.promises.promise(promise_id).await;
+        let result = self
+            .dispatch_call(kind, service_id, function_id, args, false)
+            .await;
+        let returned_service = result
+            .as_ref()
+            .ok()
+            .and_then(|returns| self.returned_service(returns, promised_returns));
+        self.promises.resolve(promise_id, returned_service).await;
+        // Watcher of revocations removes promises of services freed by server, otherwise they are removed here.
+        if !self.private_service_allocator.is_watching_revocations() {
+            self.promises
+                .retain_allocated(self.private_service_allocator.allocated_ids().await)
+                .await;
         }
+
+        result
     }
 
     async fn handle_service_request(
@@ -116,25 +382,144 @@ impl<Listener: transport::ConnectionListener, Format: EncodingFormat> CallHandle
         }
     }
 
-    #[allow(clippy::map_err_ignore)]
     async fn handle_private_service_deallocation(
         &self,
-        service_id: u32,
+        service_id: u64,
     ) -> Result<(), InvalidPrivateServiceIdError> {
         trace!("Received private service deallocation request. Service id: {service_id}");
 
-        let service_id = service_id
-            .try_into()
-            .map_err(|_| InvalidPrivateServiceIdError)?;
-        if self
-            .private_service_allocator
-            .deallocate_by_id(service_id)
+        self.deallocate_private_service(service_id).await
+    }
+
+    async fn handle_promised_service_deallocation(
+        &self,
+        promise_id: u32,
+    ) -> Result<(), InvalidPrivateServiceIdError> {
+        trace!("Received promised service deallocation request. Promise id: {promise_id}");
+
+        let service_id = self
+            .promises
+            .wait(promise_id)
             .await
-            .is_some()
-        {
-            Ok(())
-        } else {
-            Err(InvalidPrivateServiceIdError)
+            .ok_or(InvalidPrivateServiceIdError)?;
+        self.deallocate_private_service(service_id).await
+    }
+
+    async fn handle_private_services_deallocation(
+        &self,
+        service_ids: &[u64],
+        promise_ids: &[u32],
+    ) -> Result<(), InvalidPrivateServiceIdError> {
+        trace!("Received batched private services deallocation request. Service ids: {service_ids:?}, promise ids: {promise_ids:?}");
+
+        let mut result = Ok(());
+        for service_id in service_ids {
+            result = result.and(self.deallocate_private_service(*service_id).await);
+        }
+        for promise_id in promise_ids {
+            result = result.and(self.handle_promised_service_deallocation(*promise_id).await);
         }
+
+        result
+    }
+
+    #[allow(clippy::map_err_ignore)]
+    async fn handle_private_service_duplication(
+        &self,
+        kind: ServiceKind,
+        service_id: u64,
+    ) -> Result<u64, InvalidPrivateServiceIdError> {
+        trace!("Received private service duplication request. Kind: {kind:?}, service id: {service_id}");
+
+        let service_id = match kind {
+            ServiceKind::Promised => {
+                let promise_id = service_id
+                    .try_into()
+                    .map_err(|_| InvalidPrivateServiceIdError)?;
+                self.promises
+                    .wait(promise_id)
+                    .await
+                    .ok_or(InvalidPrivateServiceIdError)?
+            }
+            ServiceKind::Private => service_id,
+            ServiceKind::Public => return Err(InvalidPrivateServiceIdError),
+        };
+        self.private_service_allocator
+            .duplicate_by_id(service_id)
+            .await
+            .ok_or(InvalidPrivateServiceIdError)
+    }
+
+    fn watch_revocations(&self) -> mpsc::UnboundedReceiver<u64> {
+        trace!("Received revocations watching request");
+
+        self.private_service_allocator.watch_revocations()
+    }
+
+    async fn revoked_services(&self, service_ids: Vec<u64>) -> RevokedServices {
+        RevokedServices {
+            promise_ids: self.promises.remove_resolved_to(&service_ids).await,
+            ids: service_ids,
+        }
+    }
+
+    fn subscribe(&self, topic: &str) -> Result<TopicSubscription, TopicNotFoundError> {
+        trace!("Received topic subscription request. Topic: {topic}");
+
+        self.server
+            .topics
+            .as_ref()
+            .ok_or(TopicNotFoundError)?
+            .subscribe(topic)
+    }
+
+    fn handle_broadcast(&self, _: &[u8]) -> Result<(), BroadcastRejectedError> {
+        // Only server broadcasts messages.
+        Err(BroadcastRejectedError)
+    }
+
+    async fn handle_private_service_renewal(
+        &self,
+        service_id: u64,
+    ) -> Result<(), InvalidPrivateServiceIdError> {
+        trace!("Received private service renewal request. Service id: {service_id}");
+
+        self.renew_private_service(service_id).await
+    }
+
+    async fn handle_promised_service_renewal(
+        &self,
+        promise_id: u32,
+    ) -> Result<(), InvalidPrivateServiceIdError> {
+        trace!("Received promised service renewal request. Promise id: {promise_id}");
+
+        let service_id = self
+            .promises
+            .wait(promise_id)
+            .await
+            .ok_or(InvalidPrivateServiceIdError)?;
+        self.renew_private_service(service_id).await
+    }
+
+    async fn handle_private_services_renewal(
+        &self,
+        service_ids: &[u64],
+        promise_ids: &[u32],
+    ) -> Result<(), InvalidPrivateServiceIdError> {
+        trace!("Received batched private services renewal request. Service ids: {service_ids:?}, promise ids: {promise_ids:?}");
+
+        let mut result = Ok(());
+        for service_id in service_ids {
+            result = result.and(self.renew_private_service(*service_id).await);
+        }
+        for promise_id in promise_ids {
+            result = result.and(self.handle_promised_service_renewal(*promise_id).await);
+        }
+
+        result
+    }
+
+    fn private_service_lease(&self) -> Option<Duration> {
+        self.server.private_service_lease
     }
 }
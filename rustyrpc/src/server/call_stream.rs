@@ -4,24 +4,51 @@ use crate::{
     },
     multipart::{MultipartReceived, MultipartSendable},
     protocol::{
-        InvalidPrivateServiceIdError, PrivateServiceDeallocateRequestResult,
-        RemoteServiceIdRequestError, RequestKind, ServiceCallRequestError,
-        ServiceCallRequestResult, ServiceFound, ServiceIdRequestResult, ServiceKind,
+        AuthenticateRequestResult, AuthenticationError, BatchedCall, BroadcastRejectedError,
+        BroadcastRequestResult, InvalidPrivateServiceIdError,
+        PrivateServiceDeallocateRequestResult, PrivateServiceDuplicateRequestResult,
+        PrivateServiceLease, PromisedReturns, RemoteServiceIdRequestError, RequestKind,
+        RevokedServices, ServiceCallRequestError, ServiceCallRequestResult, ServiceFound,
+        ServiceIdRequestResult, ServiceIdsRequestResult, ServiceKind, ServiceQuery,
+        TopicNotFoundError, TopicSubscribeRequestResult,
     },
+    server::{Authenticator, ConnectionInfo, Identity, TopicSubscription},
     transport::{self, StreamExt},
 };
-use core::{future::Future, marker::PhantomData};
+use core::{future::Future, marker::PhantomData, time::Duration};
+use futures::future;
+use log::trace;
 use std::io;
+use tokio::sync::mpsc;
 
 pub(crate) trait CallHandler {
     fn handle_call(
         &self,
         kind: ServiceKind,
-        service_id: u32,
+        service_id: u64,
         function_id: u32,
         args: MultipartReceived,
     ) -> impl Future<Output = Result<MultipartSendable, ServiceCallRequestError>> + Send;
 
+    fn handle_promising_call(
+        &self,
+        kind: ServiceKind,
+        service_id: u64,
+        function_id: u32,
+        args: MultipartReceived,
+        promise_id: u32,
+        returns: PromisedReturns,
+    ) -> impl Future<Output = Result<MultipartSendable, ServiceCallRequestError>> + Send;
+
+    /// Dispatches one-way call in background, so stream isn't blocked while call is handled.
+    fn handle_one_way_call(
+        &self,
+        kind: ServiceKind,
+        service_id: u64,
+        function_id: u32,
+        args: MultipartReceived,
+    );
+
     fn handle_service_request(
         &self,
         name: &str,
@@ -30,8 +57,58 @@ pub(crate) trait CallHandler {
 
     fn handle_private_service_deallocation(
         &self,
-        service_id: u32,
+        service_id: u64,
     ) -> impl Future<Output = Result<(), InvalidPrivateServiceIdError>> + Send;
+
+    fn handle_promised_service_deallocation(
+        &self,
+        promise_id: u32,
+    ) -> impl Future<Output = Result<(), InvalidPrivateServiceIdError>> + Send;
+
+    fn handle_private_services_deallocation(
+        &self,
+        service_ids: &[u64],
+        promise_ids: &[u32],
+    ) -> impl Future<Output = Result<(), InvalidPrivateServiceIdError>> + Send;
+
+    fn handle_private_service_duplication(
+        &self,
+        kind: ServiceKind,
+        service_id: u64,
+    ) -> impl Future<Output = Result<u64, InvalidPrivateServiceIdError>> + Send;
+
+    /// Returns receiver of ids of private services freed by server itself.
+    fn watch_revocations(&self) -> mpsc::UnboundedReceiver<u64>;
+
+    fn revoked_services(
+        &self,
+        service_ids: Vec<u64>,
+    ) -> impl Future<Output = RevokedServices> + Send;
+
+    /// Subscribes to topic declared by server.
+    fn subscribe(&self, topic: &str) -> Result<TopicSubscription, TopicNotFoundError>;
+
+    /// Handles message broadcast by server.
+    fn handle_broadcast(&self, message: &[u8]) -> Result<(), BroadcastRejectedError>;
+
+    fn handle_private_service_renewal(
+        &self,
+        service_id: u64,
+    ) -> impl Future<Output = Result<(), InvalidPrivateServiceIdError>> + Send;
+
+    fn handle_promised_service_renewal(
+        &self,
+        promise_id: u32,
+    ) -> impl Future<Output = Result<(), InvalidPrivateServiceIdError>> + Send;
+
+    fn handle_private_services_renewal(
+        &self,
+        service_ids: &[u64],
+        promise_ids: &[u32],
+    ) -> impl Future<Output = Result<(), InvalidPrivateServiceIdError>> + Send;
+
+    /// Returns time private services are kept without renewal or `None` if they aren't leased.
+    fn private_service_lease(&self) -> Option<Duration>;
 }
 
 pub(crate) struct CallStream<Stream: transport::Stream, Format: EncodingFormat> {
@@ -44,9 +121,40 @@ where
     for<'b, 'c> RequestKind<'b>:
         DecodeZeroCopy<'b, Format, <RequestKind<'c> as DecodeZeroCopyFallible<Format>>::Error>,
     ServiceIdRequestResult: Encode<Format>,
+    ServiceIdsRequestResult: Encode<Format>,
     for<'a> ServiceCallRequestResult<'a>: Encode<Format>,
     PrivateServiceDeallocateRequestResult: Encode<Format>,
+    PrivateServiceDuplicateRequestResult: Encode<Format>,
+    RevokedServices: Encode<Format>,
+    TopicSubscribeRequestResult: Encode<Format>,
+    BroadcastRequestResult: Encode<Format>,
+    AuthenticateRequestResult: Encode<Format>,
+    PrivateServiceLease: Encode<Format>,
 {
+    /// Handles authentication handshake that must be the first request of connection. Returns identity of authenticated client.
+    pub(crate) async fn handle_handshake(
+        mut self,
+        authenticator: &dyn Authenticator,
+        connection: &ConnectionInfo,
+    ) -> io::Result<Identity> {
+        let request = self.stream.receive().await?;
+        let request = RequestKind::decode_zero_copy(&request)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        let RequestKind::Authenticate { credentials } = request else {
+            return Err(AuthenticationError.into());
+        };
+
+        let (response, identity): (AuthenticateRequestResult, _) =
+            match authenticator.authenticate(connection, credentials).await {
+                Ok(identity) => (Ok(()), Some(identity)),
+                Err(err) => (Err(err), None),
+            };
+        self.stream.send_encodable(&response).await?;
+        self.stream.flush().await?;
+
+        identity.ok_or_else(|| AuthenticationError.into())
+    }
+
     pub(crate) async fn handle_call<H>(mut self, handler: &H) -> io::Result<()>
     where
         H: CallHandler,
@@ -61,28 +169,200 @@ where
                     self.handle_service_id_request(handler, name, checksum)
                         .await?;
                 }
+                RequestKind::ServiceIds { services } => {
+                    self.handle_service_ids_request(handler, &services).await?;
+                }
                 RequestKind::ServiceCall {
                     kind,
                     id,
                     function_id,
                     part_sizes,
+                } => {
+                    self.handle_service_call_request(handler, kind, id, function_id, part_sizes)
+                        .await?;
+                }
+                RequestKind::PromisingCall {
+                    kind,
+                    id,
+                    function_id,
+                    part_sizes,
+                    promise_id,
+                    returns,
                 } => {
                     let args = MultipartReceived::receive_from_stream(&mut self.stream, part_sizes)
                         .await?;
-
-                    self.handle_service_call_request(handler, kind, id, function_id, args)
+                    let result = handler
+                        .handle_promising_call(kind, id, function_id, args, promise_id, returns)
+                        .await;
+                    self.send_call_result(result).await?;
+                }
+                RequestKind::BatchCall { ordered, calls } => {
+                    self.handle_batch_call_request(handler, ordered, &calls)
+                        .await?;
+                }
+                RequestKind::OneWayCall {
+                    kind,
+                    id,
+                    function_id,
+                    part_sizes,
+                } => {
+                    self.handle_one_way_call_request(handler, kind, id, function_id, part_sizes)
                         .await?;
                 }
                 RequestKind::DeallocatePrivateService { id } => {
                     let response = handler.handle_private_service_deallocation(id).await;
                     self.stream.send_encodable(&response).await?;
                 }
+                RequestKind::DeallocatePromisedService { id } => {
+                    let response = handler.handle_promised_service_deallocation(id).await;
+                    self.stream.send_encodable(&response).await?;
+                }
+                RequestKind::DeallocatePrivateServices { ids, promise_ids } => {
+                    self.handle_private_services_deallocation_request(handler, ids, promise_ids)
+                        .await?;
+                }
+                RequestKind::DuplicatePrivateService { kind, id } => {
+                    let response = handler.handle_private_service_duplication(kind, id).await;
+                    self.stream.send_encodable(&response).await?;
+                }
+                RequestKind::WatchRevocations => return self.watch_revocations(handler).await,
+                RequestKind::Subscribe { topic } => match handler.subscribe(topic) {
+                    Ok(subscription) => return self.stream_events(subscription).await,
+                    Err(err) => {
+                        self.stream
+                            .send_encodable::<TopicSubscribeRequestResult, _>(&Err(err))
+                            .await?;
+                    }
+                },
+                RequestKind::Broadcast { message } => {
+                    let response: BroadcastRequestResult = handler.handle_broadcast(message);
+                    self.stream.send_encodable(&response).await?;
+                }
+                RequestKind::Authenticate { .. } => self.reject_authentication().await?,
+                RequestKind::RenewPrivateService { id } => {
+                    let response = handler.handle_private_service_renewal(id).await;
+                    self.stream.send_encodable(&response).await?;
+                }
+                RequestKind::RenewPromisedService { id } => {
+                    let response = handler.handle_promised_service_renewal(id).await;
+                    self.stream.send_encodable(&response).await?;
+                }
+                RequestKind::RenewPrivateServices { ids, promise_ids } => {
+                    self.handle_private_services_renewal_request(handler, ids, promise_ids)
+                        .await?;
+                }
+                RequestKind::PrivateServiceLease => {
+                    self.send_private_service_lease(handler).await?;
+                }
             }
 
             self.stream.flush().await?;
         }
     }
 
+    /// Rejects authentication that isn't the first request of connection.
+    async fn reject_authentication(&mut self) -> io::Result<()> {
+        self.stream
+            .send_encodable::<AuthenticateRequestResult, _>(&Err(AuthenticationError))
+            .await
+    }
+
+    /// Sends revoked private services until client closes stream or connection is closed, so stream isn't used for other requests anymore.
+    async fn watch_revocations<H: CallHandler>(mut self, handler: &H) -> io::Result<()> {
+        let mut revocations = handler.watch_revocations();
+
+        loop {
+            tokio::select! {
+                service_id = revocations.recv() => {
+                    let Some(service_id) = service_id else {
+                        return Ok(());
+                    };
+                    let mut service_ids = vec![service_id];
+                    while let Ok(revoked_service_id) = revocations.try_recv() {
+                        service_ids.push(revoked_service_id);
+                    }
+
+                    let revoked = handler.revoked_services(service_ids).await;
+                    self.stream.send_encodable(&revoked).await?;
+                    self.stream.flush().await?;
+                }
+                // Client never sends anything else, so any result means that stream is closed.
+                _ = self.stream.receive() => return Ok(()),
+            }
+        }
+    }
+
+    /// Sends events published to topic until client closes stream or server closes slow subscription,
+    /// so stream isn't used for other requests anymore.
+    async fn stream_events(mut self, mut subscription: TopicSubscription) -> io::Result<()> {
+        self.stream
+            .send_encodable::<TopicSubscribeRequestResult, _>(&Ok(()))
+            .await?;
+        self.stream.flush().await?;
+
+        loop {
+            tokio::select! {
+                event = subscription.next() => {
+                    let Some(event) = event else {
+                        trace!("Slow subscription is closed");
+                        return Ok(());
+                    };
+                    self.stream.send(event.to_vec()).await?;
+                    self.stream.flush().await?;
+                }
+                // Client never sends anything else, so any result means that stream is closed.
+                _ = self.stream.receive() => return Ok(()),
+            }
+        }
+    }
+
+    async fn handle_one_way_call_request<H: CallHandler>(
+        &mut self,
+        handler: &H,
+        kind: ServiceKind,
+        id: u64,
+        function_id: u32,
+        part_sizes: &[u32],
+    ) -> io::Result<()> {
+        let args = MultipartReceived::receive_from_stream(&mut self.stream, part_sizes).await?;
+        handler.handle_one_way_call(kind, id, function_id, args);
+
+        Ok(())
+    }
+
+    async fn handle_private_services_deallocation_request<H: CallHandler>(
+        &mut self,
+        handler: &H,
+        ids: &[u64],
+        promise_ids: &[u32],
+    ) -> io::Result<()> {
+        let response = handler
+            .handle_private_services_deallocation(ids, promise_ids)
+            .await;
+
+        self.stream.send_encodable(&response).await
+    }
+
+    async fn handle_private_services_renewal_request<H: CallHandler>(
+        &mut self,
+        handler: &H,
+        ids: &[u64],
+        promise_ids: &[u32],
+    ) -> io::Result<()> {
+        let response = handler
+            .handle_private_services_renewal(ids, promise_ids)
+            .await;
+
+        self.stream.send_encodable(&response).await
+    }
+
+    /// Sends for how long private services are leased by handler.
+    async fn send_private_service_lease<H: CallHandler>(&mut self, handler: &H) -> io::Result<()> {
+        let response = PrivateServiceLease(handler.private_service_lease());
+
+        self.stream.send_encodable(&response).await
+    }
+
     async fn handle_service_id_request<H: CallHandler>(
         &mut self,
         handler: &H,
@@ -97,18 +377,83 @@ where
         self.stream.send_encodable(&response).await
     }
 
+    async fn handle_service_ids_request<H: CallHandler>(
+        &mut self,
+        handler: &H,
+        services: &[ServiceQuery<'_>],
+    ) -> io::Result<()> {
+        let mut response: ServiceIdsRequestResult = Vec::with_capacity(services.len());
+        for service in services {
+            response.push(
+                handler
+                    .handle_service_request(service.name, service.checksum)
+                    .await
+                    .map(ServiceFound),
+            );
+        }
+
+        self.stream.send_encodable(&response).await
+    }
+
     async fn handle_service_call_request<H: CallHandler>(
         &mut self,
         handler: &H,
         kind: ServiceKind,
-        service_id: u32,
+        service_id: u64,
         function_id: u32,
-        args: MultipartReceived,
+        part_sizes: &[u32],
     ) -> io::Result<()> {
-        match handler
+        let args = MultipartReceived::receive_from_stream(&mut self.stream, part_sizes).await?;
+        let result = handler
             .handle_call(kind, service_id, function_id, args)
-            .await
-        {
+            .await;
+
+        self.send_call_result(result).await
+    }
+
+    async fn handle_batch_call_request<H: CallHandler>(
+        &mut self,
+        handler: &H,
+        ordered: bool,
+        calls: &[BatchedCall<'_>],
+    ) -> io::Result<()> {
+        trace!(
+            "Received batch of {} calls. Ordered: {ordered}",
+            calls.len()
+        );
+
+        let mut args = Vec::with_capacity(calls.len());
+        for call in calls {
+            args.push(
+                MultipartReceived::receive_from_stream(&mut self.stream, call.part_sizes).await?,
+            );
+        }
+
+        let calls = calls.iter().zip(args).map(|(call, call_args)| {
+            handler.handle_call(call.kind, call.id, call.function_id, call_args)
+        });
+        let results = if ordered {
+            let mut results = Vec::with_capacity(calls.len());
+            for call in calls {
+                results.push(call.await);
+            }
+            results
+        } else {
+            future::join_all(calls).await
+        };
+
+        for result in results {
+            self.send_call_result(result).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn send_call_result(
+        &mut self,
+        result: Result<MultipartSendable, ServiceCallRequestError>,
+    ) -> io::Result<()> {
+        match result {
             Ok(returns) => {
                 let part_sizes: Vec<u32> = returns
                     .iter()
@@ -1,9 +1,10 @@
 use core::marker::PhantomData;
+use futures::future::BoxFuture;
 use std::io;
 
 use crate::{
     format::EncodingFormat,
-    transport::{self},
+    transport::{self, PeerIdentity},
 };
 
 use super::call_stream::CallStream;
@@ -22,6 +23,22 @@ impl<Connection: transport::ServerConnection, Format: EncodingFormat>
     ) -> io::Result<CallStream<Connection::Stream, Format>> {
         Ok(self.connection.accept_stream().await?.into())
     }
+
+    pub(crate) async fn close(self) -> io::Result<()> {
+        self.connection.close().await
+    }
+
+    pub(crate) fn closed(&self) -> BoxFuture<'static, io::Error> {
+        Box::pin(self.connection.closed())
+    }
+
+    pub(crate) fn stream_opener(&self) -> Connection::StreamOpener {
+        self.connection.stream_opener()
+    }
+
+    pub(crate) fn peer_identity(&self) -> Option<PeerIdentity> {
+        self.connection.peer_identity()
+    }
 }
 
 impl<Connection: transport::ServerConnection, Format: EncodingFormat> From<Connection>
@@ -0,0 +1,124 @@
+use alloc::sync::Arc;
+use core::marker::PhantomData;
+use derive_where::derive_where;
+use futures::future::BoxFuture;
+use std::io;
+
+use super::ServiceRef;
+use crate::{
+    format::{
+        Decode, DecodeZeroCopy, DecodeZeroCopyFallible, Encode, EncodingFormat,
+        ZeroCopyEncodingFormat,
+    },
+    multipart::{MultipartReceived, MultipartSendable},
+    protocol::{RequestKind, ServiceCallRequestResult, ServiceKind},
+    transport::{self, StreamExt},
+};
+
+type CallFn = dyn Fn(u64, u32, MultipartSendable) -> BoxFuture<'static, io::Result<MultipartReceived>>
+    + Send
+    + Sync;
+
+/// Calls services [hosted by client][crate::Client::host_service] on the other side of connection.
+/// Client passes references to its services as call arguments, e.g. to receive callbacks or progress updates.
+#[derive_where(Clone)]
+pub struct ClientServices<Format: EncodingFormat> {
+    call: Arc<CallFn>,
+    _format: PhantomData<fn() -> Format>,
+}
+
+impl<Format: ZeroCopyEncodingFormat> ClientServices<Format>
+where
+    for<'a> RequestKind<'a>: Encode<Format>,
+    for<'a> ServiceCallRequestResult<'a>: DecodeZeroCopy<
+        'a,
+        Format,
+        <ServiceCallRequestResult<'a> as DecodeZeroCopyFallible<Format>>::Error,
+    >,
+{
+    pub(crate) fn new<Opener: transport::StreamOpener>(opener: Opener) -> Self {
+        let opener = Arc::new(opener);
+        Self {
+            call: Arc::new(move |service_id, function_id, args| {
+                let opener = Arc::clone(&opener);
+                Box::pin(async move {
+                    let mut stream = opener.open_stream().await?;
+                    Self::call_on(&mut stream, service_id, function_id, &args).await
+                })
+            }),
+            _format: PhantomData,
+        }
+    }
+
+    async fn call_on<S: transport::Stream>(
+        stream: &mut S,
+        service_id: u64,
+        function_id: u32,
+        args: &MultipartSendable,
+    ) -> io::Result<MultipartReceived> {
+        let part_sizes: Vec<u32> = args
+            .iter()
+            .map(|part| part.len().try_into())
+            .try_collect()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        let request = RequestKind::ServiceCall {
+            kind: ServiceKind::Private,
+            id: service_id,
+            function_id,
+            part_sizes: &part_sizes,
+        };
+        stream.send_encodable(&request).await?;
+        stream.send_multipart(args).await?;
+        stream.flush().await?;
+
+        let service_call_result = stream.receive().await?;
+        let returns_part_sizes = ServiceCallRequestResult::decode_zero_copy(&service_call_result)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))??;
+        MultipartReceived::receive_from_stream(stream, returns_part_sizes).await
+    }
+}
+
+impl<Format: EncodingFormat> ClientServices<Format> {
+    /// Calls service hosted by client with multipart as arguments.
+    ///
+    /// # Errors
+    /// Returns an error if connection is lost or service call fails on client.
+    pub async fn call_multipart(
+        &self,
+        service: &ServiceRef,
+        function_id: u32,
+        args: MultipartSendable,
+    ) -> io::Result<MultipartReceived> {
+        (self.call)(service.service_id, function_id, args).await
+    }
+
+    /// Calls service hosted by client.
+    ///
+    /// # Errors
+    /// Returns an error if connection is lost, service call fails on client or returns can't be decoded.
+    pub async fn call<Args, Returns>(
+        &self,
+        service: &ServiceRef,
+        function_id: u32,
+        args: &Args,
+    ) -> io::Result<Returns>
+    where
+        Args: Encode<Format>,
+        Returns: Decode<Format>,
+    {
+        let args = args
+            .encode()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let returns = self
+            .call_multipart(service, function_id, MultipartSendable::from([args]))
+            .await?;
+
+        let returns = returns.iter().next().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                "Client sent no multipart when expected at least one",
+            )
+        })?;
+        Returns::decode(returns).map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+    }
+}
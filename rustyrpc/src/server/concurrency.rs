@@ -0,0 +1,255 @@
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+/// What happens to calls above [concurrency limits][ConcurrencyLimits].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConcurrencyOverflow {
+    /// Calls wait for free slot in queue of specified size shared by server. Calls that don't fit queue are refused.
+    Queue(usize),
+    /// Calls are refused immediately.
+    Refuse,
+}
+
+/// Limits of calls executed at once, see [`ServerBuilder::with_concurrency_limits`][super::ServerBuilder::with_concurrency_limits].
+#[derive(Debug, Clone, Copy)]
+pub struct ConcurrencyLimits {
+    max_calls_per_connection: Option<usize>,
+    max_calls: Option<usize>,
+    overflow: ConcurrencyOverflow,
+    reserved_streams: u32,
+}
+
+impl Default for ConcurrencyLimits {
+    fn default() -> Self {
+        Self {
+            max_calls_per_connection: None,
+            max_calls: None,
+            overflow: ConcurrencyOverflow::Refuse,
+            reserved_streams: DEFAULT_RESERVED_STREAMS,
+        }
+    }
+}
+
+impl ConcurrencyLimits {
+    /// Sets limit of calls executed at once for each connection.
+    #[must_use]
+    pub const fn with_max_calls_per_connection(mut self, max_calls: usize) -> Self {
+        self.max_calls_per_connection = Some(max_calls);
+        self
+    }
+
+    /// Sets limit of calls executed at once by server.
+    #[must_use]
+    pub const fn with_max_calls(mut self, max_calls: usize) -> Self {
+        self.max_calls = Some(max_calls);
+        self
+    }
+
+    /// Sets what happens to calls above limits. By default they are refused.
+    #[must_use]
+    pub const fn with_overflow(mut self, overflow: ConcurrencyOverflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Sets count of streams each connection may open in addition to streams of calls and queued calls.
+    /// They are used by long-lived requests like subscriptions, revocation watching and authentication handshake.
+    /// Client waits for free stream when it opens more streams than allowed, and server doesn't handle more streams of connection at once.
+    #[must_use]
+    pub const fn with_reserved_streams(mut self, reserved_streams: u32) -> Self {
+        self.reserved_streams = reserved_streams;
+        self
+    }
+
+    /// Returns count of streams each connection needs: one per call and queued call plus streams that aren't used for calls.
+    pub(super) fn max_streams_per_connection(&self) -> Option<u32> {
+        let queued = match self.overflow {
+            ConcurrencyOverflow::Queue(max_queued) => max_queued,
+            ConcurrencyOverflow::Refuse => 0,
+        };
+
+        self.max_calls_per_connection.map(|max_calls| {
+            u32::try_from(max_calls.saturating_add(queued))
+                .unwrap_or(u32::MAX)
+                .saturating_add(self.reserved_streams)
+        })
+    }
+
+    /// Returns slots of streams handled at once by each connection, so every accepted stream waits for free slot before its task is spawned.
+    pub(super) fn connection_stream_slots(&self) -> Option<Arc<Semaphore>> {
+        self.max_streams_per_connection()
+            .map(|max_streams| Arc::new(new_slots(max_streams.try_into().unwrap_or(usize::MAX))))
+    }
+
+    pub(super) fn connection_slots(&self) -> Option<Semaphore> {
+        self.max_calls_per_connection.map(new_slots)
+    }
+}
+
+const DEFAULT_RESERVED_STREAMS: u32 = 8;
+
+fn new_slots(max_calls: usize) -> Semaphore {
+    Semaphore::new(max_calls.min(Semaphore::MAX_PERMITS))
+}
+
+/// Enforces concurrency limits of calls.
+pub(super) struct ConcurrencyLimiter {
+    slots: Option<Semaphore>,
+    overflow: ConcurrencyOverflow,
+    queued: AtomicUsize,
+}
+
+impl ConcurrencyLimiter {
+    pub(super) fn new(limits: &ConcurrencyLimits) -> Self {
+        Self {
+            slots: limits.max_calls.map(new_slots),
+            overflow: limits.overflow,
+            queued: AtomicUsize::new(0),
+        }
+    }
+
+    /// Takes slot of server and of connection for call, waiting in queue if overflow policy allows.
+    /// Returns `None` if call is refused.
+    pub(super) async fn acquire<'a>(
+        &'a self,
+        connection_slots: Option<&'a Semaphore>,
+    ) -> Option<CallSlot<'a>> {
+        if let Some(slot) = Self::try_acquire(self.slots.as_ref(), connection_slots) {
+            return Some(slot);
+        }
+
+        let ConcurrencyOverflow::Queue(max_queued) = self.overflow else {
+            return None;
+        };
+        let _queued = QueuedCall::enter(&self.queued, max_queued)?;
+        // Slot of connection is taken first, so call waiting for it doesn't hold slot of server.
+        let connection = Self::acquire_from(connection_slots).await?;
+        Some(CallSlot {
+            _server: Self::acquire_from(self.slots.as_ref()).await?,
+            _connection: connection,
+        })
+    }
+
+    fn try_acquire<'a>(
+        server_slots: Option<&'a Semaphore>,
+        connection_slots: Option<&'a Semaphore>,
+    ) -> Option<CallSlot<'a>> {
+        let try_acquire_from =
+            |slots: Option<&'a Semaphore>| slots.map(Semaphore::try_acquire).transpose().ok();
+
+        Some(CallSlot {
+            _server: try_acquire_from(server_slots)?,
+            _connection: try_acquire_from(connection_slots)?,
+        })
+    }
+
+    async fn acquire_from(slots: Option<&Semaphore>) -> Option<Option<SemaphorePermit<'_>>> {
+        match slots {
+            Some(slots) => slots.acquire().await.ok().map(Some),
+            None => Some(None),
+        }
+    }
+}
+
+/// Slot of call held until call is finished.
+pub(super) struct CallSlot<'a> {
+    _server: Option<SemaphorePermit<'a>>,
+    _connection: Option<SemaphorePermit<'a>>,
+}
+
+/// Call waiting in queue for free slot.
+struct QueuedCall<'a>(&'a AtomicUsize);
+
+impl<'a> QueuedCall<'a> {
+    fn enter(queued: &'a AtomicUsize, max_queued: usize) -> Option<Self> {
+        let call = Self(queued);
+        (queued.fetch_add(1, Ordering::AcqRel) < max_queued).then_some(call)
+    }
+}
+
+impl Drop for QueuedCall<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use core::pin::pin;
+    use futures::{poll, FutureExt};
+    use tokio::sync::Semaphore;
+
+    use super::{ConcurrencyLimiter, ConcurrencyLimits, ConcurrencyOverflow};
+
+    #[tokio::test]
+    async fn calls_above_limit_are_refused() {
+        let limiter = ConcurrencyLimiter::new(&ConcurrencyLimits::default().with_max_calls(1));
+
+        let slot = limiter.acquire(None).await.unwrap();
+        assert!(limiter.acquire(None).await.is_none());
+        drop(slot);
+        assert!(limiter.acquire(None).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn connection_slots_are_limited_separately() {
+        let limits = ConcurrencyLimits::default()
+            .with_max_calls(2)
+            .with_max_calls_per_connection(1);
+        let limiter = ConcurrencyLimiter::new(&limits);
+        let first_connection = limits.connection_slots().unwrap();
+        let second_connection = limits.connection_slots().unwrap();
+
+        let _slot = limiter.acquire(Some(&first_connection)).await.unwrap();
+        assert!(limiter.acquire(Some(&first_connection)).await.is_none());
+        assert!(limiter.acquire(Some(&second_connection)).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn calls_wait_in_queue_until_it_is_full() {
+        let limiter = ConcurrencyLimiter::new(
+            &ConcurrencyLimits::default()
+                .with_max_calls(1)
+                .with_overflow(ConcurrencyOverflow::Queue(1)),
+        );
+
+        let slot = limiter.acquire(None).await.unwrap();
+        let mut queued = pin!(limiter.acquire(None));
+        assert!(poll!(queued.as_mut()).is_pending());
+        assert!(limiter.acquire(None).await.is_none());
+
+        drop(slot);
+        assert!(queued.await.is_some());
+        assert!(limiter.acquire(None).now_or_never().unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn queued_call_doesnt_hold_server_slot_while_waiting_for_connection() {
+        let limits = ConcurrencyLimits::default()
+            .with_max_calls(1)
+            .with_max_calls_per_connection(1)
+            .with_overflow(ConcurrencyOverflow::Queue(1));
+        let limiter = ConcurrencyLimiter::new(&limits);
+        let busy_connection = Semaphore::new(0);
+
+        let mut queued = pin!(limiter.acquire(Some(&busy_connection)));
+        assert!(poll!(queued.as_mut()).is_pending());
+        assert!(limiter.acquire(None).await.is_some());
+    }
+
+    #[test]
+    fn streams_per_connection_include_queued_calls_and_reserved_streams() {
+        let limits = ConcurrencyLimits::default()
+            .with_max_calls_per_connection(4)
+            .with_overflow(ConcurrencyOverflow::Queue(2))
+            .with_reserved_streams(1);
+
+        assert_eq!(limits.max_streams_per_connection(), Some(7));
+        assert_eq!(
+            ConcurrencyLimits::default().max_streams_per_connection(),
+            None
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use alloc::borrow::Cow;
+use async_trait::async_trait;
+use std::io;
+use thiserror::Error;
+
+use super::Identity;
+use crate::transport::PeerIdentity;
+
+/// Connection of client as seen by [`ConnectionHooks`] and services.
+#[derive(Debug, Clone)]
+pub struct ConnectionInfo {
+    id: u64,
+    identity: Option<Identity>,
+    peer_identity: Option<PeerIdentity>,
+}
+
+impl ConnectionInfo {
+    pub(crate) const fn new(id: u64, peer_identity: Option<PeerIdentity>) -> Self {
+        Self {
+            id,
+            identity: None,
+            peer_identity,
+        }
+    }
+
+    pub(crate) fn with_identity(mut self, identity: Identity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Returns id of connection, unique within server.
+    #[must_use]
+    pub const fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Returns identity of client established by [authentication handshake][super::Authenticator] or `None` if client isn't authenticated.
+    #[must_use]
+    pub const fn identity(&self) -> Option<&Identity> {
+        self.identity.as_ref()
+    }
+
+    /// Returns identity of client verified by transport, e.g. by [client certificate][crate::transport::quic::ConnectionListener::with_client_verification], or `None` if client isn't verified.
+    #[must_use]
+    pub const fn peer_identity(&self) -> Option<&PeerIdentity> {
+        self.peer_identity.as_ref()
+    }
+}
+
+/// Error returned by [`ConnectionHooks::on_accept`] to reject connection.
+#[derive(Error, Debug)]
+#[error("Connection rejected: {0}")]
+pub struct ConnectionRejectedError(pub Cow<'static, str>);
+
+/// Callbacks invoked by server through lifecycle of each client connection, see [`ServerBuilder::with_connection_hooks`][super::ServerBuilder::with_connection_hooks].
+#[async_trait]
+pub trait ConnectionHooks: Send + Sync + 'static {
+    /// Called once connection is accepted, before any request is handled. Connection is closed if it's rejected.
+    async fn on_accept(&self, _connection: &ConnectionInfo) -> Result<(), ConnectionRejectedError> {
+        Ok(())
+    }
+
+    /// Called once client of connection is authenticated by [handshake][super::Authenticator] or [service][super::PrivateServiceAllocator::mark_authenticated].
+    async fn on_authenticated(&self, _connection: &ConnectionInfo) {}
+
+    /// Called once connection is closed with close reason and ids of private services that were still allocated.
+    async fn on_close(
+        &self,
+        _connection: &ConnectionInfo,
+        _reason: &io::Error,
+        _private_services: &[u64],
+    ) {
+    }
+}
@@ -0,0 +1,136 @@
+use alloc::sync::Arc;
+use core::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+use std::{
+    sync::{Mutex, PoisonError},
+    time::Instant,
+};
+
+/// Settings of load shedding, see [`ServerBuilder::with_load_shedding`][super::ServerBuilder::with_load_shedding].
+///
+/// Calls are rejected when count of calls in flight reaches limit or when queueing latency stayed above target for whole interval.
+/// Queueing latency is measured by periodic probe as time task waits in run queue of async runtime before it's polled again.
+#[derive(Debug, Clone, Copy)]
+pub struct LoadShedding {
+    max_in_flight: usize,
+    target_latency: Duration,
+    interval: Duration,
+}
+
+impl Default for LoadShedding {
+    fn default() -> Self {
+        Self {
+            max_in_flight: 1024,
+            target_latency: Duration::from_millis(5),
+            interval: Duration::from_millis(100),
+        }
+    }
+}
+
+impl LoadShedding {
+    /// Sets limit of calls executed at once.
+    #[must_use]
+    pub const fn with_max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = max_in_flight;
+        self
+    }
+
+    /// Sets queueing latency server considers acceptable.
+    #[must_use]
+    pub const fn with_target_latency(mut self, target_latency: Duration) -> Self {
+        self.target_latency = target_latency;
+        self
+    }
+
+    /// Sets interval minimal queueing latency is measured over. Server is overloaded if minimal latency of interval exceeds target.
+    #[must_use]
+    pub const fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+}
+
+/// Count of queueing latency probes taken during each interval.
+const PROBES_PER_INTERVAL: u32 = 10;
+
+struct LatencyWindow {
+    started_at: Instant,
+    min_latency: Duration,
+    is_overloaded: bool,
+}
+
+/// Admits calls unless server is overloaded.
+pub(super) struct LoadShedder {
+    settings: LoadShedding,
+    in_flight: AtomicUsize,
+    window: Mutex<LatencyWindow>,
+}
+
+impl LoadShedder {
+    pub(super) fn new(settings: LoadShedding) -> Self {
+        Self {
+            settings,
+            in_flight: AtomicUsize::new(0),
+            window: Mutex::new(LatencyWindow {
+                started_at: Instant::now(),
+                min_latency: Duration::MAX,
+                is_overloaded: false,
+            }),
+        }
+    }
+
+    /// Admits call unless server is overloaded. Call is counted as in flight until returned guard is dropped.
+    pub(super) fn admit(&self) -> Option<InFlightCall<'_>> {
+        if self
+            .window
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_overloaded
+        {
+            return None;
+        }
+
+        let in_flight = self.in_flight.fetch_add(1, Ordering::AcqRel);
+        let call = InFlightCall(&self.in_flight);
+        (in_flight < self.settings.max_in_flight).then_some(call)
+    }
+
+    /// Periodically measures queueing latency of runtime. Runs until task is aborted.
+    pub(super) async fn measure_latency(self: Arc<Self>) {
+        let period = self
+            .settings
+            .interval
+            .checked_div(PROBES_PER_INTERVAL)
+            .unwrap_or(self.settings.interval);
+        loop {
+            tokio::time::sleep(period).await;
+
+            // Yielded task is put at the back of run queue, so time until it's polled again is time queued tasks wait.
+            let yielded_at = Instant::now();
+            tokio::task::yield_now().await;
+            self.observe_latency(yielded_at.elapsed());
+        }
+    }
+
+    /// Records queueing latency. Server is overloaded while minimal latency of last interval exceeds target.
+    fn observe_latency(&self, latency: Duration) {
+        let mut window = self.window.lock().unwrap_or_else(PoisonError::into_inner);
+        window.min_latency = window.min_latency.min(latency);
+        if window.started_at.elapsed() >= self.settings.interval {
+            window.is_overloaded = window.min_latency > self.settings.target_latency;
+            window.started_at = Instant::now();
+            window.min_latency = Duration::MAX;
+        }
+    }
+}
+
+/// Call counted as in flight by [`LoadShedder`].
+pub(super) struct InFlightCall<'a>(&'a AtomicUsize);
+
+impl Drop for InFlightCall<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
@@ -1,60 +1,363 @@
 mod allocator;
 mod service_ref;
+mod typed_service_ref;
 
 use crate::{format::EncodingFormat, service::Service};
-use core::ops::Deref;
+use alloc::sync::Arc;
+use core::{
+    ops::Deref,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
 use derive_where::derive_where;
-use tokio::sync::{Mutex, RwLock, RwLockReadGuard};
+use tokio::{
+    sync::{Mutex, RwLock, RwLockReadGuard},
+    time::Instant,
+};
 
 pub use allocator::PrivateServiceAllocator;
 pub use service_ref::ServiceRef;
+pub use typed_service_ref::TypedServiceRef;
 
-pub(crate) struct ServiceRefLock<'a, Format: EncodingFormat>(
-    RwLockReadGuard<'a, Option<Box<dyn Service<Format>>>>,
+/// Private service resolved by [`PrivateServiceAllocator`]. Service can't be deallocated while it's held.
+pub struct ServiceRefLock<'a, Format: EncodingFormat>(
+    RwLockReadGuard<'a, Box<dyn Service<Format>>>,
 );
 
 impl<Format: EncodingFormat> Deref for ServiceRefLock<'_, Format> {
     type Target = Box<dyn Service<Format>>;
 
-    #[allow(clippy::undocumented_unsafe_blocks)]
     fn deref(&self) -> &Self::Target {
-        unsafe { self.0.as_ref().unwrap_unchecked() }
+        &self.0
+    }
+}
+
+/// Successful deallocation of private service reference.
+pub enum Deallocated<Format: EncodingFormat> {
+    /// Deallocated reference was the last one, so service is freed.
+    Freed(Box<dyn Service<Format>>),
+    /// Service is still referenced by its [duplicates][PrivateServiceAllocator::duplicate].
+    Shared,
+}
+
+/// Private service shared by all its [duplicated][PrivateServiceAllocator::duplicate] references.
+type SharedService<Format> = Arc<Box<dyn Service<Format>>>;
+
+struct PrivateServiceSlot<Format: EncodingFormat> {
+    service: Option<SharedService<Format>>,
+    /// Incremented on each deallocation, so ids of deallocated services don't address services placed in the slot later.
+    generation: u32,
+    /// Time service is freed at unless lease is renewed.
+    expires_at: Option<Instant>,
+}
+
+impl<Format: EncodingFormat> PrivateServiceSlot<Format> {
+    fn is_expired(&self, now: Instant) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    fn holds(&self, generation: u32, now: Instant) -> bool {
+        self.generation == generation && self.service.is_some() && !self.is_expired(now)
     }
 }
 
 #[derive_where(Default)]
 pub(super) struct PrivateServices<Format: EncodingFormat> {
-    services: boxcar::Vec<RwLock<Option<Box<dyn Service<Format>>>>>,
-    free_ids: Mutex<Vec<usize>>,
+    slots: boxcar::Vec<RwLock<PrivateServiceSlot<Format>>>,
+    free_slots: Mutex<Vec<usize>>,
+    live: AtomicUsize,
+}
+
+/// Packs slot index and its generation into private service id.
+fn service_id(index: usize, generation: u32) -> Option<u64> {
+    let index = u32::try_from(index).ok()?;
+    Some(u64::from(generation) << 32u8 | u64::from(index))
+}
+
+/// Unpacks slot index and generation from private service id.
+fn slot_of(service_id: u64) -> Option<(usize, u32)> {
+    let index = usize::try_from(service_id & u64::from(u32::MAX)).ok()?;
+    let generation = u32::try_from(service_id >> 32u8).ok()?;
+    Some((index, generation))
 }
 
 impl<Format: EncodingFormat> PrivateServices<Format> {
-    pub(super) async fn push(&self, service: Box<dyn Service<Format>>) -> usize {
-        if let Some(service_id) = self.free_ids.lock().await.pop() {
-            #[allow(clippy::unwrap_used)]
-            let mut service_entry = self.services.get(service_id).unwrap().write().await;
-            *service_entry = Some(service);
+    /// Places service into free slot.
+    /// Returns `None` if count of live services reached limit or there are no more addressable slots.
+    pub(super) async fn push(
+        &self,
+        service: SharedService<Format>,
+        lease: Option<Duration>,
+        limit: usize,
+    ) -> Option<u64> {
+        // Place is reserved before service is placed, so concurrent pushes don't exceed limit.
+        self.live
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |live| {
+                (live < limit).then(|| live.saturating_add(1))
+            })
+            .ok()?;
+        let expires_at = lease.and_then(|duration| Instant::now().checked_add(duration));
+
+        let id = self.place(service, expires_at).await;
+        if id.is_none() {
+            self.live.fetch_sub(1, Ordering::AcqRel);
+        }
+
+        id
+    }
+
+    async fn place(
+        &self,
+        service: SharedService<Format>,
+        expires_at: Option<Instant>,
+    ) -> Option<u64> {
+        if let Some(index) = self.free_slots.lock().await.pop() {
+            let mut slot = self.slots.get(index)?.write().await;
+            slot.service = Some(service);
+            slot.expires_at = expires_at;
+
+            return service_id(index, slot.generation);
+        }
+
+        let index = self.slots.push(RwLock::new(PrivateServiceSlot {
+            service: Some(service),
+            generation: 0,
+            expires_at,
+        }));
+        let id = service_id(index, 0);
+        if id.is_none() {
+            // Slot can't be addressed, so it's left empty forever.
+            if let Some(slot) = self.slots.get(index) {
+                slot.write().await.service = None;
+            }
+        }
+
+        id
+    }
+
+    /// Returns count of services that aren't freed yet.
+    pub(super) fn len(&self) -> usize {
+        self.live.load(Ordering::Acquire)
+    }
+
+    pub(super) async fn get(&self, id: u64) -> Option<ServiceRefLock<Format>> {
+        let (index, generation) = slot_of(id)?;
+        let slot = self.slots.get(index)?.read().await;
+        if !slot.holds(generation, Instant::now()) {
+            return None;
+        }
+
+        RwLockReadGuard::try_map(slot, |entry| entry.service.as_deref())
+            .ok()
+            .map(ServiceRefLock)
+    }
+
+    /// Returns service shared with reference of specified id.
+    pub(super) async fn share(&self, id: u64) -> Option<SharedService<Format>> {
+        let (index, generation) = slot_of(id)?;
+        let slot = self.slots.get(index)?.read().await;
+        if !slot.holds(generation, Instant::now()) {
+            return None;
+        }
+
+        slot.service.clone()
+    }
 
-            service_id
-        } else {
-            self.services.push(Some(service).into())
+    /// Extends lease of service by specified duration from now. Returns `false` if service is already freed.
+    pub(super) async fn renew(&self, id: u64, lease: Option<Duration>) -> bool {
+        let Some((index, generation)) = slot_of(id) else {
+            return false;
+        };
+        let Some(slot) = self.slots.get(index) else {
+            return false;
+        };
+
+        let mut slot = slot.write().await;
+        let now = Instant::now();
+        if !slot.holds(generation, now) {
+            return false;
         }
+
+        slot.expires_at = lease.and_then(|duration| now.checked_add(duration));
+        true
     }
 
-    pub(super) async fn get(&self, id: usize) -> Option<ServiceRefLock<Format>> {
-        let service = self.services.get(id)?.read().await;
-        service.as_ref()?;
+    /// Frees reference of specified id. Returns service that may be still referenced by its duplicates.
+    pub(super) async fn remove(&self, id: u64) -> Option<SharedService<Format>> {
+        let (index, generation) = slot_of(id)?;
+        let mut slot = self.slots.get(index)?.write().await;
+        if slot.generation != generation {
+            return None;
+        }
 
-        Some(ServiceRefLock(service))
+        let service = slot.service.take()?;
+        self.vacate(index, &mut slot).await;
+
+        Some(service)
     }
 
-    pub(super) async fn remove(&self, id: usize) -> Option<Box<dyn Service<Format>>> {
-        let mut service_entry = self.services.get(id)?.write().await;
+    /// Frees services with expired lease. Returns ids of freed services.
+    /// Services used by running calls are skipped until next time.
+    pub(super) async fn remove_expired(&self) -> Vec<u64> {
+        let now = Instant::now();
+        let mut expired = Vec::new();
+
+        for (index, slot) in &self.slots {
+            let Ok(mut slot) = slot.try_write() else {
+                continue;
+            };
+            if slot.service.is_none() || !slot.is_expired(now) {
+                continue;
+            }
 
-        if service_entry.is_some() {
-            self.free_ids.lock().await.push(id);
+            expired.extend(service_id(index, slot.generation));
+            slot.service = None;
+            self.vacate(index, &mut slot).await;
         }
 
-        service_entry.take()
+        expired
+    }
+
+    /// Returns ids of services that are still allocated.
+    pub(super) async fn ids(&self) -> Vec<u64> {
+        let mut ids = Vec::with_capacity(self.len());
+        for (index, slot) in &self.slots {
+            let slot = slot.read().await;
+            if slot.service.is_some() {
+                ids.extend(service_id(index, slot.generation));
+            }
+        }
+
+        ids
+    }
+
+    async fn vacate(&self, index: usize, slot: &mut PrivateServiceSlot<Format>) {
+        slot.expires_at = None;
+        self.live.fetch_sub(1, Ordering::AcqRel);
+
+        // Slot with exhausted generations is retired instead of wrapping to generation of already issued ids.
+        if let Some(next_generation) = slot.generation.checked_add(1) {
+            slot.generation = next_generation;
+            self.free_slots.lock().await.push(index);
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use alloc::{borrow::Cow, sync::Arc};
+    use async_trait::async_trait;
+    use core::time::Duration;
+
+    use super::{slot_of, PrivateServiceAllocator, PrivateServices, SharedService};
+    use crate::{
+        format::rkyv::RkyvFormat,
+        multipart::{MultipartReceived, MultipartSendable},
+        protocol::ServiceCallRequestError,
+        service::Service,
+    };
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    struct NoopService;
+
+    #[async_trait]
+    impl Service<RkyvFormat> for NoopService {
+        fn checksum(&self) -> Cow<'static, [u8]> {
+            Cow::Borrowed(&[])
+        }
+
+        fn is_one_way(&self, _function_id: u32) -> bool {
+            false
+        }
+
+        async fn call(
+            &self,
+            _service_allocator: Arc<PrivateServiceAllocator<RkyvFormat>>,
+            _function_id: u32,
+            _args: MultipartReceived,
+        ) -> Result<MultipartSendable, ServiceCallRequestError> {
+            Err(ServiceCallRequestError::InvalidFunctionId)
+        }
+    }
+
+    fn service() -> SharedService<RkyvFormat> {
+        Arc::new(Box::new(NoopService))
+    }
+
+    #[tokio::test]
+    async fn freed_slot_is_reused_with_next_generation() {
+        let services = PrivateServices::default();
+        let first = services.push(service(), None, usize::MAX).await.unwrap();
+        assert!(services.remove(first).await.is_some());
+
+        let second = services.push(service(), None, usize::MAX).await.unwrap();
+
+        assert_eq!(slot_of(first).unwrap().0, slot_of(second).unwrap().0);
+        assert_ne!(first, second);
+        assert!(services.get(first).await.is_none());
+        assert!(services.remove(first).await.is_none());
+        assert!(services.get(second).await.is_some());
+        assert_eq!(services.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn slot_with_exhausted_generations_is_retired() {
+        let services = PrivateServices::default();
+        let id = services.push(service(), None, usize::MAX).await.unwrap();
+        let (index, _) = slot_of(id).unwrap();
+        services.slots.get(index).unwrap().write().await.generation = u32::MAX;
+
+        let last_id = services.ids().await.pop().unwrap();
+        assert!(services.remove(last_id).await.is_some());
+        let next = services.push(service(), None, usize::MAX).await.unwrap();
+
+        assert_ne!(slot_of(next).unwrap().0, index);
+        assert!(services.get(last_id).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn services_above_limit_are_rejected() {
+        let services = PrivateServices::default();
+        let id = services.push(service(), None, 1).await.unwrap();
+
+        assert!(services.push(service(), None, 1).await.is_none());
+        services.remove(id).await;
+        assert!(services.push(service(), None, 1).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn services_with_expired_lease_are_freed() {
+        let services = PrivateServices::default();
+        let expired = services
+            .push(service(), Some(Duration::ZERO), usize::MAX)
+            .await
+            .unwrap();
+        let leased = services
+            .push(service(), Some(HOUR), usize::MAX)
+            .await
+            .unwrap();
+        let unleased = services.push(service(), None, usize::MAX).await.unwrap();
+
+        assert!(services.get(expired).await.is_none());
+        assert!(!services.renew(expired, Some(HOUR)).await);
+        assert_eq!(services.remove_expired().await, vec![expired]);
+        assert!(services.get(leased).await.is_some());
+        assert!(services.get(unleased).await.is_some());
+        assert_eq!(services.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn renewed_lease_starts_from_now() {
+        let services = PrivateServices::default();
+        let id = services
+            .push(service(), Some(HOUR), usize::MAX)
+            .await
+            .unwrap();
+
+        assert!(services.renew(id, Some(Duration::ZERO)).await);
+        assert_eq!(services.remove_expired().await, vec![id]);
+        assert!(!services.renew(id, Some(HOUR)).await);
     }
 }
@@ -1,36 +1,257 @@
-use derive_where::derive_where;
+use alloc::sync::Arc;
+use core::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+use std::sync::{Mutex, PoisonError};
+use tokio::sync::mpsc;
 
-use crate::{format::EncodingFormat, service::Service};
+use crate::{
+    format::EncodingFormat,
+    service::{Service, ServiceDescriptor},
+};
 
-use super::{service_ref::ServiceRef, PrivateServices, ServiceRefLock};
+use super::{
+    service_ref::ServiceRef, Deallocated, PrivateServices, ServiceRefLock, TypedServiceRef,
+};
+use crate::server::{ClientServices, ConnectionHooks, ConnectionInfo};
 
-/// Allocator for private service refs.
-#[derive_where(Default)]
-pub struct PrivateServiceAllocator<Format: EncodingFormat>(PrivateServices<Format>);
+/// Allocator for private service refs. Each connection has its own allocator.
+///
+/// Services may be allocated with lease, so services abandoned by client are freed after lease expires unless client renews it.
+pub struct PrivateServiceAllocator<Format: EncodingFormat> {
+    services: PrivateServices<Format>,
+    /// Random id distinguishing refs issued by this allocator from refs issued on other connections.
+    id: u64,
+    lease: Option<Duration>,
+    max_services: usize,
+    revocations: Mutex<Option<mpsc::UnboundedSender<u64>>>,
+    client_services: Option<ClientServices<Format>>,
+    connection: Option<ConnectionInfo>,
+    connection_hooks: Option<Arc<dyn ConnectionHooks>>,
+    is_authenticated: AtomicBool,
+}
 
 impl<Format: EncodingFormat> PrivateServiceAllocator<Format> {
-    /// Allocate service ref.
-    pub async fn allocate(&self, service: Box<dyn Service<Format>>) -> ServiceRef {
+    /// Sets lease of allocated services. Services are freed if lease isn't renewed by client in time.
+    #[must_use]
+    pub const fn with_lease(mut self, lease: Option<Duration>) -> Self {
+        self.lease = lease;
+        self
+    }
+
+    /// Sets limit of live services. Allocations above limit fail.
+    #[must_use]
+    pub const fn with_max_services(mut self, max_services: usize) -> Self {
+        self.max_services = max_services;
+        self
+    }
+
+    pub(crate) fn with_client_services(mut self, client_services: ClientServices<Format>) -> Self {
+        self.client_services = Some(client_services);
+        self
+    }
+
+    pub(crate) fn with_connection(
+        mut self,
+        connection: ConnectionInfo,
+        hooks: Option<Arc<dyn ConnectionHooks>>,
+    ) -> Self {
+        self.is_authenticated = connection.identity().is_some().into();
+        self.connection = Some(connection);
+        self.connection_hooks = hooks;
+        self
+    }
+
+    /// Returns connection allocator is bound to or `None` if allocator isn't bound to connection.
+    #[must_use]
+    pub const fn connection(&self) -> Option<&ConnectionInfo> {
+        self.connection.as_ref()
+    }
+
+    /// Marks client of connection as authenticated, e.g. by auth service, and notifies [connection hooks][ConnectionHooks::on_authenticated].
+    /// Hooks are notified only once per connection.
+    pub async fn mark_authenticated(&self) {
+        if self.is_authenticated.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        if let (Some(connection), Some(hooks)) = (&self.connection, &self.connection_hooks) {
+            hooks.on_authenticated(connection).await;
+        }
+    }
+
+    /// Returns caller of services hosted by client of connection or `None` if allocator isn't bound to connection.
+    #[must_use]
+    pub const fn client_services(&self) -> Option<&ClientServices<Format>> {
+        self.client_services.as_ref()
+    }
+
+    /// Allocate service ref. Returns `None` if limit of live services is reached or allocator ran out of service ids.
+    pub async fn allocate(&self, service: Box<dyn Service<Format>>) -> Option<ServiceRef> {
         let checksum = service.checksum();
-        let service_id = self.0.push(service).await;
+        let service_id = self.push(Arc::new(service)).await?;
 
-        ServiceRef {
+        Some(ServiceRef {
             service_id,
             service_checksum: checksum,
+            allocator_id: self.id,
+            origin: None,
+        })
+    }
+
+    /// Allocate service ref typed with described service.
+    /// Returns `None` without allocation if service doesn't match description or if [allocation][Self::allocate] fails.
+    pub async fn allocate_typed<S: ServiceDescriptor>(
+        &self,
+        service: Box<dyn Service<Format>>,
+    ) -> Option<TypedServiceRef<S>> {
+        if *service.checksum() != *S::CHECKSUM {
+            return None;
         }
+
+        self.allocate(service)
+            .await
+            .map(TypedServiceRef::from_untyped_unchecked)
+    }
+
+    /// Checks whether service ref was issued by this allocator.
+    pub(crate) fn has_issued(&self, service_ref: &ServiceRef) -> bool {
+        service_ref.allocator_id == self.id
+    }
+
+    /// Resolves service ref received from client (e.g. as call argument) into allocated service.
+    /// Returns `None` if ref wasn't issued on the same connection, service is already deallocated or checksum doesn't match.
+    /// Refs to deallocated services never resolve, even if their slot is reused by another service.
+    pub async fn resolve(&self, service_ref: &ServiceRef) -> Option<ServiceRefLock<Format>> {
+        if service_ref.allocator_id != self.id {
+            return None;
+        }
+
+        let service = self.services.get(service_ref.service_id).await?;
+        (*service.checksum() == *service_ref.service_checksum).then_some(service)
+    }
+
+    /// Allocates another reference to the same service, so service is freed only after all its references are deallocated.
+    /// Returns `None` if ref wasn't issued on the same connection, service is already deallocated or allocation fails.
+    pub async fn duplicate(&self, service_ref: &ServiceRef) -> Option<ServiceRef> {
+        if service_ref.allocator_id != self.id {
+            return None;
+        }
+
+        Some(ServiceRef {
+            service_id: self.duplicate_by_id(service_ref.service_id).await?,
+            ..service_ref.clone()
+        })
     }
 
-    /// Deallocate service ref.
-    pub async fn deallocate(&self, service_ref: ServiceRef) -> Option<Box<dyn Service<Format>>> {
+    /// Allocates another reference to service by service id. Returns id of new reference.
+    pub async fn duplicate_by_id(&self, id: u64) -> Option<u64> {
+        let service = self.services.share(id).await?;
+        self.push(service).await
+    }
+
+    /// Deallocate service ref. Returns `None` if ref wasn't issued on the same connection or service is already deallocated.
+    /// See [`deallocate_by_id`][Self::deallocate_by_id] for details.
+    pub async fn deallocate(&self, service_ref: ServiceRef) -> Option<Deallocated<Format>> {
+        if service_ref.allocator_id != self.id {
+            return None;
+        }
+
         self.deallocate_by_id(service_ref.service_id).await
     }
 
-    /// Deallocate service ref by service id.
-    pub async fn deallocate_by_id(&self, id: usize) -> Option<Box<dyn Service<Format>>> {
-        self.0.remove(id).await
+    /// Deallocate service ref by service id and notify client [watching revocations][crate::Client::revoked] about it.
+    /// Only this reference is revoked, duplicates of service stay allocated and keep service alive.
+    /// Returns `None` if id is stale or service is already deallocated.
+    pub async fn deallocate_by_id(&self, id: u64) -> Option<Deallocated<Format>> {
+        let service = self.release(id).await?;
+        self.notify_revoked(id);
+
+        Some(Arc::into_inner(service).map_or(Deallocated::Shared, Deallocated::Freed))
+    }
+
+    /// Frees reference by service id. Returns service that may be still referenced by its duplicates.
+    pub(crate) async fn release(&self, id: u64) -> Option<Arc<Box<dyn Service<Format>>>> {
+        self.services.remove(id).await
+    }
+
+    async fn push(&self, service: Arc<Box<dyn Service<Format>>>) -> Option<u64> {
+        if self.services.len() >= self.max_services {
+            self.remove_expired().await;
+        }
+
+        self.services
+            .push(service, self.lease, self.max_services)
+            .await
+    }
+
+    /// Renews lease of service. Returns `false` if service is already freed.
+    pub(crate) async fn renew(&self, id: u64) -> bool {
+        self.services.renew(id, self.lease).await
+    }
+
+    /// Frees services with expired lease. Returns ids of freed services.
+    pub(crate) async fn remove_expired(&self) -> Vec<u64> {
+        let expired = self.services.remove_expired().await;
+        expired.iter().for_each(|id| self.notify_revoked(*id));
+
+        expired
+    }
+
+    /// Returns receiver of ids of services freed by server itself. Only the latest receiver is notified.
+    pub(crate) fn watch_revocations(&self) -> mpsc::UnboundedReceiver<u64> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        *self
+            .revocations
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(sender);
+
+        receiver
     }
 
-    pub(crate) async fn get(&self, service_id: usize) -> Option<ServiceRefLock<Format>> {
-        self.0.get(service_id).await
+    /// Returns whether client watches revocations of services.
+    pub(crate) fn is_watching_revocations(&self) -> bool {
+        self.revocations
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+            .is_some_and(|revocations| !revocations.is_closed())
+    }
+
+    fn notify_revoked(&self, id: u64) {
+        if let Some(revocations) = &*self
+            .revocations
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+        {
+            #[allow(clippy::let_underscore_must_use)] // Client may stop watching at any time.
+            let _: Result<(), _> = revocations.send(id);
+        }
+    }
+
+    /// Returns ids of services that are still allocated.
+    pub(crate) async fn allocated_ids(&self) -> Vec<u64> {
+        self.services.ids().await
+    }
+
+    pub(crate) async fn get(&self, service_id: u64) -> Option<ServiceRefLock<Format>> {
+        self.services.get(service_id).await
+    }
+}
+
+impl<Format: EncodingFormat> Default for PrivateServiceAllocator<Format> {
+    fn default() -> Self {
+        Self {
+            services: PrivateServices::default(),
+            id: rand::random(),
+            lease: None,
+            max_services: usize::MAX,
+            revocations: Mutex::default(),
+            client_services: None,
+            connection: None,
+            connection_hooks: None,
+            is_authenticated: AtomicBool::new(false),
+        }
     }
 }
@@ -1,33 +1,95 @@
 use alloc::{borrow::Cow, sync::Arc};
+use std::io;
 
-use crate::{client::Client, format::EncodingFormat, protocol::ServiceKind, service, transport};
+use crate::{
+    client::{Client, ConnectionOrigin},
+    format::{self, Decode, DecodeZeroCopy, DecodeZeroCopyFallible, Encode},
+    protocol::{
+        PrivateServiceDeallocateRequestResult, PrivateServiceDuplicateRequestResult,
+        PrivateServiceLease, RequestKind, ServiceCallRequestResult, ServiceIdRequestResult,
+    },
+    service, transport,
+};
 
-/// Reference to private service in `PrivateServiceAllocator`
+/// Reference to private service in `PrivateServiceAllocator`.
+///
+/// Can be passed in both directions: returned by server to client and sent back by client as call argument,
+/// so server resolves it with [`PrivateServiceAllocator::resolve`][super::PrivateServiceAllocator::resolve].
+#[allow(clippy::partial_pub_fields)] // Allocator id and origin are managed by library.
+#[derive(Clone)]
 pub struct ServiceRef {
-    /// Private service id
-    pub service_id: usize,
+    /// Private service id. Includes generation of service's slot, so it never addresses services allocated after deallocation.
+    pub service_id: u64,
     /// Private service checksum
     pub service_checksum: Cow<'static, [u8]>,
+    /// Id of allocator that issued reference.
+    pub(crate) allocator_id: u64,
+    /// Client connection reference was received on.
+    pub(crate) origin: Option<ConnectionOrigin>,
 }
 
 impl ServiceRef {
-    /// Creates service client from reference and [rpc client][Client]
-    pub fn into_client<
-        ServiceClient: service::ServiceClient<Connection, Format>,
-        Connection: transport::ClientConnection,
-        Format: EncodingFormat,
-    >(
+    /// Creates service client from reference and [rpc client][Client]. Returns `None` if checksum doesn't match.
+    /// Service is [shared][Client::share_service], so it's leased while service client is alive and deallocated once it's dropped.
+    #[must_use]
+    pub fn into_client<ServiceClient, Connection, Format>(
         self,
         rpc_client: Arc<Client<Connection, Format>>,
-    ) -> Option<ServiceClient> {
+    ) -> Option<ServiceClient>
+    where
+        ServiceClient: service::ServiceClient<Connection, Format>,
+        Connection: transport::ClientConnection + 'static,
+        Format: format::ZeroCopyEncodingFormat + 'static,
+        for<'a> RequestKind<'a>: Encode<Format>,
+        for<'a> ServiceCallRequestResult<'a>: DecodeZeroCopy<
+            'a,
+            Format,
+            <ServiceCallRequestResult<'a> as DecodeZeroCopyFallible<Format>>::Error,
+        >,
+        ServiceIdRequestResult: Decode<Format>,
+        PrivateServiceDeallocateRequestResult: Decode<Format>,
+        PrivateServiceLease: Decode<Format>,
+    {
         if *ServiceClient::SERVICE_CHECKSUM != *self.service_checksum {
             return None;
         }
 
+        let service = rpc_client.private_service_handle_on(self.service_id, self.origin);
         Some(ServiceClient::new(
-            ServiceKind::Private,
-            self.service_id,
+            rpc_client.share_service(service),
             rpc_client,
         ))
     }
+
+    /// Asks server that issued reference for another independent reference to the same service,
+    /// so service is freed only after both of them are deallocated.
+    ///
+    /// # Errors
+    /// Returns an error if service is already freed, connection it was received on is lost or duplication request fails.
+    pub async fn duplicate<Connection, Format>(
+        &self,
+        rpc_client: &Client<Connection, Format>,
+    ) -> io::Result<Self>
+    where
+        Connection: transport::ClientConnection,
+        Format: format::ZeroCopyEncodingFormat,
+        for<'a> RequestKind<'a>: Encode<Format>,
+        for<'a> ServiceCallRequestResult<'a>: DecodeZeroCopy<
+            'a,
+            Format,
+            <ServiceCallRequestResult<'a> as DecodeZeroCopyFallible<Format>>::Error,
+        >,
+        ServiceIdRequestResult: Decode<Format>,
+        PrivateServiceDuplicateRequestResult: Decode<Format>,
+    {
+        let service = rpc_client.private_service_handle_on(self.service_id, self.origin);
+        let duplicate = rpc_client.duplicate_private_service(service).await?;
+
+        Ok(Self {
+            service_id: duplicate.id(),
+            service_checksum: self.service_checksum.clone(),
+            allocator_id: self.allocator_id,
+            origin: Some(duplicate.origin()),
+        })
+    }
 }
@@ -0,0 +1,101 @@
+use alloc::sync::Arc;
+use core::marker::PhantomData;
+use derive_where::derive_where;
+use std::io;
+
+use super::ServiceRef;
+use crate::{
+    client::Client,
+    format::{Decode, DecodeZeroCopy, DecodeZeroCopyFallible, Encode, ZeroCopyEncodingFormat},
+    protocol::{
+        PrivateServiceDeallocateRequestResult, PrivateServiceDuplicateRequestResult,
+        PrivateServiceLease, RequestKind, ServiceCallRequestResult, ServiceIdRequestResult,
+    },
+    service::{DescribedClient, ServiceDescriptor},
+    transport,
+};
+
+/// [`ServiceRef`] carrying type of referenced service, so client converts it into client of that service without specifying its type.
+#[derive_where(Clone)]
+pub struct TypedServiceRef<S: ServiceDescriptor> {
+    service_ref: ServiceRef,
+    _service: PhantomData<fn() -> S>,
+}
+
+impl<S: ServiceDescriptor> TypedServiceRef<S> {
+    /// Types service reference. Returns `None` if checksum of referenced service doesn't match described one.
+    #[must_use]
+    pub fn new(service_ref: ServiceRef) -> Option<Self> {
+        (*service_ref.service_checksum == *S::CHECKSUM).then_some(Self {
+            service_ref,
+            _service: PhantomData,
+        })
+    }
+
+    /// Creates client of referenced service. Returns `None` if checksum of received reference doesn't match
+    /// (e.g. client and server are built with different versions of service) or service id is invalid.
+    #[must_use]
+    pub fn into_client<Connection, Format>(
+        self,
+        rpc_client: Arc<Client<Connection, Format>>,
+    ) -> Option<S::Client>
+    where
+        Connection: transport::ClientConnection + 'static,
+        Format: ZeroCopyEncodingFormat + 'static,
+        S: DescribedClient<Connection, Format>,
+        for<'a> RequestKind<'a>: Encode<Format>,
+        for<'a> ServiceCallRequestResult<'a>: DecodeZeroCopy<
+            'a,
+            Format,
+            <ServiceCallRequestResult<'a> as DecodeZeroCopyFallible<Format>>::Error,
+        >,
+        ServiceIdRequestResult: Decode<Format>,
+        PrivateServiceDeallocateRequestResult: Decode<Format>,
+        PrivateServiceLease: Decode<Format>,
+    {
+        self.service_ref.into_client(rpc_client)
+    }
+
+    /// Asks server for another independent reference to the same service, see [`ServiceRef::duplicate`].
+    ///
+    /// # Errors
+    /// Returns an error if service is already freed, connection it was received on is lost or duplication request fails.
+    pub async fn duplicate<Connection, Format>(
+        &self,
+        rpc_client: &Client<Connection, Format>,
+    ) -> io::Result<Self>
+    where
+        Connection: transport::ClientConnection,
+        Format: ZeroCopyEncodingFormat,
+        for<'a> RequestKind<'a>: Encode<Format>,
+        for<'a> ServiceCallRequestResult<'a>: DecodeZeroCopy<
+            'a,
+            Format,
+            <ServiceCallRequestResult<'a> as DecodeZeroCopyFallible<Format>>::Error,
+        >,
+        ServiceIdRequestResult: Decode<Format>,
+        PrivateServiceDuplicateRequestResult: Decode<Format>,
+    {
+        self.service_ref
+            .duplicate(rpc_client)
+            .await
+            .map(Self::from_untyped_unchecked)
+    }
+
+    /// Returns untyped reference.
+    #[must_use]
+    pub fn into_untyped(self) -> ServiceRef {
+        self.service_ref
+    }
+
+    pub(crate) const fn as_untyped(&self) -> &ServiceRef {
+        &self.service_ref
+    }
+
+    pub(crate) const fn from_untyped_unchecked(service_ref: ServiceRef) -> Self {
+        Self {
+            service_ref,
+            _service: PhantomData,
+        }
+    }
+}
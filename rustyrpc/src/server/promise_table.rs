@@ -0,0 +1,193 @@
+use core::time::Duration;
+use std::collections::{HashMap, HashSet};
+use tokio::sync::{watch, Mutex};
+
+/// Time promise is awaited for its promising call to arrive.
+const PROMISING_CALL_TIMEOUT: Duration = Duration::from_secs(10);
+/// Max count of promises awaited before their promising calls arrive.
+const MAX_PENDING_PROMISES: usize = 1024;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PromiseState {
+    /// Promising call hasn't arrived yet
+    Pending,
+    /// Promising call is in progress
+    Promised,
+    Resolved(u64),
+    Broken,
+}
+
+/// Private services promised by promising calls of a single connection.
+///
+/// Promise may be awaited before promising call arrives because calls are sent on different streams,
+/// so such calls wait [limited time][PROMISING_CALL_TIMEOUT] for it.
+/// Resolved promises are kept until their private service is freed, broken promises are removed once resolved.
+#[derive(Default)]
+pub(super) struct PromiseTable(Mutex<HashMap<u32, watch::Sender<PromiseState>>>);
+
+impl PromiseTable {
+    async fn subscribe(&self, promise_id: u32) -> Option<watch::Receiver<PromiseState>> {
+        let mut promises = self.0.lock().await;
+        if let Some(promise) = promises.get(&promise_id) {
+            return Some(promise.subscribe());
+        }
+
+        let pending = promises
+            .values()
+            .filter(|promise| *promise.borrow() == PromiseState::Pending)
+            .count();
+        (pending < MAX_PENDING_PROMISES).then(|| {
+            promises
+                .entry(promise_id)
+                .or_insert_with(|| watch::Sender::new(PromiseState::Pending))
+                .subscribe()
+        })
+    }
+
+    /// Marks promise as promised by promising call that just arrived.
+    pub(super) async fn promise(&self, promise_id: u32) {
+        self.0
+            .lock()
+            .await
+            .entry(promise_id)
+            .or_insert_with(|| watch::Sender::new(PromiseState::Pending))
+            .send_replace(PromiseState::Promised);
+    }
+
+    /// Resolves promise with id of returned private service or breaks it if no service was returned.
+    pub(super) async fn resolve(&self, promise_id: u32, service_id: Option<u64>) {
+        let mut promises = self.0.lock().await;
+        let state = service_id.map_or(PromiseState::Broken, PromiseState::Resolved);
+        promises
+            .entry(promise_id)
+            .or_insert_with(|| watch::Sender::new(PromiseState::Pending))
+            .send_replace(state);
+
+        // Calls already waiting for broken promise are notified by their receivers.
+        if state == PromiseState::Broken {
+            promises.remove(&promise_id);
+        }
+    }
+
+    /// Waits for promising call to finish. Returns id of promised private service or `None` if promise is broken,
+    /// too many promises are pending or promising call doesn't arrive in time.
+    pub(super) async fn wait(&self, promise_id: u32) -> Option<u64> {
+        let mut promise = self.subscribe(promise_id).await?;
+        let arrived = tokio::time::timeout(
+            PROMISING_CALL_TIMEOUT,
+            promise.wait_for(|state| *state != PromiseState::Pending),
+        )
+        .await
+        .is_ok();
+        if !arrived {
+            drop(promise);
+            self.remove_abandoned(promise_id).await;
+            return None;
+        }
+
+        let resolved = promise
+            .wait_for(|state| !matches!(state, PromiseState::Pending | PromiseState::Promised))
+            .await
+            .ok()?;
+
+        match *resolved {
+            PromiseState::Resolved(service_id) => Some(service_id),
+            PromiseState::Pending | PromiseState::Promised | PromiseState::Broken => None,
+        }
+    }
+
+    /// Removes pending promise nobody waits for anymore.
+    async fn remove_abandoned(&self, promise_id: u32) {
+        let mut promises = self.0.lock().await;
+        let is_abandoned = promises.get(&promise_id).is_some_and(|promise| {
+            *promise.borrow() == PromiseState::Pending && promise.receiver_count() == 0
+        });
+        if is_abandoned {
+            promises.remove(&promise_id);
+        }
+    }
+
+    /// Removes promises resolved with specified freed private services and returns their ids.
+    pub(super) async fn remove_resolved_to(&self, service_ids: &[u64]) -> Vec<u32> {
+        let mut promises = self.0.lock().await;
+        let promise_ids: Vec<u32> = promises
+            .iter()
+            .filter(|(_, promise)| {
+                matches!(*promise.borrow(), PromiseState::Resolved(service_id) if service_ids.contains(&service_id))
+            })
+            .map(|(promise_id, _)| *promise_id)
+            .collect();
+        for promise_id in &promise_ids {
+            promises.remove(promise_id);
+        }
+
+        promise_ids
+    }
+
+    /// Removes promises resolved with private services that aren't allocated anymore.
+    pub(super) async fn retain_allocated(&self, allocated_ids: Vec<u64>) {
+        let allocated_ids: HashSet<u64> = allocated_ids.into_iter().collect();
+        self.0.lock().await.retain(|_, promise| {
+            !matches!(*promise.borrow(), PromiseState::Resolved(service_id) if !allocated_ids.contains(&service_id))
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::pin::pin;
+    use futures::poll;
+
+    use super::PromiseTable;
+
+    #[tokio::test]
+    async fn resolved_promise_returns_service_id() {
+        let promises = PromiseTable::default();
+        promises.promise(0).await;
+        promises.resolve(0, Some(7)).await;
+
+        assert_eq!(promises.wait(0).await, Some(7));
+        // Resolved promise is kept for further calls.
+        assert_eq!(promises.wait(0).await, Some(7));
+    }
+
+    #[tokio::test]
+    async fn promise_awaited_before_promising_call_is_resolved_later() {
+        let promises = PromiseTable::default();
+        let mut waiting = pin!(promises.wait(0));
+        assert!(poll!(waiting.as_mut()).is_pending());
+
+        promises.promise(0).await;
+        assert!(poll!(waiting.as_mut()).is_pending());
+        promises.resolve(0, Some(3)).await;
+
+        assert_eq!(waiting.await, Some(3));
+    }
+
+    #[tokio::test]
+    async fn broken_promise_is_removed_once_waiters_are_notified() {
+        let promises = PromiseTable::default();
+        promises.promise(0).await;
+        let mut waiting = pin!(promises.wait(0));
+        assert!(poll!(waiting.as_mut()).is_pending());
+
+        promises.resolve(0, None).await;
+
+        assert_eq!(waiting.await, None);
+        assert!(promises.0.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn promises_of_freed_services_are_removed() {
+        let promises = PromiseTable::default();
+        for (promise_id, service_id) in [(0, 10), (1, 11), (2, 12)] {
+            promises.resolve(promise_id, Some(service_id)).await;
+        }
+
+        assert_eq!(promises.remove_resolved_to(&[10]).await, vec![0]);
+        promises.retain_allocated(vec![12]).await;
+
+        let remaining: Vec<u32> = promises.0.lock().await.keys().copied().collect();
+        assert_eq!(remaining, vec![2]);
+    }
+}
@@ -0,0 +1,282 @@
+use core::time::Duration;
+use std::{
+    collections::HashMap,
+    sync::{Mutex, PoisonError},
+    time::Instant,
+};
+
+use alloc::sync::Arc;
+
+use super::Identity;
+
+/// What calls share token bucket of [`RateLimit`], see [`ServerBuilder::with_rate_limit`][super::ServerBuilder::with_rate_limit].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitKey {
+    /// Each connection has its own bucket.
+    Connection,
+    /// Each authenticated identity has its own bucket shared by all of its connections.
+    /// Clients that aren't authenticated have bucket per connection.
+    Identity,
+    /// Each service has its own bucket shared by all clients.
+    Service,
+    /// Each function of service has its own bucket shared by all clients.
+    Function,
+}
+
+/// Token bucket limit: bucket holds up to burst calls and refills with rate of calls per period.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    emission_interval: Duration,
+    burst: u32,
+}
+
+impl RateLimit {
+    /// Allows specified number of calls per period, at least one. Burst is equal to number of calls.
+    #[must_use]
+    pub fn new(calls: u32, period: Duration) -> Self {
+        let calls = calls.max(1);
+        Self {
+            emission_interval: period.checked_div(calls).unwrap_or(period),
+            burst: calls,
+        }
+    }
+
+    /// Sets number of calls that may be done at once after bucket is refilled.
+    #[must_use]
+    pub const fn with_burst(mut self, burst: u32) -> Self {
+        self.burst = burst;
+        self
+    }
+
+    /// Time bucket may be ahead of present, i.e. time it takes to refill burst except one call.
+    fn tolerance(&self) -> Duration {
+        self.emission_interval
+            .saturating_mul(self.burst.saturating_sub(1))
+    }
+}
+
+#[derive(PartialEq, Eq, Hash)]
+enum Bucket {
+    Connection(u64),
+    Identity(Arc<str>),
+    Service {
+        connection_id: Option<u64>,
+        service_id: u64,
+    },
+    Function {
+        connection_id: Option<u64>,
+        service_id: u64,
+        function_id: u32,
+    },
+}
+
+/// Call that is checked against rate limits.
+pub(super) struct Call<'a> {
+    pub(super) connection_id: u64,
+    pub(super) identity: Option<&'a Identity>,
+    pub(super) is_private: bool,
+    pub(super) service_id: u64,
+    pub(super) function_id: u32,
+}
+
+impl Call<'_> {
+    fn bucket(&self, key: RateLimitKey) -> Bucket {
+        // Ids of private services are unique only within connection.
+        let connection_id = self.is_private.then_some(self.connection_id);
+        match key {
+            RateLimitKey::Identity if let Some(identity) = self.identity => {
+                Bucket::Identity(identity.name().into())
+            }
+            RateLimitKey::Connection | RateLimitKey::Identity => {
+                Bucket::Connection(self.connection_id)
+            }
+            RateLimitKey::Service => Bucket::Service {
+                connection_id,
+                service_id: self.service_id,
+            },
+            RateLimitKey::Function => Bucket::Function {
+                connection_id,
+                service_id: self.service_id,
+                function_id: self.function_id,
+            },
+        }
+    }
+}
+
+/// Enforces rate limits with token buckets. Each bucket is tracked as theoretical arrival time of next call (GCRA).
+#[derive(Default)]
+pub(super) struct RateLimiter {
+    limits: Vec<(RateLimitKey, RateLimit)>,
+    buckets: Mutex<HashMap<(usize, Bucket), Instant>>,
+}
+
+impl RateLimiter {
+    pub(super) fn add_limit(&mut self, key: RateLimitKey, limit: RateLimit) {
+        self.limits.push((key, limit));
+    }
+
+    /// Takes token from every bucket call belongs to. If any bucket is empty, no token is taken
+    /// and time after which call would be accepted is returned.
+    pub(super) fn acquire(&self, call: &Call<'_>) -> Result<(), Duration> {
+        if self.limits.is_empty() {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut updates = Vec::with_capacity(self.limits.len());
+        let mut retry_after = Duration::ZERO;
+        for (index, (key, limit)) in self.limits.iter().enumerate() {
+            let bucket = (index, call.bucket(*key));
+            let arrival = buckets
+                .get(&bucket)
+                .map_or(now, |arrival| now.max(*arrival));
+            let ahead = arrival.saturating_duration_since(now);
+            if let Some(wait) = ahead.checked_sub(limit.tolerance())
+                && !wait.is_zero()
+            {
+                retry_after = retry_after.max(wait);
+            } else {
+                let next_arrival = arrival
+                    .checked_add(limit.emission_interval)
+                    .unwrap_or(arrival);
+                updates.push((bucket, next_arrival));
+            }
+        }
+
+        if !retry_after.is_zero() {
+            return Err(retry_after);
+        }
+        buckets.extend(updates);
+        Ok(())
+    }
+
+    /// Drops buckets of closed connection.
+    pub(super) fn forget_connection(&self, connection_id: u64) {
+        if self.limits.is_empty() {
+            return;
+        }
+
+        self.buckets
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|(_, bucket), _| match bucket {
+                Bucket::Connection(id)
+                | Bucket::Service {
+                    connection_id: Some(id),
+                    ..
+                }
+                | Bucket::Function {
+                    connection_id: Some(id),
+                    ..
+                } => *id != connection_id,
+                Bucket::Identity(_) | Bucket::Service { .. } | Bucket::Function { .. } => true,
+            });
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use core::time::Duration;
+
+    use super::{Call, RateLimit, RateLimitKey, RateLimiter};
+    use crate::server::Identity;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    fn call(connection_id: u64, identity: Option<&Identity>, function_id: u32) -> Call<'_> {
+        Call {
+            connection_id,
+            identity,
+            is_private: false,
+            service_id: 0,
+            function_id,
+        }
+    }
+
+    fn limiter(key: RateLimitKey, limit: RateLimit) -> RateLimiter {
+        let mut limiter = RateLimiter::default();
+        limiter.add_limit(key, limit);
+        limiter
+    }
+
+    #[test]
+    fn calls_without_limits_are_always_accepted() {
+        let limiter = RateLimiter::default();
+
+        for _ in 0..100u32 {
+            assert_eq!(limiter.acquire(&call(0, None, 0)), Ok(()));
+        }
+    }
+
+    #[test]
+    fn burst_is_accepted_and_next_call_waits_for_refill() {
+        let limiter = limiter(RateLimitKey::Connection, RateLimit::new(3, HOUR));
+
+        for _ in 0..3u32 {
+            assert_eq!(limiter.acquire(&call(0, None, 0)), Ok(()));
+        }
+        let retry_after = limiter.acquire(&call(0, None, 0)).unwrap_err();
+
+        // One call is refilled every 20 minutes.
+        assert!(retry_after <= HOUR / 3);
+        assert!(retry_after > HOUR / 3 - Duration::from_secs(60));
+    }
+
+    #[test]
+    fn burst_may_differ_from_rate() {
+        let limiter = limiter(
+            RateLimitKey::Connection,
+            RateLimit::new(10, HOUR).with_burst(1),
+        );
+
+        assert_eq!(limiter.acquire(&call(0, None, 0)), Ok(()));
+        assert!(limiter.acquire(&call(0, None, 0)).is_err());
+    }
+
+    #[test]
+    fn buckets_are_separated_by_key() {
+        let connections = limiter(RateLimitKey::Connection, RateLimit::new(1, HOUR));
+        assert_eq!(connections.acquire(&call(0, None, 0)), Ok(()));
+        assert_eq!(connections.acquire(&call(1, None, 0)), Ok(()));
+        assert!(connections.acquire(&call(0, None, 1)).is_err());
+
+        let functions = limiter(RateLimitKey::Function, RateLimit::new(1, HOUR));
+        assert_eq!(functions.acquire(&call(0, None, 0)), Ok(()));
+        assert_eq!(functions.acquire(&call(0, None, 1)), Ok(()));
+        assert!(functions.acquire(&call(1, None, 0)).is_err());
+    }
+
+    #[test]
+    fn identity_bucket_is_shared_by_its_connections() {
+        let limiter = limiter(RateLimitKey::Identity, RateLimit::new(1, HOUR));
+        let identity = Identity::new("admin");
+
+        assert_eq!(limiter.acquire(&call(0, Some(&identity), 0)), Ok(()));
+        assert!(limiter.acquire(&call(1, Some(&identity), 0)).is_err());
+        // Anonymous clients are limited per connection.
+        assert_eq!(limiter.acquire(&call(2, None, 0)), Ok(()));
+        assert_eq!(limiter.acquire(&call(3, None, 0)), Ok(()));
+    }
+
+    #[test]
+    fn rejected_call_takes_no_token_from_other_buckets() {
+        let mut limiter = limiter(RateLimitKey::Function, RateLimit::new(2, HOUR));
+        limiter.add_limit(RateLimitKey::Connection, RateLimit::new(1, HOUR));
+
+        assert_eq!(limiter.acquire(&call(0, None, 0)), Ok(()));
+        assert!(limiter.acquire(&call(0, None, 0)).is_err());
+        // Function bucket still has a token left for another connection.
+        assert_eq!(limiter.acquire(&call(1, None, 0)), Ok(()));
+    }
+
+    #[test]
+    fn buckets_of_forgotten_connection_are_refilled() {
+        let limiter = limiter(RateLimitKey::Connection, RateLimit::new(1, HOUR));
+
+        assert_eq!(limiter.acquire(&call(0, None, 0)), Ok(()));
+        limiter.forget_connection(0);
+        assert_eq!(limiter.acquire(&call(0, None, 0)), Ok(()));
+    }
+}
@@ -0,0 +1,199 @@
+use alloc::sync::Arc;
+use core::{
+    marker::PhantomData,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+};
+use std::{
+    collections::HashMap,
+    io,
+    sync::{Mutex, PoisonError},
+};
+use tokio::sync::mpsc;
+
+use crate::{
+    format::{Encode, EncodingFormat},
+    protocol::TopicNotFoundError,
+};
+
+const DEFAULT_SUBSCRIBER_BUFFER_SIZE: usize = 64;
+
+/// Policy applied to subscriber once its buffer of undelivered events is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SlowSubscriberPolicy {
+    /// New events are dropped for subscriber until it catches up.
+    #[default]
+    Drop,
+    /// Events are buffered without limit.
+    Buffer,
+    /// Subscription is closed, so client has to subscribe again.
+    Disconnect,
+}
+
+/// Topics declared by server. Services publish events to topics and each event is delivered to all subscribed clients.
+///
+/// Shared by server and services publishing events, see [`ServerBuilder::with_topics`][super::ServerBuilder::with_topics].
+pub struct Topics<Format: EncodingFormat> {
+    topics: HashMap<Box<str>, Arc<Topic>>,
+    subscriber_buffer_size: usize,
+    slow_subscriber_policy: SlowSubscriberPolicy,
+    _format: PhantomData<fn() -> Format>,
+}
+
+impl<Format: EncodingFormat> Default for Topics<Format> {
+    fn default() -> Self {
+        Self {
+            topics: HashMap::new(),
+            subscriber_buffer_size: DEFAULT_SUBSCRIBER_BUFFER_SIZE,
+            slow_subscriber_policy: SlowSubscriberPolicy::default(),
+            _format: PhantomData,
+        }
+    }
+}
+
+impl<Format: EncodingFormat> Topics<Format> {
+    /// Declares topic, so events can be published to it.
+    #[must_use]
+    pub fn with_topic(mut self, name: &str) -> Self {
+        self.topics.insert(name.into(), Arc::default());
+        self
+    }
+
+    /// Sets count of undelivered events after which subscriber is considered slow.
+    #[must_use]
+    pub const fn with_subscriber_buffer_size(mut self, size: usize) -> Self {
+        self.subscriber_buffer_size = size;
+        self
+    }
+
+    /// Sets policy applied to slow subscribers.
+    #[must_use]
+    pub const fn with_slow_subscriber_policy(mut self, policy: SlowSubscriberPolicy) -> Self {
+        self.slow_subscriber_policy = policy;
+        self
+    }
+
+    /// Publishes event to all subscribers of topic. Returns count of subscribers event is queued for.
+    ///
+    /// # Errors
+    /// Returns an error if topic isn't declared or event can't be encoded.
+    pub fn publish<Event: Encode<Format>>(&self, topic: &str, event: &Event) -> io::Result<usize> {
+        let topic = self.topics.get(topic).ok_or(TopicNotFoundError)?;
+        let event = event
+            .encode()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        Ok(topic.publish(
+            &event.into(),
+            self.subscriber_buffer_size,
+            self.slow_subscriber_policy,
+        ))
+    }
+
+    /// Returns count of subscribers of topic or `None` if topic isn't declared.
+    #[must_use]
+    pub fn subscribers(&self, topic: &str) -> Option<usize> {
+        self.topics
+            .get(topic)
+            .map(|declared_topic| declared_topic.subscribers().len())
+    }
+
+    pub(crate) fn subscribe(&self, topic: &str) -> Result<TopicSubscription, TopicNotFoundError> {
+        self.topics
+            .get(topic)
+            .map(Topic::subscribe)
+            .ok_or(TopicNotFoundError)
+    }
+}
+
+struct Subscriber {
+    id: u64,
+    events: mpsc::UnboundedSender<Arc<[u8]>>,
+    /// Count of events sent but not received by subscription yet.
+    pending: Arc<AtomicUsize>,
+}
+
+#[derive(Default)]
+struct Topic {
+    subscribers: Mutex<Vec<Subscriber>>,
+    next_subscriber_id: AtomicU64,
+}
+
+impl Topic {
+    fn subscribers(&self) -> std::sync::MutexGuard<'_, Vec<Subscriber>> {
+        self.subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn publish(
+        &self,
+        event: &Arc<[u8]>,
+        buffer_size: usize,
+        slow_subscriber_policy: SlowSubscriberPolicy,
+    ) -> usize {
+        let mut queued = 0usize;
+        self.subscribers().retain(|subscriber| {
+            let is_slow = subscriber.pending.load(Ordering::Acquire) >= buffer_size;
+            match slow_subscriber_policy {
+                SlowSubscriberPolicy::Drop if is_slow => return true,
+                SlowSubscriberPolicy::Disconnect if is_slow => return false,
+                SlowSubscriberPolicy::Drop
+                | SlowSubscriberPolicy::Buffer
+                | SlowSubscriberPolicy::Disconnect => {}
+            }
+
+            subscriber.pending.fetch_add(1, Ordering::AcqRel);
+            let is_sent = subscriber.events.send(Arc::clone(event)).is_ok();
+            if is_sent {
+                queued = queued.saturating_add(1);
+            }
+            is_sent
+        });
+
+        queued
+    }
+
+    fn subscribe(self: &Arc<Self>) -> TopicSubscription {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let id = self.next_subscriber_id.fetch_add(1, Ordering::Relaxed);
+        let pending = Arc::default();
+
+        self.subscribers().push(Subscriber {
+            id,
+            events: sender,
+            pending: Arc::clone(&pending),
+        });
+
+        TopicSubscription {
+            topic: Arc::clone(self),
+            id,
+            events: receiver,
+            pending,
+        }
+    }
+}
+
+/// Subscription of single client stream, unsubscribed once dropped.
+pub(crate) struct TopicSubscription {
+    topic: Arc<Topic>,
+    id: u64,
+    events: mpsc::UnboundedReceiver<Arc<[u8]>>,
+    pending: Arc<AtomicUsize>,
+}
+
+impl TopicSubscription {
+    /// Returns next published event or `None` if subscription is closed by server.
+    pub(crate) async fn next(&mut self) -> Option<Arc<[u8]>> {
+        let event = self.events.recv().await?;
+        self.pending.fetch_sub(1, Ordering::AcqRel);
+        Some(event)
+    }
+}
+
+impl Drop for TopicSubscription {
+    fn drop(&mut self) {
+        self.topic
+            .subscribers()
+            .retain(|subscriber| subscriber.id != self.id);
+    }
+}
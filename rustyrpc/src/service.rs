@@ -3,10 +3,10 @@ use alloc::{borrow::Cow, sync::Arc};
 use async_trait::async_trait;
 
 use crate::{
-    client::Client,
+    client::{Client, SharedService},
     format::EncodingFormat,
     multipart::{MultipartReceived, MultipartSendable},
-    protocol::{ServiceCallRequestError, ServiceKind},
+    protocol::ServiceCallRequestError,
     server::PrivateServiceAllocator,
     transport,
 };
@@ -21,12 +21,25 @@ where
     /// Service checksum that client corresponds to
     const SERVICE_CHECKSUM: &'static [u8];
 
-    /// Create new service client from service kind, id and RPC client
-    fn new(
-        service_kind: ServiceKind,
-        service_id: usize,
-        rpc_client: Arc<Client<Connection, Format>>,
-    ) -> Self;
+    /// Create new service client from shared service and RPC client.
+    /// Lease of private service is renewed while service client holds shared service.
+    fn new(service: SharedService, rpc_client: Arc<Client<Connection, Format>>) -> Self;
+}
+
+/// Type level description of service, used by [`TypedServiceRef`][crate::server::TypedServiceRef] to carry type of referenced service.
+pub trait ServiceDescriptor: 'static {
+    /// Service name.
+    const NAME: &'static str;
+    /// Service checksum.
+    const CHECKSUM: &'static [u8];
+}
+
+/// Links described service with its client for specific connection and encoding format.
+pub trait DescribedClient<Connection: transport::ClientConnection, Format: EncodingFormat>:
+    ServiceDescriptor
+{
+    /// Client of described service.
+    type Client: ServiceClient<Connection, Format>;
 }
 
 /// Service wrapper that wraps implementor of specific service trait like `AuthService` to implement [`Service`].
@@ -49,6 +62,11 @@ pub trait Service<Format: EncodingFormat>: Send + Sync {
     /// Returns checksum of service.
     fn checksum(&self) -> Cow<'static, [u8]>;
 
+    /// Returns whether function is one-way, so it can be called without sending response to the client.
+    fn is_one_way(&self, _function_id: u32) -> bool {
+        false
+    }
+
     /// Call service.
     async fn call(
         &self,
@@ -6,9 +6,12 @@ use core::future::Future;
 use extension_traits::extension;
 use std::io;
 
+mod peer_identity;
 /// Provides transport implementation via QUIC protocol.
 pub mod quic;
 
+pub use peer_identity::PeerIdentity;
+
 /// Transport specific connection's stream.
 pub trait Stream: Send {
     /// Send a message on the stream.
@@ -58,6 +61,8 @@ impl<T: Stream> T {
 pub trait Connection: Send + 'static {
     /// Close connection.
     fn close(self) -> impl Future<Output = io::Result<()>> + Send;
+    /// Returns future that resolves with the reason of connection close once it's closed by any side or lost.
+    fn closed(&self) -> impl Future<Output = io::Error> + Send + 'static;
 }
 
 /// Transport specific connection on client side.
@@ -67,6 +72,24 @@ pub trait ClientConnection: Connection {
 
     /// Create new stream and notify other side of connection about it.
     fn new_stream(&mut self) -> impl Future<Output = io::Result<Self::Stream>> + Send;
+
+    /// Create new stream that isn't shared with other requests, used for long-lived exchanges.
+    fn new_dedicated_stream(&mut self) -> impl Future<Output = io::Result<Self::Stream>> + Send;
+
+    /// Acceptor of streams created by server.
+    type StreamAcceptor: StreamAcceptor;
+
+    /// Returns acceptor of streams created by server to call services hosted by client.
+    fn stream_acceptor(&self) -> Self::StreamAcceptor;
+}
+
+/// Transport specific establisher of client side connections. Used to connect again after connection loss.
+pub trait Connector: Send + Sync + 'static {
+    /// Connection produced by connector.
+    type Connection: ClientConnection;
+
+    /// Establishes a new connection.
+    fn connect(&self) -> impl Future<Output = io::Result<Self::Connection>> + Send;
 }
 
 /// Transport specific connection on server side.
@@ -76,6 +99,33 @@ pub trait ServerConnection: Connection {
 
     /// Accept new stream created by other side of connection.
     fn accept_stream(&mut self) -> impl Future<Output = io::Result<Self::Stream>> + Send;
+
+    /// Opener of streams toward client.
+    type StreamOpener: StreamOpener<Stream = Self::Stream>;
+
+    /// Returns opener of streams toward client, used to call services hosted by client independently of accepting streams.
+    fn stream_opener(&self) -> Self::StreamOpener;
+
+    /// Returns identity of client verified by transport or `None` if client isn't verified, e.g. didn't present certificate.
+    fn peer_identity(&self) -> Option<PeerIdentity>;
+}
+
+/// Opens streams toward other side of connection.
+pub trait StreamOpener: Send + Sync + 'static {
+    /// Stream produced by opener.
+    type Stream: Stream + 'static;
+
+    /// Create new stream and notify other side of connection about it.
+    fn open_stream(&self) -> impl Future<Output = io::Result<Self::Stream>> + Send;
+}
+
+/// Accepts streams created by other side of connection.
+pub trait StreamAcceptor: Send + 'static {
+    /// Stream produced by acceptor.
+    type Stream: Stream + 'static;
+
+    /// Accept new stream created by other side of connection.
+    fn accept_stream(&mut self) -> impl Future<Output = io::Result<Self::Stream>> + Send;
 }
 
 /// Transport specific incoming connections listener like a [`TcpListener`][`std::net::TcpListener`] or others
@@ -85,4 +135,10 @@ pub trait ConnectionListener: Send {
 
     /// Accepts a new connection
     fn accept_connection(&mut self) -> impl Future<Output = io::Result<Self::Connection>>;
+
+    /// Limits count of streams each connection may have open at once.
+    ///
+    /// # Errors
+    /// Returns an error if limit can't be applied to listener.
+    fn set_max_concurrent_streams(&mut self, max_streams: u32) -> io::Result<()>;
 }
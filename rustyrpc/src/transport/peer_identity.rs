@@ -0,0 +1,46 @@
+mod x509;
+
+/// Identity of peer verified by transport, e.g. by client certificate of mutual TLS.
+#[derive(Debug, Clone)]
+pub struct PeerIdentity {
+    certificate_chain: Vec<Vec<u8>>,
+    subject: Option<String>,
+    subject_alt_names: Vec<String>,
+}
+
+impl PeerIdentity {
+    /// Creates identity from DER encoded certificate chain verified by transport, end-entity certificate first.
+    /// Subject and subject alternative names are derived from end-entity certificate.
+    #[must_use]
+    pub fn from_certificate_chain(certificate_chain: Vec<Vec<u8>>) -> Self {
+        let certificate = certificate_chain
+            .first()
+            .and_then(|certificate| x509::Certificate::parse(certificate))
+            .unwrap_or_default();
+
+        Self {
+            certificate_chain,
+            subject: certificate.subject,
+            subject_alt_names: certificate.subject_alt_names,
+        }
+    }
+
+    /// Returns DER encoded certificate chain presented by peer, end-entity certificate first.
+    #[must_use]
+    pub fn certificate_chain(&self) -> &[Vec<u8>] {
+        &self.certificate_chain
+    }
+
+    /// Returns subject of end-entity certificate like `CN=client,O=Example` or `None` if it can't be derived.
+    /// Subject is rendered as described in RFC 4514 with every attribute kept, so it can't be spoofed by attribute value.
+    #[must_use]
+    pub fn subject(&self) -> Option<&str> {
+        self.subject.as_deref()
+    }
+
+    /// Returns DNS names, emails, URIs and IP addresses from subject alternative names of end-entity certificate.
+    #[must_use]
+    pub fn subject_alt_names(&self) -> &[String] {
+        &self.subject_alt_names
+    }
+}
@@ -0,0 +1,185 @@
+use core::{
+    fmt::Write,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+};
+use x509_parser::{
+    der_parser::{asn1_rs::ToDer, Oid},
+    extensions::GeneralName,
+    oid_registry::{
+        OID_DOMAIN_COMPONENT, OID_USERID, OID_X509_COMMON_NAME, OID_X509_COUNTRY_NAME,
+        OID_X509_LOCALITY_NAME, OID_X509_ORGANIZATIONAL_UNIT, OID_X509_ORGANIZATION_NAME,
+        OID_X509_STATE_OR_PROVINCE_NAME, OID_X509_STREET_ADDRESS,
+    },
+    x509::{AttributeTypeAndValue, X509Name},
+};
+
+/// Attributes with short names defined by RFC 4514.
+const ATTRIBUTES: &[(Oid<'static>, &str)] = &[
+    (OID_X509_COMMON_NAME, "CN"),
+    (OID_X509_LOCALITY_NAME, "L"),
+    (OID_X509_STATE_OR_PROVINCE_NAME, "ST"),
+    (OID_X509_ORGANIZATION_NAME, "O"),
+    (OID_X509_ORGANIZATIONAL_UNIT, "OU"),
+    (OID_X509_COUNTRY_NAME, "C"),
+    (OID_X509_STREET_ADDRESS, "STREET"),
+    (OID_DOMAIN_COMPONENT, "DC"),
+    (OID_USERID, "UID"),
+];
+
+/// Fields of X.509 certificate used to derive peer identity.
+#[derive(Default)]
+pub(super) struct Certificate {
+    pub(super) subject: Option<String>,
+    pub(super) subject_alt_names: Vec<String>,
+}
+
+impl Certificate {
+    /// Parses DER encoded certificate. Returns `None` if certificate is malformed.
+    pub(super) fn parse(certificate: &[u8]) -> Option<Self> {
+        let (_, certificate) = x509_parser::parse_x509_certificate(certificate).ok()?;
+        let subject = render_name(certificate.subject())?;
+        let subject_alt_names = certificate
+            .subject_alternative_name()
+            .ok()?
+            .map(|extension| {
+                extension
+                    .value
+                    .general_names
+                    .iter()
+                    .filter_map(render_general_name)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Some(Self {
+            subject: (!subject.is_empty()).then_some(subject),
+            subject_alt_names,
+        })
+    }
+}
+
+/// Renders name as described in RFC 4514: relative names in reverse order, attributes without short name
+/// or with value that isn't a string as dotted OID and hex encoded DER value.
+/// Returns `None` if value can't be encoded.
+fn render_name(name: &X509Name<'_>) -> Option<String> {
+    let mut relative_names = Vec::new();
+    for relative_name in name.iter_rdn() {
+        let attributes: Vec<String> = relative_name
+            .iter()
+            .map(render_attribute)
+            .collect::<Option<_>>()?;
+        relative_names.push(attributes.join("+"));
+    }
+    relative_names.reverse();
+
+    Some(relative_names.join(","))
+}
+
+fn render_attribute(attribute: &AttributeTypeAndValue<'_>) -> Option<String> {
+    let short_name = ATTRIBUTES
+        .iter()
+        .find(|(oid, _)| oid == attribute.attr_type())
+        .map(|(_, short_name)| *short_name);
+    if let Some(short_name) = short_name
+        && let Ok(value) = attribute.as_str()
+    {
+        return Some(format!("{short_name}={}", escape_attribute_value(value)));
+    }
+
+    let value = attribute.attr_value().to_der_vec().ok()?;
+    let mut rendered = format!("{}=#", attribute.attr_type().to_id_string());
+    for byte in value {
+        write!(rendered, "{byte:02x}").ok()?;
+    }
+    Some(rendered)
+}
+
+fn escape_attribute_value(value: &str) -> String {
+    let last_index = value.chars().count().saturating_sub(1);
+    let mut escaped = String::with_capacity(value.len());
+    for (index, character) in value.chars().enumerate() {
+        match character {
+            '"' | '+' | ',' | ';' | '<' | '>' | '\\' => escaped.push('\\'),
+            '#' | ' ' if index == 0 => escaped.push('\\'),
+            ' ' if index == last_index => escaped.push('\\'),
+            '\0' => {
+                escaped.push_str("\\00");
+                continue;
+            }
+            _ => {}
+        }
+        escaped.push(character);
+    }
+
+    escaped
+}
+
+fn render_general_name(name: &GeneralName<'_>) -> Option<String> {
+    match *name {
+        GeneralName::RFC822Name(name) | GeneralName::DNSName(name) | GeneralName::URI(name) => {
+            Some(name.to_owned())
+        }
+        GeneralName::IPAddress(address) => render_ip_address(address),
+        GeneralName::OtherName(..)
+        | GeneralName::X400Address(_)
+        | GeneralName::DirectoryName(_)
+        | GeneralName::EDIPartyName(_)
+        | GeneralName::RegisteredID(_) => None,
+    }
+}
+
+fn render_ip_address(address: &[u8]) -> Option<String> {
+    let address = if let Ok(octets) = <[u8; 4]>::try_from(address) {
+        IpAddr::from(Ipv4Addr::from(octets))
+    } else {
+        IpAddr::from(Ipv6Addr::from(<[u8; 16]>::try_from(address).ok()?))
+    };
+    Some(address.to_string())
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use rcgen::{CertificateParams, DistinguishedName, DnType};
+
+    use super::Certificate;
+
+    fn subject_of(distinguished_name: DistinguishedName) -> Option<String> {
+        let mut params = CertificateParams::new(Vec::new());
+        params.distinguished_name = distinguished_name;
+        let certificate = rcgen::Certificate::from_params(params)
+            .unwrap()
+            .serialize_der()
+            .unwrap();
+
+        Certificate::parse(&certificate).unwrap().subject
+    }
+
+    #[test]
+    fn subject_is_rendered_last_first_with_escaped_values() {
+        let mut distinguished_name = DistinguishedName::new();
+        distinguished_name.push(DnType::CommonName, "evil, O=Admins");
+        distinguished_name.push(DnType::OrganizationName, " #Example ");
+
+        assert_eq!(
+            subject_of(distinguished_name).as_deref(),
+            Some(r"O=\ #Example\ ,CN=evil\, O=Admins")
+        );
+    }
+
+    #[test]
+    fn attributes_without_short_name_are_rendered_as_hex() {
+        let mut distinguished_name = DistinguishedName::new();
+        distinguished_name.push(DnType::CommonName, "client");
+        distinguished_name.push(
+            DnType::CustomDnType(vec![0, 9, 2342, 19_200_300, 100, 1, 25]),
+            "example",
+        );
+        distinguished_name.push(DnType::CustomDnType(vec![1, 2, 3, 4]), "x");
+
+        assert_eq!(
+            subject_of(distinguished_name).as_deref(),
+            Some("1.2.3.4=#0c0178,DC=example,CN=client")
+        );
+    }
+}
@@ -1,6 +1,8 @@
 mod connection;
+mod connector;
 mod listener;
 mod stream;
 
 pub use connection::*;
-pub use listener::ConnectionListener;
+pub use connector::Connector;
+pub use listener::{ClientVerification, ConnectionListener};
@@ -2,5 +2,5 @@ mod client;
 mod server;
 mod stream_pool;
 
-pub use client::ClientConnection;
-pub use server::ServerConnection;
+pub use client::{ClientConnection, StreamAcceptor};
+pub use server::{ServerConnection, StreamOpener};
@@ -1,10 +1,14 @@
 use alloc::sync::Arc;
-use core::net::SocketAddr;
+use core::{future::Future, net::SocketAddr};
 use std::io;
 
-use quinn::{ClientConfig, Endpoint, VarInt};
+use quinn::{ClientConfig, VarInt};
 
-use crate::transport;
+use crate::transport::{
+    self,
+    quic::{stream::Stream, Connector},
+    Connector as _,
+};
 
 use super::stream_pool::{PooledStream, StreamPool};
 
@@ -35,15 +39,15 @@ impl ClientConnection {
         server_name: &str,
         stream_pool_size: usize,
     ) -> io::Result<Self> {
-        let mut endpoint = Endpoint::client(local_address)?;
-        endpoint.set_default_client_config(client_config);
-
-        let connection = endpoint
-            .connect(address, server_name)
-            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?
-            .await?;
-
-        Ok(Self::new(connection, stream_pool_size))
+        Connector::new(
+            client_config,
+            local_address,
+            address,
+            server_name,
+            stream_pool_size,
+        )?
+        .connect()
+        .await
     }
 }
 
@@ -52,6 +56,11 @@ impl transport::Connection for ClientConnection {
         self.connection.close(VarInt::from_u32(0), b"");
         Ok(())
     }
+
+    fn closed(&self) -> impl Future<Output = io::Error> + Send + 'static {
+        let connection = self.connection.clone();
+        async move { connection.closed().await.into() }
+    }
 }
 
 impl transport::ClientConnection for ClientConnection {
@@ -60,4 +69,25 @@ impl transport::ClientConnection for ClientConnection {
     async fn new_stream(&mut self) -> io::Result<Self::Stream> {
         Ok(self.stream_pool.get().await?)
     }
+
+    async fn new_dedicated_stream(&mut self) -> io::Result<Self::Stream> {
+        Ok(self.stream_pool.get_dedicated().await?)
+    }
+
+    type StreamAcceptor = StreamAcceptor;
+
+    fn stream_acceptor(&self) -> Self::StreamAcceptor {
+        StreamAcceptor(self.connection.clone())
+    }
+}
+
+/// Accepts QUIC streams created by server.
+pub struct StreamAcceptor(quinn::Connection);
+
+impl transport::StreamAcceptor for StreamAcceptor {
+    type Stream = Stream;
+
+    async fn accept_stream(&mut self) -> io::Result<Self::Stream> {
+        Ok(self.0.accept_bi().await?.into())
+    }
 }
@@ -1,8 +1,9 @@
+use core::future::Future;
 use std::io;
 
 use quinn::VarInt;
 
-use crate::transport::{self, quic::stream::Stream};
+use crate::transport::{self, quic::stream::Stream, PeerIdentity};
 
 /// Connection via QUIC protocol used on server side.
 pub struct ServerConnection(quinn::Connection);
@@ -12,6 +13,11 @@ impl transport::Connection for ServerConnection {
         self.0.close(VarInt::from_u32(0), b"");
         Ok(())
     }
+
+    fn closed(&self) -> impl Future<Output = io::Error> + Send + 'static {
+        let connection = self.0.clone();
+        async move { connection.closed().await.into() }
+    }
 }
 
 impl transport::ServerConnection for ServerConnection {
@@ -20,6 +26,37 @@ impl transport::ServerConnection for ServerConnection {
     async fn accept_stream(&mut self) -> io::Result<Self::Stream> {
         Ok(self.0.accept_bi().await?.into())
     }
+
+    type StreamOpener = StreamOpener;
+
+    fn stream_opener(&self) -> Self::StreamOpener {
+        StreamOpener(self.0.clone())
+    }
+
+    fn peer_identity(&self) -> Option<PeerIdentity> {
+        let certificate_chain = self
+            .0
+            .peer_identity()?
+            .downcast::<Vec<rustls::Certificate>>()
+            .ok()?;
+        Some(PeerIdentity::from_certificate_chain(
+            certificate_chain
+                .into_iter()
+                .map(|certificate| certificate.0)
+                .collect(),
+        ))
+    }
+}
+
+/// Opens QUIC streams toward client.
+pub struct StreamOpener(quinn::Connection);
+
+impl transport::StreamOpener for StreamOpener {
+    type Stream = Stream;
+
+    async fn open_stream(&self) -> io::Result<Self::Stream> {
+        Ok(self.0.open_bi().await?.into())
+    }
 }
 
 impl From<quinn::Connection> for ServerConnection {
@@ -17,13 +17,24 @@ use crate::{
 pub struct PooledStream {
     inner: MaybeUninit<Stream>,
     pool: Arc<StreamPool>,
+    // Dedicated streams are created outside of pool and never return to it.
+    dedicated: bool,
+    // Set while operation is in progress and left set if it fails or gets cancelled, so stream with unread or partially written data never returns to pool.
+    broken: bool,
 }
 
 impl Drop for PooledStream {
     #[allow(clippy::undocumented_unsafe_blocks, clippy::let_underscore_must_use)]
     fn drop(&mut self) {
         let stream = unsafe { self.inner.assume_init_read() };
-        let _: Result<(), SendError<Stream>> = self.pool.stream_sender.send(stream);
+        if self.dedicated {
+            drop(stream);
+        } else if self.broken {
+            drop(stream);
+            self.pool.size.add_permits(1);
+        } else {
+            let _: Result<(), SendError<Stream>> = self.pool.stream_sender.send(stream);
+        }
     }
 }
 
@@ -45,22 +56,40 @@ impl DerefMut for PooledStream {
 
 impl transport::Stream for PooledStream {
     async fn send(&mut self, message: Vec<u8>) -> io::Result<()> {
-        self.deref_mut().send(message).await
+        self.broken = true;
+        self.deref_mut().send(message).await?;
+        self.broken = false;
+        Ok(())
     }
     async fn send_not_prefixed(&mut self, message: Vec<u8>) -> io::Result<()> {
-        self.deref_mut().send_not_prefixed(message).await
+        self.broken = true;
+        self.deref_mut().send_not_prefixed(message).await?;
+        self.broken = false;
+        Ok(())
     }
     async fn send_multipart(&mut self, multipart: &MultipartSendable) -> io::Result<()> {
-        self.deref_mut().send_multipart(multipart).await
+        self.broken = true;
+        self.deref_mut().send_multipart(multipart).await?;
+        self.broken = false;
+        Ok(())
     }
     async fn receive(&mut self) -> io::Result<Vec<u8>> {
-        self.deref_mut().receive().await
+        self.broken = true;
+        let message = self.deref_mut().receive().await?;
+        self.broken = false;
+        Ok(message)
     }
     async fn receive_not_prefixed(&mut self, buffer: &mut [u8]) -> io::Result<()> {
-        self.deref_mut().receive_not_prefixed(buffer).await
+        self.broken = true;
+        self.deref_mut().receive_not_prefixed(buffer).await?;
+        self.broken = false;
+        Ok(())
     }
     async fn flush(&mut self) -> io::Result<()> {
-        self.deref_mut().flush().await
+        self.broken = true;
+        self.deref_mut().flush().await?;
+        self.broken = false;
+        Ok(())
     }
 }
 
@@ -84,10 +113,19 @@ impl StreamPool {
 
     async fn try_create_stream(&self) -> Result<Result<Stream, ConnectionError>, TryAcquireError> {
         self.size.try_acquire()?.forget();
-        Ok(self.connection.open_bi().await.map(Into::into))
+        Ok(self.create_stream().await)
+    }
+
+    /// Opens stream in place of permit already taken from pool size. Permit is returned if stream isn't opened.
+    async fn create_stream(&self) -> Result<Stream, ConnectionError> {
+        let stream = self.connection.open_bi().await;
+        if stream.is_err() {
+            self.size.add_permits(1);
+        }
+
+        stream.map(Into::into)
     }
 
-    #[allow(clippy::undocumented_unsafe_blocks)]
     pub(super) async fn get(self: &Arc<Self>) -> Result<PooledStream, ConnectionError> {
         if let Ok(stream) = self.stream_receiver.try_recv() {
             return Ok(self.new_pooled_stream(stream));
@@ -97,14 +135,33 @@ impl StreamPool {
             return stream_creation_result.map(|stream| self.new_pooled_stream(stream));
         }
 
-        let stream = self.stream_receiver.recv_async().await;
-        Ok(self.new_pooled_stream(unsafe { stream.unwrap_unchecked() }))
+        // Broken streams free their place in pool instead of returning to it, so either may come first.
+        tokio::select! {
+            Ok(stream) = self.stream_receiver.recv_async() => Ok(self.new_pooled_stream(stream)),
+            Ok(permit) = self.size.acquire() => {
+                permit.forget();
+                self.create_stream().await.map(|stream| self.new_pooled_stream(stream))
+            }
+        }
+    }
+
+    /// Opens stream that doesn't count toward pool size and is closed once dropped.
+    pub(super) async fn get_dedicated(self: &Arc<Self>) -> Result<PooledStream, ConnectionError> {
+        let stream = self.connection.open_bi().await?;
+        Ok(PooledStream {
+            inner: MaybeUninit::new(stream.into()),
+            pool: Arc::clone(self),
+            dedicated: true,
+            broken: false,
+        })
     }
 
     fn new_pooled_stream(self: &Arc<Self>, stream: Stream) -> PooledStream {
         PooledStream {
             inner: MaybeUninit::new(stream),
             pool: Arc::clone(self),
+            dedicated: false,
+            broken: false,
         }
     }
 }
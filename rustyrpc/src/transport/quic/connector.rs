@@ -0,0 +1,53 @@
+use core::net::SocketAddr;
+use std::io;
+
+use quinn::{ClientConfig, Endpoint};
+
+use super::connection::ClientConnection;
+use crate::transport;
+
+/// Establishes connections to server via QUIC protocol.
+pub struct Connector {
+    endpoint: Endpoint,
+    address: SocketAddr,
+    server_name: Box<str>,
+    stream_pool_size: usize,
+}
+
+impl Connector {
+    /// Creates new connector bound to specified local address.
+    ///
+    /// # Errors
+    /// Returns error if client endpoint fails to bind.
+    pub fn new(
+        client_config: ClientConfig,
+        local_address: SocketAddr,
+        address: SocketAddr,
+        server_name: &str,
+        stream_pool_size: usize,
+    ) -> io::Result<Self> {
+        let mut endpoint = Endpoint::client(local_address)?;
+        endpoint.set_default_client_config(client_config);
+
+        Ok(Self {
+            endpoint,
+            address,
+            server_name: server_name.into(),
+            stream_pool_size,
+        })
+    }
+}
+
+impl transport::Connector for Connector {
+    type Connection = ClientConnection;
+
+    async fn connect(&self) -> io::Result<Self::Connection> {
+        let connection = self
+            .endpoint
+            .connect(self.address, &self.server_name)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?
+            .await?;
+
+        Ok(ClientConnection::new(connection, self.stream_pool_size))
+    }
+}
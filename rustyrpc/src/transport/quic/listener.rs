@@ -1,25 +1,64 @@
+use alloc::sync::Arc;
 use core::net::SocketAddr;
 use std::io;
 
-use quinn::{Endpoint, ServerConfig};
+use quinn::{Endpoint, ServerConfig, VarInt};
+use rustls::{
+    server::{AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient},
+    Certificate, PrivateKey, RootCertStore,
+};
 
 use super::connection::ServerConnection;
 
 /// Listener for incoming connections via QUIC protocol.
-pub struct ConnectionListener(quinn::Endpoint);
+pub struct ConnectionListener {
+    endpoint: quinn::Endpoint,
+    server_config: ServerConfig,
+}
 
 impl crate::transport::ConnectionListener for ConnectionListener {
     type Connection = ServerConnection;
 
     async fn accept_connection(&mut self) -> io::Result<Self::Connection> {
         Ok(self
-            .0
+            .endpoint
             .accept()
             .await
             .ok_or_else(|| io::Error::new(io::ErrorKind::NotConnected, "Endpoint is closed"))?
             .await
             .map(Into::into)?)
     }
+
+    /// Changes limit of concurrent bidirectional streams in transport config of server config, keeping its other settings.
+    fn set_max_concurrent_streams(&mut self, max_streams: u32) -> io::Result<()> {
+        // Endpoint holds copy of server config, so it's detached to make transport config unique.
+        self.endpoint.set_server_config(None);
+        let limited = Arc::get_mut(&mut self.server_config.transport)
+            .map(|transport| {
+                transport.max_concurrent_bidi_streams(VarInt::from_u32(max_streams));
+            })
+            .is_some();
+        self.endpoint
+            .set_server_config(Some(self.server_config.clone()));
+
+        if limited {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Transport config is shared with other server configs, so concurrent streams can't be limited",
+            ))
+        }
+    }
+}
+
+/// Whether clients must present certificate, see [`ConnectionListener::with_client_verification`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientVerification {
+    /// Clients without certificate are rejected.
+    Required,
+    /// Clients without certificate are accepted but have no [peer identity][crate::transport::PeerIdentity].
+    Optional,
 }
 
 impl ConnectionListener {
@@ -28,6 +67,40 @@ impl ConnectionListener {
     /// # Errors
     /// Returns if connection was failed to be accepted.
     pub fn new(server_config: ServerConfig, addr: SocketAddr) -> Result<Self, std::io::Error> {
-        Ok(Self(Endpoint::server(server_config, addr)?))
+        Ok(Self {
+            endpoint: Endpoint::server(server_config.clone(), addr)?,
+            server_config,
+        })
+    }
+
+    /// Creates new listener that verifies client certificates against trusted roots (mutual TLS).
+    /// Verified certificate chain is exposed as [peer identity][crate::transport::ServerConnection::peer_identity] of connection.
+    ///
+    /// # Errors
+    /// Returns error if server certificate or key is invalid or endpoint fails to bind.
+    pub fn with_client_verification(
+        certificate_chain: Vec<Certificate>,
+        private_key: PrivateKey,
+        client_roots: RootCertStore,
+        verification: ClientVerification,
+        addr: SocketAddr,
+    ) -> io::Result<Self> {
+        let client_verifier = match verification {
+            ClientVerification::Required => AllowAnyAuthenticatedClient::new(client_roots).boxed(),
+            ClientVerification::Optional => {
+                AllowAnyAnonymousOrAuthenticatedClient::new(client_roots).boxed()
+            }
+        };
+        let mut crypto = rustls::ServerConfig::builder()
+            .with_safe_default_cipher_suites()
+            .with_safe_default_kx_groups()
+            .with_protocol_versions(&[&rustls::version::TLS13])
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?
+            .with_client_cert_verifier(client_verifier)
+            .with_single_cert(certificate_chain, private_key)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        crypto.max_early_data_size = u32::MAX;
+
+        Self::new(ServerConfig::with_crypto(Arc::new(crypto)), addr)
     }
 }
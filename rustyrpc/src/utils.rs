@@ -3,15 +3,18 @@ use core::{
     ops::{Deref, DerefMut},
 };
 
+use log::debug;
+
 use crate::transport;
 
 pub(crate) struct ConnectionCloseOnDrop<Connection: transport::Connection>(pub(crate) Connection);
 
 impl<Connection: transport::Connection> OwnedDroppable for ConnectionCloseOnDrop<Connection> {
-    #[allow(clippy::unwrap_used)]
     fn drop_owned(self) {
         tokio::spawn(async move {
-            self.0.close().await.unwrap();
+            if let Err(err) = self.0.close().await {
+                debug!("Failed to close connection: {err}");
+            }
         });
     }
 }